@@ -0,0 +1,61 @@
+//! Deletion of a `node_modules` tree, plainly and with `--fast-delete`.
+//!
+//! Run with `cargo bench`.
+
+use std::path::{Path, PathBuf};
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rskill::Deleter;
+
+/// Packages in the tree.
+const PACKAGES: usize = 200;
+/// Files in each package.
+const FILES: usize = 20;
+
+fn modules(root: &Path, run: usize) -> PathBuf {
+    let modules = root.join(format!("project-{run}")).join("node_modules");
+    for package in 0..PACKAGES {
+        let lib = modules.join(format!("pkg-{package}")).join("lib");
+        std::fs::create_dir_all(&lib).unwrap();
+        for i in 0..FILES {
+            std::fs::write(lib.join(format!("{i}.js")), "x".repeat(512)).unwrap();
+        }
+    }
+    modules
+}
+
+fn delete(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let root = std::env::temp_dir().join(format!("rskill-bench-delete-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let mut group = c.benchmark_group("delete");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements((PACKAGES * FILES) as u64));
+
+    for (name, deleter) in [
+        ("plain", Deleter::default()),
+        ("fast", Deleter::default().with_fast(true)),
+    ] {
+        let mut run = 0;
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter_batched(
+                || {
+                    run += 1;
+                    modules(&root, run)
+                },
+                |modules| {
+                    let removal = runtime.block_on(async { deleter.delete(modules).await });
+                    let removal = removal.unwrap();
+                    removal.result.unwrap();
+                },
+                BatchSize::PerIteration,
+            );
+        });
+    }
+
+    group.finish();
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+criterion_group!(benches, delete);
+criterion_main!(benches);
@@ -0,0 +1,85 @@
+//! Scan throughput on wide and deep trees, with the default pool and with `--threads`.
+//!
+//! Run with `cargo bench`.
+
+use std::path::{Path, PathBuf};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rskill::{ScanOptions, Scanner};
+
+/// Projects directly below the root of the wide tree.
+const WIDE_PROJECTS: usize = 2000;
+/// Nesting levels of the deep tree, each with a project next to the next level.
+const DEEP_LEVELS: usize = 40;
+/// Branches of the deep tree.
+const DEEP_BRANCHES: usize = 8;
+
+fn project(dir: &Path) {
+    let modules = dir.join("node_modules").join("pkg").join("lib");
+    std::fs::create_dir_all(&modules).unwrap();
+    std::fs::write(dir.join("package.json"), "{}").unwrap();
+    for i in 0..4 {
+        std::fs::write(modules.join(format!("{i}.js")), "x".repeat(512)).unwrap();
+    }
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+}
+
+fn wide_tree(root: &Path) -> usize {
+    for i in 0..WIDE_PROJECTS {
+        project(&root.join(format!("project-{i}")));
+    }
+    WIDE_PROJECTS
+}
+
+fn deep_tree(root: &Path) -> usize {
+    for branch in 0..DEEP_BRANCHES {
+        let mut dir = root.join(format!("branch-{branch}"));
+        for level in 0..DEEP_LEVELS {
+            project(&dir);
+            dir = dir.join(format!("level-{level}"));
+        }
+    }
+    DEEP_BRANCHES * DEEP_LEVELS
+}
+
+fn fixture(name: &str, build: fn(&Path) -> usize) -> (PathBuf, usize) {
+    let root = std::env::temp_dir().join(format!("rskill-bench-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    let targets = build(&root);
+    (root, targets)
+}
+
+fn scan(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("scan");
+    group.sample_size(10);
+
+    for (name, build) in [
+        ("wide", wide_tree as fn(&Path) -> usize),
+        ("deep", deep_tree),
+    ] {
+        let (root, targets) = fixture(name, build);
+        group.throughput(Throughput::Elements(targets as u64));
+
+        for threads in [None, Some(1), Some(4)] {
+            let mut scanner = Scanner::new(ScanOptions::default());
+            if let Some(threads) = threads {
+                scanner = scanner.with_threads(threads).unwrap();
+            }
+            let label = threads.map_or(String::from("default"), |t| t.to_string());
+            group.bench_with_input(BenchmarkId::new(name, label), &root, |b, root| {
+                b.iter(|| {
+                    let (found, _) = runtime.block_on(scanner.scan(root)).unwrap();
+                    assert_eq!(found.len(), targets);
+                });
+            });
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
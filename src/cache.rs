@@ -0,0 +1,408 @@
+//! Results of the last scan, kept between runs so `--cached` can skip walking the tree again,
+//! and the tags and notes given to directories in the interface.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicUsize, Arc},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::{
+    fs::{find_workspace_root, home_dir, mark_active, measure},
+    scanner::{CancelToken, FoundDir, ScanEvent, ScanOptions},
+};
+
+/// What is remembered about each directory found.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedDir {
+    path: PathBuf,
+    size: u64,
+    /// Seconds since the Unix epoch at which the project was last modified.
+    parent_modified: u64,
+}
+
+fn modified_secs(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    modified
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// `$XDG_CACHE_HOME/rskill`, `%LOCALAPPDATA%\rskill` on Windows, or `~/.cache/rskill`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+    };
+    base.filter(|dir| dir.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(".cache")))
+        .map(|dir| dir.join("rskill"))
+}
+
+/// The cache file for scanning `start_dirs` for the targets of `options`. The results are
+/// stored filtered, so scans that walk, filter or measure differently don't share them.
+pub fn cache_path(start_dirs: &[PathBuf], options: &ScanOptions) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    start_dirs.hash(&mut hasher);
+    options_key(options).hash(&mut hasher);
+    cache_dir().map(|dir| dir.join(format!("scan-{:016x}.json", hasher.finish())))
+}
+
+/// A hash of the options that decide which directories a scan finds and how it measures them.
+pub fn options_key(options: &ScanOptions) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    // Only pacing and size budgets are left out: partial sizes aren't stored.
+    let ScanOptions {
+        targets,
+        exclude_hidden,
+        exclude_paths,
+        exclude_regex,
+        match_regex,
+        max_depth,
+        respect_ignore_files,
+        include_system,
+        require_manifest,
+        min_size,
+        older_than,
+        active_within,
+        skip_active,
+        follow_symlinks,
+        same_filesystem,
+        io_nice: _,
+        disk_usage,
+        caches,
+        size_budget: _,
+        use_atime,
+        include_nested,
+        project_roots,
+    } = options;
+    targets.hash(&mut hasher);
+    exclude_hidden.hash(&mut hasher);
+    exclude_paths.hash(&mut hasher);
+    exclude_regex.hash(&mut hasher);
+    match_regex.hash(&mut hasher);
+    max_depth.hash(&mut hasher);
+    respect_ignore_files.hash(&mut hasher);
+    include_system.hash(&mut hasher);
+    require_manifest.hash(&mut hasher);
+    min_size.hash(&mut hasher);
+    older_than.hash(&mut hasher);
+    active_within.hash(&mut hasher);
+    skip_active.hash(&mut hasher);
+    follow_symlinks.hash(&mut hasher);
+    same_filesystem.hash(&mut hasher);
+    disk_usage.hash(&mut hasher);
+    caches.hash(&mut hasher);
+    use_atime.hash(&mut hasher);
+    include_nested.hash(&mut hasher);
+    project_roots.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Stores the readable directories of a finished scan. Partial sizes are measured again next
+/// time.
+pub fn save(path: &Path, found: &[FoundDir]) -> io::Result<()> {
+    let entries: Vec<CachedDir> = found
+        .iter()
+        .filter(|dir| dir.error.is_none() && !dir.partial)
+        .filter_map(|dir| {
+            Some(CachedDir {
+                path: dir.path.clone(),
+                size: dir.size,
+                parent_modified: modified_secs(dir.path.parent()?)?,
+            })
+        })
+        .collect();
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_vec(&entries)?)
+}
+
+/// Loads the directories stored by [`save`]. Directories that no longer exist are left out,
+/// those whose project was modified since are measured again, and the filters of `options`
+/// are applied once more.
+pub fn load(path: &Path, options: &ScanOptions) -> io::Result<Vec<FoundDir>> {
+    let entries: Vec<CachedDir> = serde_json::from_slice(&std::fs::read(path)?)?;
+
+    Ok(entries
+        .into_par_iter()
+        .filter(|entry| entry.path.is_dir())
+        .map(|entry| {
+            let parent_modified = entry.path.parent().and_then(modified_secs);
+            // Files are read without their project changing, so access times are read again.
+            if options.use_atime || parent_modified != Some(entry.parent_modified) {
+                return measure(entry.path, options);
+            }
+
+            let modified = UNIX_EPOCH + Duration::from_secs(entry.parent_modified);
+            let mut dir = FoundDir::with_size(entry.path, entry.size, modified);
+            dir.workspace = find_workspace_root(&dir.path);
+            mark_active(&mut dir, options);
+            dir
+        })
+        .filter(|dir| dir.matches(options))
+        .collect())
+}
+
+/// A verdict given to a directory with `1`, `2` or `3`, restored by every later scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tag {
+    Keep,
+    Later,
+    Delete,
+}
+
+impl Tag {
+    pub fn label(self) -> &'static str {
+        match self {
+            Tag::Keep => "keep",
+            Tag::Later => "later",
+            Tag::Delete => "delete",
+        }
+    }
+}
+
+/// What the user noted about a directory in the interface, restored by every later scan.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredNote")]
+pub struct Note {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<Tag>,
+    /// Free text given with `n`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl Note {
+    pub fn is_empty(&self) -> bool {
+        self.tag.is_none() && self.text.is_none()
+    }
+}
+
+/// A [`Note`] as stored, which is just its tag in files of older versions.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredNote {
+    Tag(Tag),
+    Note {
+        #[serde(default)]
+        tag: Option<Tag>,
+        #[serde(default)]
+        text: Option<String>,
+    },
+}
+
+impl From<StoredNote> for Note {
+    fn from(stored: StoredNote) -> Self {
+        match stored {
+            StoredNote::Tag(tag) => Note {
+                tag: Some(tag),
+                text: None,
+            },
+            StoredNote::Note { tag, text } => Note { tag, text },
+        }
+    }
+}
+
+/// `tags.json` in the [cache directory](cache_dir), shared by every scan.
+pub fn tags_path() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("tags.json"))
+}
+
+/// The notes stored by [`update_notes`], by path. A missing file holds no notes.
+pub fn load_notes(path: &Path) -> io::Result<HashMap<PathBuf, Note>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Stores `changes` over the notes at `path`, where an empty note takes one away, and returns
+/// them all. Notes of other directories are kept as they are on disk, so that sessions running
+/// at the same time don't undo each other's. The file is replaced whole, never left half
+/// written.
+pub fn update_notes(
+    path: &Path,
+    changes: HashMap<PathBuf, Note>,
+) -> io::Result<HashMap<PathBuf, Note>> {
+    let mut notes = load_notes(path)?;
+    for (dir, note) in changes {
+        if note.is_empty() {
+            notes.remove(&dir);
+        } else {
+            notes.insert(dir, note);
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", std::process::id()));
+    let temporary = path.with_file_name(name);
+    std::fs::write(&temporary, serde_json::to_vec(&notes)?)?;
+    std::fs::rename(&temporary, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temporary);
+    })?;
+    Ok(notes)
+}
+
+/// Sends `found` as the events of a scan that has already finished.
+pub fn replay(found: Vec<FoundDir>) -> (UnboundedReceiver<ScanEvent>, Arc<AtomicUsize>) {
+    let (tx, rx) = unbounded_channel();
+    for dir in found {
+        let _ = tx.send(ScanEvent::Found(dir));
+    }
+    (rx, Arc::new(AtomicUsize::new(0)))
+}
+
+/// Passes the events of a running scan through, handing what it found to `on_complete` once it
+/// completes, on a blocking thread. Nothing is handed over if the receiving end goes away first
+/// or the scan was cancelled, since its results are incomplete.
+pub fn record(
+    mut rx: UnboundedReceiver<ScanEvent>,
+    cancel: CancelToken,
+    on_complete: impl FnOnce(Vec<FoundDir>) + Send + 'static,
+) -> UnboundedReceiver<ScanEvent> {
+    let (tx, forwarded) = unbounded_channel();
+    tokio::spawn(async move {
+        let mut found = Vec::new();
+        while let Some(event) = rx.recv().await {
+            if let ScanEvent::Found(dir) = &event {
+                found.push(dir.clone());
+            }
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+        if !cancel.is_cancelled() {
+            let _ = tokio::task::spawn_blocking(move || on_complete(found)).await;
+        }
+    });
+    forwarded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_load_revalidates_changed_projects() {
+        let root = TempDir::new("cache");
+        for project in ["same", "changed", "gone"] {
+            std::fs::create_dir_all(root.join(project).join("node_modules")).unwrap();
+        }
+        std::fs::write(root.join("changed/node_modules/index.js"), "12345").unwrap();
+
+        let options = ScanOptions::default();
+        let found: Vec<FoundDir> = ["same", "changed", "gone"]
+            .iter()
+            .map(|project| FoundDir {
+                size: 1000,
+                ..measure(root.join(project).join("node_modules"), &options)
+            })
+            .collect();
+        let cache = root.join("cache.json");
+        save(&cache, &found).unwrap();
+
+        std::fs::remove_dir_all(root.join("gone")).unwrap();
+        std::fs::File::open(root.join("changed"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let mut loaded = load(&cache, &options).unwrap();
+        loaded.sort_by(|a, b| a.path.cmp(&b.path));
+        let sizes: Vec<_> = loaded
+            .iter()
+            .map(|dir| (dir.path.strip_prefix(&root).unwrap(), dir.size))
+            .collect();
+        assert_eq!(
+            sizes,
+            [
+                (Path::new("changed/node_modules"), 5),
+                (Path::new("same/node_modules"), 1000)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cache_path_differs_per_filter() {
+        let dirs = [PathBuf::from("/work")];
+        let path = |options: ScanOptions| cache_path(&dirs, &options);
+        let default = path(ScanOptions::default());
+        assert_eq!(default, path(ScanOptions::default()));
+        for options in [
+            ScanOptions {
+                min_size: Some(1024),
+                ..ScanOptions::default()
+            },
+            ScanOptions {
+                exclude_paths: vec![String::from("**/vendor")],
+                ..ScanOptions::default()
+            },
+            ScanOptions {
+                match_regex: vec![String::from("app")],
+                ..ScanOptions::default()
+            },
+            ScanOptions {
+                max_depth: Some(2),
+                ..ScanOptions::default()
+            },
+            ScanOptions {
+                skip_active: true,
+                ..ScanOptions::default()
+            },
+        ] {
+            assert_ne!(path(options), default);
+        }
+        let paced = ScanOptions {
+            io_nice: true,
+            ..ScanOptions::default()
+        };
+        assert_eq!(path(paced), default, "pacing doesn't change what is found");
+    }
+
+    #[test]
+    fn test_notes_are_merged_with_those_on_disk() {
+        let dir = TempDir::new("tags");
+        let path = dir.join("tags.json");
+        assert!(load_notes(&path).unwrap().is_empty());
+        let (a, b) = (
+            PathBuf::from("/work/a/node_modules"),
+            PathBuf::from("/work/b/target"),
+        );
+        let tagged = |tag| Note {
+            tag: Some(tag),
+            text: None,
+        };
+
+        // Written by an older version.
+        std::fs::write(&path, r#"{"/work/a/node_modules":"keep"}"#).unwrap();
+        let noted = Note {
+            tag: Some(Tag::Delete),
+            text: Some(String::from("old prototype")),
+        };
+        let notes = update_notes(&path, HashMap::from([(b.clone(), noted.clone())])).unwrap();
+        assert_eq!(notes[&a], tagged(Tag::Keep));
+        assert_eq!(load_notes(&path).unwrap(), notes);
+
+        let notes = update_notes(&path, HashMap::from([(a.clone(), Note::default())])).unwrap();
+        assert_eq!(notes, HashMap::from([(b, noted)]));
+    }
+}
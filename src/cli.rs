@@ -1,11 +1,40 @@
-use chrono::{DateTime, Local};
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
 use crossterm::event::KeyCode;
-use fs_extra::dir::{DirEntryAttr, DirEntryValue};
 use std::time::{Duration, Instant};
-use std::{collections::HashMap, path::PathBuf, time::SystemTime};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::IsTerminal,
+    net::SocketAddr,
+    num::NonZeroUsize,
+    ops::{Deref, Range},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use crate::fs::is_dangerous;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    cache::{self, Note, Tag},
+    config::{add_exclude, config_path},
+    daemon::{self, ServiceKind},
+    deleter::{DeleteError, DeleteEvent, Deleter},
+    docker,
+    fs::{
+        count_locked_packages, dir_breakdown, exclude_glob, expand_home, find_workspace_root,
+        home_dir, is_nested_target, measure, nesting_depth, open_in_file_manager,
+        restore_from_trash, DirBreakdown, DiskSpace,
+    },
+    history::record_deletion,
+    notify,
+    output::{format_rate, format_size, shorten_path, OutputFormat, SizeUnit},
+    policy::{parse_protect, Policy, ProtectLevel, ProtectRule},
+    remote::{parse_remote, RemoteRoot},
+    restore,
+    scanner::{CancelToken, FoundDir, ManifestCheck, ScanOptions, SizeBudget, SkippedDir},
+    script,
+    theme::{no_color_requested, ColorOverrides, SizeBands, Theme, ThemeName},
+};
 
 #[derive(Debug, Clone, clap::ValueEnum)]
 #[value(rename_all = "kebab-case")]
@@ -13,135 +42,3543 @@ pub enum SortBy {
     Size,
     Path,
     LastMod,
+    /// By the name of the project directory, keeping the targets of each project together.
+    Project,
+}
+
+/// The name of the project directory holding `dir`, compared case-insensitively.
+fn project_name(dir: &FoundDir) -> String {
+    dir.path
+        .parent()
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+impl SortBy {
+    pub fn compare(&self, a: &FoundDir, b: &FoundDir) -> std::cmp::Ordering {
+        match self {
+            SortBy::Path => a
+                .path
+                .to_string_lossy()
+                .partial_cmp(&b.path.to_string_lossy())
+                .unwrap_or(std::cmp::Ordering::Equal),
+            SortBy::Size => b
+                .size
+                .partial_cmp(&a.size)
+                .unwrap_or(std::cmp::Ordering::Equal),
+            SortBy::LastMod => b.modified.cmp(&a.modified),
+            SortBy::Project => project_name(a)
+                .cmp(&project_name(b))
+                .then_with(|| a.path.cmp(&b.path)),
+        }
+    }
+
+    /// Name shown in the header, with `reversed` flipping the natural direction.
+    pub fn label(&self, reversed: bool) -> &'static str {
+        match (self, reversed) {
+            (SortBy::Size, false) => "size desc",
+            (SortBy::Size, true) => "size asc",
+            (SortBy::Path, false) => "path asc",
+            (SortBy::Path, true) => "path desc",
+            (SortBy::LastMod, false) => "last-mod desc",
+            (SortBy::LastMod, true) => "last-mod asc",
+            (SortBy::Project, false) => "project asc",
+            (SortBy::Project, true) => "project desc",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[value(rename_all = "kebab-case")]
+pub enum Profile {
+    Node,
+    Rust,
+    Python,
+    Java,
+    All,
+}
+
+impl Profile {
+    pub fn targets(&self) -> &'static [&'static str] {
+        match self {
+            Profile::Node => &["node_modules"],
+            Profile::Rust => &["target"],
+            Profile::Python => &[".venv", "__pycache__"],
+            Profile::Java => &["build", ".gradle"],
+            Profile::All => &[
+                "node_modules",
+                "target",
+                ".venv",
+                "__pycache__",
+                "build",
+                ".gradle",
+            ],
+        }
+    }
+}
+
+/// The tab a directory of `target_kind` is listed under: the profile the target belongs to, such
+/// as `node` for `node_modules`, or the target itself.
+pub fn ecosystem(target_kind: &str) -> String {
+    [Profile::Node, Profile::Rust, Profile::Python, Profile::Java]
+        .into_iter()
+        .find(|profile| profile.targets().contains(&target_kind))
+        .and_then(|profile| profile.to_possible_value())
+        .map_or_else(
+            || target_kind.to_string(),
+            |value| value.get_name().to_string(),
+        )
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DeleteState {
+    #[default]
+    Present,
+    /// Waiting for a free slot in the deletion queue.
+    Queued,
+    Deleting,
+    Deleted,
+    Failed(DeleteError),
 }
 
+/// A found directory together with its state in the interface.
 #[derive(Debug, Clone)]
 pub struct NodeModule {
-    pub path: PathBuf,
-    pub size: u64,
-    pub modified: i64,
-    pub deleted: bool,
-    pub is_dangerous: bool,
+    pub dir: FoundDir,
+    pub state: DeleteState,
+    /// Whether the deletion moves the directory to the trash rather than removing it.
+    pub to_trash: bool,
+    pub marked: bool,
+    /// The verdict given with `1`, `2` or `3`, kept between sessions.
+    pub tag: Option<Tag>,
+    /// The note typed after `n`, kept between sessions like the tag.
+    pub note: Option<String>,
+    /// The size is still being computed in the background.
+    pub sizing: bool,
+    /// Left out of the list, e.g. because it failed the size filter once measured.
+    pub hidden: bool,
+    /// How the [`Policy`] protects the directory from deletion.
+    pub protection: Option<ProtectLevel>,
+    /// Queued as part of the running [`DeleteBatch`].
+    pub in_batch: bool,
+    /// How long removing the directory took, once deleted.
+    pub delete_time: Option<Duration>,
+    /// Inside another target directory, listed under `--include-nested`. Its size is part of
+    /// that directory's, so it isn't counted again.
+    pub nested: bool,
+}
+
+impl From<FoundDir> for NodeModule {
+    fn from(dir: FoundDir) -> Self {
+        Self {
+            dir,
+            state: DeleteState::Present,
+            to_trash: false,
+            marked: false,
+            tag: None,
+            note: None,
+            sizing: false,
+            hidden: false,
+            protection: None,
+            in_batch: false,
+            delete_time: None,
+            nested: false,
+        }
+    }
+}
+
+impl Deref for NodeModule {
+    type Target = FoundDir;
+
+    fn deref(&self) -> &FoundDir {
+        &self.dir
+    }
 }
 
 impl NodeModule {
-    #[inline]
-    pub fn new(
-        path: PathBuf,
-        details: Option<(HashMap<DirEntryAttr, DirEntryValue>, SystemTime)>,
-    ) -> Self {
-        let (size, modified) = if let Some((attrs, parent_modified)) = details {
-            let size = attrs.get(&DirEntryAttr::Size).and_then(|v| match v {
-                DirEntryValue::U64(size) => Some(*size),
-                _ => None,
-            });
+    /// Whether the directory should be listed under `filter`.
+    fn is_shown(&self, filter: &str) -> bool {
+        !self.hidden && fuzzy_match(&self.path.to_string_lossy(), filter)
+    }
+
+    /// Whether the directory is gone or on its way out.
+    pub fn is_removed(&self) -> bool {
+        matches!(
+            self.state,
+            DeleteState::Queued | DeleteState::Deleting | DeleteState::Deleted
+        )
+    }
+
+    /// Whether the size is part of [`App::total_size`].
+    fn is_counted(&self) -> bool {
+        !self.sizing && self.error.is_none() && !self.nested
+    }
+}
 
-            let size = size.unwrap_or(0);
+/// Exit code when the scan found nothing to delete.
+pub const EXIT_NOTHING_FOUND: i32 = 3;
+/// Exit code when the session deleted directories, and none failed to.
+pub const EXIT_CLEANED: i32 = 4;
+/// Longest time between two clicks on a row for them to count as a double-click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+/// Columns the list keeps for a path however narrow the screen.
+const MIN_PATH_WIDTH: usize = 20;
+
+/// What an interactive session did, printed with `--summary`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct SessionSummary {
+    pub found: usize,
+    pub deleted: usize,
+    pub failed: usize,
+    /// Bytes freed by the directories deleted or trashed.
+    pub reclaimed: u64,
+}
 
-            (size, parent_modified)
+impl SessionSummary {
+    /// 1 if a deletion failed, [`EXIT_NOTHING_FOUND`] if there was nothing to delete,
+    /// [`EXIT_CLEANED`] if directories were deleted, and 0 if they were only looked at.
+    pub fn exit_code(&self) -> i32 {
+        if self.failed > 0 {
+            1
+        } else if self.found == 0 {
+            EXIT_NOTHING_FOUND
+        } else if self.deleted > 0 {
+            EXIT_CLEANED
         } else {
-            (0, SystemTime::now())
+            0
+        }
+    }
+}
+
+/// State of the `--pick-root` browser: the directory shown and its subdirectories.
+#[derive(Debug, Clone)]
+pub struct RootPicker {
+    pub dir: PathBuf,
+    /// Names of the subdirectories of `dir`, hidden ones last.
+    pub entries: Vec<String>,
+    pub selected: usize,
+    /// Why `dir` couldn't be listed, if it couldn't.
+    pub error: Option<String>,
+}
+
+/// What a key press in the [`RootPicker`] decided.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PickerAction {
+    Browse,
+    Scan(PathBuf),
+    Cancel,
+}
+
+impl RootPicker {
+    pub fn new(dir: PathBuf) -> Self {
+        let mut picker = Self {
+            dir,
+            entries: Vec::new(),
+            selected: 0,
+            error: None,
         };
+        picker.load();
+        picker
+    }
 
-        let modified = {
-            let local = DateTime::<Local>::from(modified);
-            let now = Local::now().signed_duration_since(local);
-            now.num_seconds()
+    fn load(&mut self) {
+        self.selected = 0;
+        self.error = None;
+        self.entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect(),
+            Err(e) => {
+                self.error = Some(e.kind().to_string());
+                Vec::new()
+            }
         };
+        self.entries
+            .sort_by_key(|name| (name.starts_with('.'), name.to_lowercase()));
+    }
 
-        NodeModule {
-            path: path.clone(),
-            size,
-            modified,
-            deleted: false,
-            is_dangerous: is_dangerous(&path),
+    fn open(&mut self, dir: PathBuf) {
+        let came_from = self
+            .dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        let going_up = self.dir.starts_with(&dir);
+        self.dir = dir;
+        self.load();
+        if going_up {
+            if let Some(position) =
+                came_from.and_then(|name| self.entries.iter().position(|e| *e == name))
+            {
+                self.selected = position;
+            }
+        }
+    }
+
+    pub fn on_key(&mut self, key: KeyCode) -> PickerAction {
+        let last = self.entries.len().saturating_sub(1);
+        match key {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Enter | KeyCode::Right => {
+                if let Some(name) = self.entries.get(self.selected) {
+                    let dir = self.dir.join(name);
+                    self.open(dir);
+                }
+            }
+            KeyCode::Left | KeyCode::Backspace => {
+                if let Some(parent) = self.dir.parent() {
+                    let parent = parent.to_path_buf();
+                    self.open(parent);
+                }
+            }
+            KeyCode::Char('s') => return PickerAction::Scan(self.dir.clone()),
+            KeyCode::Esc | KeyCode::Char('q') => return PickerAction::Cancel,
+            _ => {}
+        }
+        PickerAction::Browse
+    }
+}
+
+/// Progress of deletions queued together by `--delete-all` or by deleting the marked modules.
+#[derive(Debug, Clone)]
+pub struct DeleteBatch {
+    pub started: Instant,
+    pub total: usize,
+    pub total_bytes: u64,
+    /// Deletions finished, whether they succeeded or not.
+    pub completed: usize,
+    pub completed_bytes: u64,
+    pub failed: usize,
+    pub reclaimed: u64,
+    /// The directory whose removal started most recently.
+    pub current: Option<PathBuf>,
+}
+
+impl DeleteBatch {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            total: 0,
+            total_bytes: 0,
+            completed: 0,
+            completed_bytes: 0,
+            failed: 0,
+            reclaimed: 0,
+            current: None,
+        }
+    }
+
+    /// Share of the batch done, by size, between 0 and 1.
+    pub fn ratio(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return if self.total == 0 {
+                0.0
+            } else {
+                self.completed as f64 / self.total as f64
+            };
         }
+        (self.completed_bytes as f64 / self.total_bytes as f64).min(1.0)
     }
 
-    pub fn delete(&mut self) {
-        let path = self.path.clone();
-        self.deleted = true;
+    /// Bytes removed per second so far, once something was removed.
+    pub fn rate(&self) -> Option<String> {
+        (self.completed_bytes > 0)
+            .then(|| format_rate(self.completed_bytes, self.started.elapsed(), SizeUnit::Auto))
+    }
 
-        tokio::spawn(tokio::fs::remove_dir_all(path));
+    /// Time left at the rate bytes were removed so far, once something was removed.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.completed_bytes == 0 {
+            return None;
+        }
+        let remaining = self.total_bytes.saturating_sub(self.completed_bytes);
+        Some(
+            self.started
+                .elapsed()
+                .mul_f64(remaining as f64 / self.completed_bytes as f64),
+        )
     }
 }
 
 pub struct App {
+    /// Every module found so far, in discovery order. Entries are never removed or reordered, so
+    /// indices into it stay valid for the whole session.
     pub modules: Vec<NodeModule>,
     pub scroll: usize,
+    pub start: Instant,
     pub scan_time: Duration,
+    pub scanning: bool,
+    /// Stops the scan early, keeping what was found so far.
+    pub cancel: CancelToken,
+    pub dirs_scanned: usize,
+    /// Size of every readable module found so far.
+    pub total_size: u64,
     pub total_deleted: u64,
+    pub skipped: Vec<SkippedDir>,
+    pub show_skipped: bool,
+    pub show_help: bool,
+    /// The options the session was started with, listed in the help overlay.
+    pub flags: Vec<String>,
+    pub status: Option<(String, Instant)>,
+    pub disk: Option<DiskSpace>,
+    pub confirm_delete_marked: bool,
+    pub confirm_each: bool,
+    /// Index into `modules` waiting for confirmation under `--confirm-each` or a
+    /// `double-confirm` rule.
+    pub pending_delete: Option<usize>,
+    /// Times `y` must still be pressed to delete `pending_delete`.
+    pub confirmations_left: u8,
+    /// Indices into `modules` of the collapsed workspace waiting for confirmation to be
+    /// deleted together.
+    pub pending_workspace: Option<Vec<usize>>,
+    /// Index into `modules` just hidden with `x`, asking whether to exclude it for good.
+    pub pending_exclude: Option<usize>,
+    /// Index into `modules` whose deletion was denied, waiting for confirmation to remove it as
+    /// administrator.
+    pub pending_elevation: Option<usize>,
+    pub policy: Policy,
+    pub filter: String,
+    pub filtering: bool,
+    /// The directory names searched for.
+    pub targets: Vec<String>,
+    /// The names being typed after `t`, to scan for instead.
+    pub target_input: Option<String>,
+    /// Names typed after `t`, waiting for the interface to start scanning for them.
+    pub retarget: Option<Vec<String>>,
+    /// The note being typed after `n` for the highlighted directory.
+    pub note_input: Option<String>,
+    /// Indices into `modules` that match `filter`, in display order.
+    pub visible: Vec<usize>,
+    /// List the directories of each monorepo workspace as one row, toggled with `w`.
+    pub collapse_workspaces: bool,
+    /// Indices into `modules` of the directories found in each workspace, by its root.
+    workspaces: HashMap<PathBuf, Vec<usize>>,
+    pub sort: Option<SortBy>,
+    /// Flips the direction of `sort`, toggled by cycling with `s`.
+    pub sort_reversed: bool,
+    /// The [ecosystem] whose directories are listed, or `None` for all of them.
+    pub tab: Option<String>,
+    /// The sort of every tab left, restored when it is shown again.
+    tab_sorts: HashMap<Option<String>, (Option<SortBy>, bool)>,
+    pub auto_delete: bool,
+    pub theme: Theme,
+    /// Where sizes turn from the small to the medium and large colors.
+    pub size_bands: SizeBands,
+    /// Where `--delete-all` records each directory before deleting it.
+    pub restore_manifest: Option<PathBuf>,
+    /// Where `W` writes the marked directories as a [script](crate::script).
+    pub script_path: PathBuf,
+    /// Deletions queued together and still running, shown with a progress bar.
+    pub batch: Option<DeleteBatch>,
+    pub deleter: Deleter,
+    delete_tx: UnboundedSender<DeleteEvent<usize>>,
+    delete_rx: UnboundedReceiver<DeleteEvent<usize>>,
+    /// Indices into `modules` moved to the trash this session, most recent last.
+    pub trashed: Vec<usize>,
+    /// Index into `modules` of every directory whose size is still being computed.
+    sizing: HashMap<PathBuf, usize>,
+    /// Index into `modules` of every directory listed before a re-scan that it hasn't found
+    /// again yet.
+    stale: HashMap<PathBuf, usize>,
+    pub show_details: bool,
+    /// Show the largest directories as a bar chart in place of the list, toggled with `b`.
+    pub show_chart: bool,
+    /// Rows of the list that fit on screen, moved by PgUp/PgDn.
+    pub page_size: usize,
+    /// The first of [`App::rows`] on screen, kept across frames so that the list scrolls only
+    /// as far as the selection moves.
+    pub list_offset: usize,
+    /// Show paths whole instead of [shortened](shorten_path) to fit the list.
+    pub full_paths: bool,
+    /// Show when directories were last modified as dates instead of ages, toggled with `l`.
+    pub absolute_dates: bool,
+    /// The tag and note of every directory noted in this or an earlier session, by path.
+    pub notes: HashMap<PathBuf, Note>,
+    /// Where `notes` are saved whenever they change, if anywhere.
+    pub notes_path: Option<PathBuf>,
+    home: Option<PathBuf>,
+    /// The row last clicked and when, to tell double-clicks.
+    last_click: Option<(usize, Instant)>,
+    /// The system clipboard, opened on the first copy and kept open since on Linux, copied text
+    /// can only be pasted while its owner runs.
+    clipboard: Option<arboard::Clipboard>,
+    /// Breakdowns for the detail pane, keyed by index into `modules`. `None` while computing.
+    pub details: HashMap<usize, Option<Result<DirBreakdown, String>>>,
+    /// The module whose subdirectories are listed by size, opened with Enter.
+    pub drill_down: Option<usize>,
+    /// Highlighted row of the drill-down list.
+    pub drill_scroll: usize,
+    /// Breakdowns of the modules shown in the detail pane, with the packages their lockfile pins.
+    details_tx: UnboundedSender<(usize, Result<DirBreakdown, String>, Option<u64>)>,
+    details_rx: UnboundedReceiver<(usize, Result<DirBreakdown, String>, Option<u64>)>,
+    /// How directories with a partial size are measured again, without a size budget.
+    recount_options: Arc<ScanOptions>,
+    recount_tx: UnboundedSender<FoundDir>,
+    recount_rx: UnboundedReceiver<FoundDir>,
+    /// The roots given to `scan`, labelling the rows found below each when there are several.
+    pub roots: Vec<(String, PathBuf)>,
+}
+
+/// A row of the interactive list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListRow {
+    /// The module at this position of [`App::visible`].
+    Module(usize),
+    /// The subtotal of a project holding several of the modules listed.
+    Project {
+        path: PathBuf,
+        size: u64,
+        count: usize,
+    },
+}
+
+/// Case-insensitive subsequence match, so "apnm" matches "app/node_modules".
+pub fn fuzzy_match(haystack: &str, needle: &str) -> bool {
+    let mut haystack = haystack.chars().flat_map(char::to_lowercase);
+    needle
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|c| haystack.any(|h| h == c))
 }
 
 impl App {
-    pub fn new(modules: Vec<NodeModule>, start: Instant) -> Self {
+    pub fn new(args: &Args, disk: Option<DiskSpace>, start: Instant) -> Self {
+        let (delete_tx, delete_rx) = unbounded_channel();
+        let (details_tx, details_rx) = unbounded_channel();
+        let (recount_tx, recount_rx) = unbounded_channel();
+        let recount_options = ScanOptions {
+            size_budget: SizeBudget::default(),
+            ..ScanOptions::from(args)
+        };
         Self {
-            modules,
+            modules: Vec::new(),
             scroll: 0,
-            scan_time: start.elapsed(),
+            start,
+            scan_time: Duration::ZERO,
+            scanning: true,
+            cancel: CancelToken::default(),
+            dirs_scanned: 0,
+            total_size: 0,
             total_deleted: 0,
+            skipped: Vec::new(),
+            show_skipped: false,
+            show_help: false,
+            flags: args.flags_summary(),
+            status: None,
+            disk,
+            confirm_delete_marked: false,
+            confirm_each: args.confirm_each,
+            pending_delete: None,
+            confirmations_left: 0,
+            pending_workspace: None,
+            pending_exclude: None,
+            pending_elevation: None,
+            policy: Policy::new(&args.protect, args.allow_global),
+            filter: String::new(),
+            filtering: false,
+            targets: args.targets(),
+            target_input: None,
+            retarget: None,
+            note_input: None,
+            visible: Vec::new(),
+            collapse_workspaces: true,
+            workspaces: HashMap::new(),
+            sort: args.sort.clone(),
+            sort_reversed: false,
+            tab: None,
+            tab_sorts: HashMap::new(),
+            auto_delete: args.delete_all,
+            theme: Theme::resolve(args.theme, &args.colors, no_color_requested()),
+            size_bands: args.size_bands.unwrap_or_default(),
+            // Only projects on this machine can be reinstalled from the manifest.
+            restore_manifest: args
+                .restore_manifest
+                .clone()
+                .filter(|_| args.deleter().is_local()),
+            script_path: args
+                .emit_script
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(script::DEFAULT_PATH)),
+            batch: None,
+            deleter: args.deleter(),
+            delete_tx,
+            delete_rx,
+            trashed: Vec::new(),
+            sizing: HashMap::new(),
+            stale: HashMap::new(),
+            show_details: false,
+            show_chart: false,
+            page_size: 10,
+            list_offset: 0,
+            full_paths: false,
+            absolute_dates: false,
+            notes: HashMap::new(),
+            notes_path: None,
+            home: home_dir(),
+            last_click: None,
+            clipboard: None,
+            details: HashMap::new(),
+            drill_down: None,
+            drill_scroll: 0,
+            details_tx,
+            details_rx,
+            recount_options: Arc::new(recount_options),
+            recount_tx,
+            recount_rx,
+            roots: args.roots().unwrap_or_default(),
         }
     }
 
-    pub fn on_key(&mut self, key: KeyCode) {
+    /// The name of the root `path` was found below, when several roots are scanned.
+    pub fn root_label(&self, path: &Path) -> Option<&str> {
+        if self.roots.len() < 2 {
+            return None;
+        }
+        self.roots
+            .iter()
+            .filter(|(_, root)| path.starts_with(root))
+            .max_by_key(|(_, root)| root.components().count())
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Adds a module found by the scanner, deleting it straight away under `--delete-all`.
+    pub fn push_module(&mut self, module: NodeModule) {
+        let index = self.modules.len();
+        let protection = self.policy.level(&module.path);
+        let note = self.notes.get(&module.path).cloned().unwrap_or_default();
+        let nested = is_nested_target(&module.path, &self.targets);
+        self.modules.push(NodeModule {
+            protection,
+            tag: note.tag,
+            note: note.text,
+            nested,
+            ..module
+        });
+        self.on_sized(index);
+
+        if let Some(root) = &self.modules[index].workspace {
+            self.workspaces.entry(root.clone()).or_default().push(index);
+        }
+        if self.is_listed(index) {
+            self.insert_visible(index);
+        }
+    }
+
+    /// Lists a directory the scanner just matched, before its size is known. A directory already
+    /// listed before a re-scan keeps its row until it is measured again.
+    pub fn push_pending(&mut self, path: PathBuf) {
+        if let Some(index) = self.stale.remove(&path) {
+            self.sizing.insert(path, index);
+            return;
+        }
+        self.sizing.insert(path.clone(), self.modules.len());
+        // Matched directories are local, unlike those of docker or --ssh.
+        let workspace = find_workspace_root(&path);
+        self.push_module(NodeModule {
+            sizing: true,
+            ..NodeModule::from(FoundDir {
+                workspace,
+                ..FoundDir::pending(path)
+            })
+        });
+    }
+
+    /// Fills in the details of a directory listed by [`push_pending`](Self::push_pending),
+    /// moving it to its sorted position.
+    pub fn update_module(&mut self, dir: FoundDir) {
+        let index = self
+            .sizing
+            .remove(&dir.path)
+            .or_else(|| self.stale.remove(&dir.path));
+        let Some(index) = index else {
+            self.push_module(dir.into());
+            return;
+        };
+
+        let module = &self.modules[index];
+        if module.is_counted() {
+            self.total_size -= module.size;
+        }
+        self.details.remove(&index);
+        // Stays in the workspace it was grouped with when first listed.
+        let workspace = self.modules[index].dir.workspace.take();
+        self.modules[index].dir = FoundDir { workspace, ..dir };
+        self.modules[index].sizing = false;
+        self.on_sized(index);
+
+        let was_selected = self.remove_visible(index);
+        if self.is_listed(index) {
+            let position = self.insert_visible(index);
+            if was_selected {
+                self.scroll = position;
+            }
+        }
+    }
+
+    /// Hides a directory listed by [`push_pending`](Self::push_pending) that failed the filters.
+    pub fn drop_module(&mut self, path: &Path) {
+        if let Some(index) = self.sizing.remove(path) {
+            self.modules[index].hidden = true;
+            self.remove_visible(index);
+            self.relist_workspace(index);
+        }
+    }
+
+    /// Counts a module whose size is known, deleting it straight away under `--delete-all`.
+    fn on_sized(&mut self, index: usize) {
+        let module = &self.modules[index];
+        if module.sizing || module.error.is_some() {
+            return;
+        }
+
+        if module.is_counted() {
+            self.total_size += module.size;
+        }
+        if !self.auto_delete
+            || module
+                .protection
+                .is_some_and(ProtectLevel::blocks_bulk_delete)
+        {
+            return;
+        }
+
+        let recorded = self
+            .restore_manifest
+            .as_deref()
+            .map_or(Ok(()), |manifest| restore::append(manifest, module));
+        if let Err(e) = recorded {
+            let message = format!(
+                "Kept {}: could not write the restore manifest: {e}",
+                module.path.display()
+            );
+            self.set_status(message);
+            return;
+        }
+        self.delete_in_batch(index);
+    }
+
+    /// Puts `index` at its sorted position in `visible`, keeping the highlighted row selected,
+    /// and returns that position.
+    fn insert_visible(&mut self, index: usize) -> usize {
+        let position = self
+            .visible
+            .partition_point(|&i| self.compare(i, index).is_le());
+        if position <= self.scroll && !self.visible.is_empty() {
+            self.scroll += 1;
+        }
+        self.visible.insert(position, index);
+        position
+    }
+
+    /// Takes `index` out of `visible`, keeping the highlighted row selected where possible, and
+    /// returns whether it was the highlighted one.
+    fn remove_visible(&mut self, index: usize) -> bool {
+        let Some(position) = self.visible.iter().position(|&i| i == index) else {
+            return false;
+        };
+
+        let was_selected = position == self.scroll;
+        self.visible.remove(position);
+        if position < self.scroll {
+            self.scroll -= 1;
+        }
+        self.scroll = self.scroll.min(self.visible.len().saturating_sub(1));
+        was_selected
+    }
+
+    /// Lists the directory standing for the workspace of `index` anew after `index` left the
+    /// list, since it may have been the one.
+    fn relist_workspace(&mut self, index: usize) {
+        let Some(root) = &self.modules[index].workspace else {
+            return;
+        };
+        for member in self.workspaces[root].clone() {
+            if self.is_listed(member) && !self.visible.contains(&member) {
+                self.insert_visible(member);
+            }
+        }
+    }
+
+    /// Marks the scan as done. Directories listed before a re-scan that it didn't find again
+    /// are gone, and leave the list.
+    pub fn finish_scan(&mut self) {
+        self.scanning = false;
+        self.scan_time = self.start.elapsed();
+        let mut gone: Vec<usize> = self.stale.drain().map(|(_, index)| index).collect();
+        gone.sort_unstable();
+        for index in gone {
+            let module = &mut self.modules[index];
+            if module.is_counted() {
+                self.total_size -= module.size;
+            }
+            module.dir.error = Some(String::from("no longer exists"));
+            module.hidden = true;
+            module.marked = false;
+            self.remove_visible(index);
+            self.relist_workspace(index);
+        }
+    }
+
+    /// Starts over with the results of a new scan, keeping the rows, marks, filter, tab and
+    /// highlighted row of the directories it finds again.
+    pub fn restart_scan(&mut self, cancel: CancelToken) {
+        self.stale = self
+            .modules
+            .iter()
+            .enumerate()
+            // Rows of directories already gone stay gone.
+            .filter(|(_, m)| !(m.is_removed() || m.hidden && m.error.is_some()))
+            .map(|(i, m)| (m.path.clone(), i))
+            .collect();
+        self.sizing.clear();
+        self.skipped.clear();
+        self.cancel = cancel;
+        self.scanning = true;
+        self.start = Instant::now();
+        self.dirs_scanned = 0;
+    }
+
+    /// Starts over with the results of a scan for `targets`, [keeping](Self::restart_scan) the
+    /// rows of those searched for before too and hiding the others.
+    pub fn retarget_scan(&mut self, targets: Vec<String>, cancel: CancelToken) {
+        for index in 0..self.modules.len() {
+            let module = &self.modules[index];
+            if targets.contains(&module.target_kind) || module.hidden {
+                continue;
+            }
+            if module.is_counted() {
+                self.total_size -= module.size;
+            }
+            let module = &mut self.modules[index];
+            // Left out of the rows the new scan may find again, like directories already gone.
+            module.dir.error = Some(String::from("not searched for"));
+            module.hidden = true;
+            module.marked = false;
+            self.remove_visible(index);
+            self.relist_workspace(index);
+        }
+        self.targets = targets;
+        self.restart_scan(cancel);
+    }
+
+    /// Stops the walkers. The scan finishes once the directories already being measured are done.
+    pub fn cancel_scan(&mut self) {
+        if self.scanning && !self.cancel.is_cancelled() {
+            self.cancel.cancel();
+            self.set_status(String::from(
+                "Scan cancelled, showing the directories found so far",
+            ));
+        }
+    }
+
+    /// Orders two indices into `modules` by the active sort, falling back to discovery order.
+    fn compare(&self, a: usize, b: usize) -> std::cmp::Ordering {
+        self.sort
+            .as_ref()
+            .map_or(std::cmp::Ordering::Equal, |sort| {
+                let ordering = sort.compare(&self.modules[a], &self.modules[b]);
+                if self.sort_reversed {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .then(a.cmp(&b))
+    }
+
+    /// Moves to the next sort order (each key in both directions) and re-sorts in place,
+    /// keeping the highlighted module selected.
+    pub fn cycle_sort(&mut self) {
+        (self.sort, self.sort_reversed) = match (&self.sort, self.sort_reversed) {
+            (None, _) => (Some(SortBy::Size), false),
+            (Some(sort), false) => (Some(sort.clone()), true),
+            (Some(SortBy::Size), true) => (Some(SortBy::Path), false),
+            (Some(SortBy::Path), true) => (Some(SortBy::LastMod), false),
+            (Some(SortBy::LastMod), true) => (Some(SortBy::Project), false),
+            (Some(SortBy::Project), true) => (Some(SortBy::Size), false),
+        };
+        self.apply_filter();
+    }
+
+    /// Whether the module at `index` should be listed under the filter and the current tab.
+    /// Of a collapsed workspace, only the first directory found is, standing for the others.
+    fn is_listed(&self, index: usize) -> bool {
+        self.matches_view(index)
+            && self.workspace_root(index).is_none_or(|root| {
+                self.workspaces[root]
+                    .iter()
+                    .find(|&&i| self.matches_view(i))
+                    == Some(&index)
+            })
+    }
+
+    /// Whether the module at `index` matches the filter and the current tab.
+    fn matches_view(&self, index: usize) -> bool {
+        let module = &self.modules[index];
+        module.is_shown(&self.filter)
+            && self
+                .tab
+                .as_ref()
+                .is_none_or(|tab| *tab == ecosystem(&module.target_kind))
+    }
+
+    /// The root of the workspace of `index`, while workspaces are collapsed.
+    fn workspace_root(&self, index: usize) -> Option<&PathBuf> {
+        self.modules[index]
+            .workspace
+            .as_ref()
+            .filter(|_| self.collapse_workspaces)
+    }
+
+    /// The root and the directories of the collapsed workspace the listed module at `index`
+    /// stands for, when it stands for more than itself.
+    pub fn workspace_members(&self, index: usize) -> Option<(&Path, Vec<usize>)> {
+        let root = self.workspace_root(index)?;
+        let members: Vec<usize> = self.workspaces[root]
+            .iter()
+            .copied()
+            .filter(|&i| self.matches_view(i))
+            .collect();
+        (members.len() > 1).then_some((root.as_path(), members))
+    }
+
+    /// Indices into `modules` of the `count` largest directories matching the filter and the
+    /// current tab that are still there, largest first. Those of collapsed workspaces count
+    /// one by one.
+    pub fn largest(&self, count: usize) -> Vec<usize> {
+        let mut largest: Vec<usize> = (0..self.modules.len())
+            .filter(|&i| self.matches_view(i))
+            .filter(|&i| {
+                let module = &self.modules[i];
+                !module.sizing && module.error.is_none() && !module.is_removed()
+            })
+            .collect();
+        largest.sort_by_key(|&i| std::cmp::Reverse(self.modules[i].size));
+        largest.truncate(count);
+        largest
+    }
+
+    /// Lists the directories of each workspace one by one, or collapses them again.
+    pub fn toggle_workspaces(&mut self) {
+        let selected = self.selected();
+        self.collapse_workspaces = !self.collapse_workspaces;
+        self.apply_filter();
+        // A directory collapsed away leaves the highlight on the row standing for it.
+        if let Some(index) = selected.filter(|i| !self.visible.contains(i)) {
+            let workspace = &self.modules[index].workspace;
+            if let Some(position) = self
+                .visible
+                .iter()
+                .position(|&i| self.modules[i].workspace == *workspace)
+            {
+                self.scroll = position;
+            }
+        }
+    }
+
+    /// The ecosystems of the modules found so far with the size of each, in the order of their
+    /// tabs after the one listing everything.
+    pub fn tabs(&self) -> Vec<(String, u64)> {
+        let mut sizes: BTreeMap<String, u64> = BTreeMap::new();
+        for module in self.modules.iter().filter(|m| !m.hidden) {
+            let size = sizes.entry(ecosystem(&module.target_kind)).or_default();
+            if !module.sizing && module.error.is_none() {
+                *size += module.size;
+            }
+        }
+        sizes.into_iter().collect()
+    }
+
+    /// Shows the tab `step` places away, wrapping around. Each tab keeps its own sort, starting
+    /// from the one of the tab left the first time it is shown.
+    pub fn switch_tab(&mut self, step: isize) {
+        let tabs: Vec<Option<String>> = std::iter::once(None)
+            .chain(self.tabs().into_iter().map(|(name, _)| Some(name)))
+            .collect();
+        let current = tabs.iter().position(|tab| *tab == self.tab).unwrap_or(0);
+        let next = (current as isize + step).rem_euclid(tabs.len() as isize) as usize;
+
+        let sort = (self.sort.clone(), self.sort_reversed);
+        self.tab_sorts.insert(self.tab.take(), sort.clone());
+        self.tab = tabs[next].clone();
+        (self.sort, self.sort_reversed) = self.tab_sorts.get(&self.tab).cloned().unwrap_or(sort);
+        self.apply_filter();
+    }
+
+    /// Whether a prompt or popup is open and should receive every key press.
+    pub fn captures_input(&self) -> bool {
+        self.show_skipped
+            || self.show_help
+            || self.drill_down.is_some()
+            || self.confirm_delete_marked
+            || self.pending_delete.is_some()
+            || self.pending_workspace.is_some()
+            || self.pending_exclude.is_some()
+            || self.pending_elevation.is_some()
+            || self.target_input.is_some()
+            || self.note_input.is_some()
+            || self.filtering
+    }
+
+    /// The keys that matter in the current state, for the status bar.
+    pub fn key_hints(&self) -> &'static str {
+        if self.show_help {
+            "any key: close"
+        } else if self.drill_down.is_some() {
+            "↑/↓ move · Esc back"
+        } else if self.show_skipped {
+            "Esc close"
+        } else if self.confirm_delete_marked
+            || self.pending_delete.is_some()
+            || self.pending_workspace.is_some()
+            || self.pending_exclude.is_some()
+            || self.pending_elevation.is_some()
+        {
+            "y confirm · any other key: cancel"
+        } else if self.filtering {
+            "Enter keep · Esc clear"
+        } else if self.target_input.is_some() {
+            "Enter scan for these names, separated by commas · Esc cancel"
+        } else if self.note_input.is_some() {
+            "Enter save the note, empty to remove it · Esc cancel"
+        } else if self.scanning {
+            "Space delete · m mark · / filter · Esc stop the scan · ? help"
+        } else if self.modules.iter().any(|m| m.marked) {
+            "d delete marked · A clear marks · W write a script · m mark · ? help · q quit"
+        } else if self.selected().is_some_and(|i| self.modules[i].partial) {
+            "F count fully · Space delete · m mark · ? help · q quit"
+        } else {
+            "Space delete · m mark · / filter · s sort · ? help · q quit"
+        }
+    }
+
+    /// The filter, tab and sort in effect and the deletions still pending, for the status bar.
+    pub fn state_summary(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.filter.is_empty() {
+            parts.push(format!("Filter: {}", self.filter));
+        }
+        if let Some(tab) = &self.tab {
+            parts.push(format!("Tab: {tab}"));
+        }
+        if let Some(sort) = &self.sort {
+            parts.push(format!("Sort: {}", sort.label(self.sort_reversed)));
+        }
+        match self.deletions_in_flight() {
+            0 => {}
+            pending => parts.push(format!("Pending deletions: {pending}")),
+        }
+        parts.join(" · ")
+    }
+
+    /// Index into `modules` of the highlighted entry.
+    pub fn selected(&self) -> Option<usize> {
+        self.visible.get(self.scroll).copied()
+    }
+
+    pub fn apply_filter(&mut self) {
+        let selected = self.selected();
+        let mut visible: Vec<usize> = (0..self.modules.len())
+            .filter(|&i| self.is_listed(i))
+            .collect();
+        visible.sort_by(|&a, &b| self.compare(a, b));
+        self.visible = visible;
+        self.scroll = selected
+            .and_then(|selected| self.visible.iter().position(|&i| i == selected))
+            .unwrap_or(0);
+    }
+
+    fn on_filter_key(&mut self, key: KeyCode) {
         match key {
-            KeyCode::Up if self.scroll > 0 => self.scroll -= 1,
-            KeyCode::Down if self.scroll < self.modules.len().saturating_sub(1) => self.scroll += 1,
-            KeyCode::Char(' ') => {
-                self.delete_module();
+            KeyCode::Esc => {
+                self.filtering = false;
+                self.filter.clear();
             }
-            _ => {}
+            KeyCode::Enter => self.filtering = false,
+            KeyCode::Backspace => {
+                self.filter.pop();
+            }
+            KeyCode::Char(c) => self.filter.push(c),
+            _ => return,
         }
+        self.apply_filter();
     }
 
-    pub fn delete_module(&mut self) {
-        if let Some(module) = self.modules.get_mut(self.scroll) {
-            if module.deleted {
-                return;
+    fn on_target_key(&mut self, key: KeyCode) {
+        let Some(input) = &mut self.target_input else {
+            return;
+        };
+        match key {
+            KeyCode::Esc => self.target_input = None,
+            KeyCode::Enter => {
+                let targets: Vec<String> = input
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|target| !target.is_empty())
+                    .map(String::from)
+                    .collect();
+                if !targets.is_empty() && targets != self.targets {
+                    self.retarget = Some(targets);
+                }
+                self.target_input = None;
+            }
+            KeyCode::Backspace => {
+                input.pop();
             }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
+    }
+
+    pub fn set_status(&mut self, message: String) {
+        self.status = Some((message, Instant::now()));
+    }
 
-            module.delete();
-            self.total_deleted += module.size;
+    /// Whether the listed directories are on this machine. Otherwise says that `action` isn't
+    /// available for those of Docker or `--ssh`.
+    pub fn is_local(&mut self, action: &str) -> bool {
+        let local = self.deleter.is_local();
+        if !local {
+            self.set_status(format!(
+                "{action} only works on local directories, not those of Docker or --ssh"
+            ));
         }
+        local
     }
-}
 
-#[derive(Parser, Debug, Clone)]
-#[command(version, about, long_about = None)]
-pub struct Args {
-    ///Set the directory from which to begin searching. By default, starting-point is .
-    #[arg(short, long, default_value_t = String::from("."))]
-    pub directory: String,
+    pub fn summary(&self) -> SessionSummary {
+        let count = |state: fn(&DeleteState) -> bool| {
+            self.modules.iter().filter(|m| state(&m.state)).count()
+        };
+        SessionSummary {
+            found: self.modules.len(),
+            deleted: count(|state| *state == DeleteState::Deleted),
+            failed: count(|state| matches!(state, DeleteState::Failed(_))),
+            reclaimed: self.total_deleted,
+        }
+    }
 
-    ///Exclude directories from search (directory list must be inside double quotes "", each directory separated by ',' ) Example: "ignore1, ignore2"
-    #[arg(
-        short = 'x',
-        long = "exclude-hidden-directories",
-        default_value_t = false
-    )]
-    pub exclude_hidden: bool,
+    pub fn selected_project(&self) -> Option<PathBuf> {
+        self.selected()
+            .and_then(|i| self.modules.get(i))
+            .and_then(|m| m.path.parent())
+            .map(PathBuf::from)
+    }
 
-    ///Specify the name of the directories you want to search (by default, is node_modules)
-    #[arg(short, long, default_value_t = String::from("node_modules"))]
-    pub target: String,
+    pub fn open_parent(&mut self) {
+        let Some(parent) = self.selected_project() else {
+            return;
+        };
+        if !self.is_local("Opening the project") {
+            return;
+        }
 
-    ///Start searching from the home of the user (example: "/home/user" in linux)
-    #[arg(short, long, default_value_t = false)]
-    pub full: bool,
+        match open_in_file_manager(&parent) {
+            Ok(()) => self.set_status(format!("Opened {}", parent.display())),
+            Err(e) => self.set_status(format!("Could not open {}: {e}", parent.display())),
+        }
+    }
 
-    ///Show folders in Gigabytes instead of Megabytes.
-    #[arg(long = "gb", default_value_t = false)]
-    pub in_gb: bool,
+    pub fn on_key(&mut self, key: KeyCode) {
+        if self.show_help {
+            self.show_help = false;
+            return;
+        }
 
-    ///Exclude directories from search (directory list must be inside double quotes "", each directory separated by ',' ) Example: "ignore1, ignore2"
-    #[arg(long = "exclude", short = 'E')]
-    pub exclude_paths: Option<String>,
+        if self.drill_down.is_some() {
+            self.on_drill_down_key(key);
+            return;
+        }
 
-    /// Sort results by: size, path or last-mod
-    #[arg(long, short, value_enum)]
-    pub sort: Option<SortBy>,
+        if self.show_skipped {
+            if matches!(key, KeyCode::Char('E') | KeyCode::Esc) {
+                self.show_skipped = false;
+            }
+            return;
+        }
 
-    /// Automatically delete all node_modules folders that are found. Suggested to be used together with -x.
-    #[arg(long = "delete-all", short = 'D')]
-    pub delete_all: bool,
+        if self.confirm_delete_marked {
+            if key == KeyCode::Char('y') {
+                self.delete_marked();
+            }
+            self.confirm_delete_marked = false;
+            return;
+        }
+
+        if let Some(members) = self.pending_workspace.take() {
+            if key == KeyCode::Char('y') {
+                self.delete_together(members);
+            }
+            return;
+        }
+
+        if let Some(index) = self.pending_delete.take() {
+            if key == KeyCode::Char('y') {
+                self.confirmations_left = self.confirmations_left.saturating_sub(1);
+                if self.confirmations_left > 0 {
+                    self.pending_delete = Some(index);
+                } else {
+                    self.delete_at(index);
+                }
+            }
+            return;
+        }
+
+        if let Some(index) = self.pending_exclude.take() {
+            if key == KeyCode::Char('y') {
+                self.exclude_permanently(index);
+            }
+            return;
+        }
+
+        // `y` is taken by the interface, which must hand over the terminal first.
+        if self.pending_elevation.take().is_some() {
+            return;
+        }
+
+        if self.filtering {
+            self.on_filter_key(key);
+            return;
+        }
+
+        if self.target_input.is_some() {
+            self.on_target_key(key);
+            return;
+        }
+
+        if self.note_input.is_some() {
+            self.on_note_key(key);
+            return;
+        }
+
+        if self.show_chart && Self::is_row_key(key) {
+            return self.set_status(String::from("The chart hides the list, b shows it again"));
+        }
+
+        match key {
+            KeyCode::Char('/') => self.filtering = true,
+            KeyCode::Esc if !self.filter.is_empty() => {
+                self.filter.clear();
+                self.apply_filter();
+            }
+            KeyCode::Esc => self.cancel_scan(),
+            KeyCode::Char('E') => self.show_skipped = true,
+            KeyCode::Char('?') => self.show_help = true,
+            KeyCode::Char('T') => self.toggle_trash(),
+            KeyCode::Char('u') => self.restore_last(),
+            KeyCode::Char('r') => self.retry_selected(),
+            KeyCode::Char('S') => self.request_elevation(),
+            KeyCode::Char('m') => self.toggle_mark(),
+            KeyCode::Tab => self.show_details = !self.show_details,
+            KeyCode::Char('b') => self.show_chart = !self.show_chart,
+            KeyCode::Char('a') => self.set_all_marks(true),
+            KeyCode::Char('A') => self.set_all_marks(false),
+            KeyCode::Char('d') | KeyCode::Char('D') if self.marked_summary().0 > 0 => {
+                self.confirm_delete_marked = true
+            }
+            KeyCode::Char('o') => self.open_parent(),
+            KeyCode::Char('y') => self.copy_selected_path(),
+            KeyCode::Char('Y') => self.copy_marked_paths(),
+            KeyCode::Char('W') => self.write_script(),
+            KeyCode::Char('t') if self.scanning => self.set_status(String::from(
+                "Wait for the scan to finish to search for other directories",
+            )),
+            KeyCode::Char('t') => self.target_input = Some(self.targets.join(",")),
+            KeyCode::Char('x') => self.hide_selected(),
+            KeyCode::Enter => self.open_drill_down(),
+            KeyCode::Char('s') => self.cycle_sort(),
+            KeyCode::Char('f') => self.full_paths = !self.full_paths,
+            KeyCode::Char('l') => self.absolute_dates = !self.absolute_dates,
+            KeyCode::Char('1') => self.tag_selected(Tag::Keep),
+            KeyCode::Char('2') => self.tag_selected(Tag::Later),
+            KeyCode::Char('3') => self.tag_selected(Tag::Delete),
+            KeyCode::Char('n') => self.edit_note(),
+            KeyCode::Char('w') => self.toggle_workspaces(),
+            KeyCode::Char('F') => self.recount_selected(),
+            KeyCode::Left => self.switch_tab(-1),
+            KeyCode::Right => self.switch_tab(1),
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-(self.page_size as isize)),
+            KeyCode::PageDown => self.move_selection(self.page_size as isize),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => self.scroll = self.visible.len().saturating_sub(1),
+            KeyCode::Char(' ') => {
+                self.delete_module();
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `key` moves the highlight or acts on the highlighted rows, which the chart hides.
+    pub fn is_row_key(key: KeyCode) -> bool {
+        match key {
+            KeyCode::Char(c) => " 123aAdDeFgGmnoprSWxyY".contains(c),
+            KeyCode::Up
+            | KeyCode::Down
+            | KeyCode::PageUp
+            | KeyCode::PageDown
+            | KeyCode::Home
+            | KeyCode::End
+            | KeyCode::Enter => true,
+            _ => false,
+        }
+    }
+
+    /// Takes the highlighted module out of the list, then asks whether to exclude it from future
+    /// scans too.
+    pub fn hide_selected(&mut self) {
+        let Some(index) = self.selected() else {
+            return;
+        };
+        if let Some((root, members)) = self.workspace_members(index) {
+            let message = format!("Hid {} directories of {}", members.len(), root.display());
+            for member in members {
+                self.modules[member].hidden = true;
+                self.modules[member].marked = false;
+            }
+            self.remove_visible(index);
+            return self.set_status(message);
+        }
+
+        self.modules[index].hidden = true;
+        self.modules[index].marked = false;
+        self.remove_visible(index);
+        // The config only excludes paths of this machine.
+        if self.deleter.is_local() {
+            self.pending_exclude = Some(index);
+        }
+    }
+
+    /// Adds the path of `index` to the `exclude` list of the config file.
+    fn exclude_permanently(&mut self, index: usize) {
+        let path = &self.modules[index].path;
+        let pattern = globset::escape(&path.to_string_lossy());
+        let result = config_path()
+            .ok_or_else(|| String::from("no config directory"))
+            .and_then(|config| add_exclude(&config, &pattern).map_err(|e| e.to_string()));
+
+        let message = match result {
+            Ok(()) => format!("{} will be excluded from future scans", path.display()),
+            Err(e) => format!("Could not update the config: {e}"),
+        };
+        self.set_status(message);
+    }
+
+    /// Moves the highlight by `rows`, stopping at either end of the list.
+    pub fn move_selection(&mut self, rows: isize) {
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(rows)
+            .min(self.visible.len().saturating_sub(1));
+    }
+
+    /// Highlights the row at `position` in the list, if there is one.
+    pub fn select_visible(&mut self, position: usize) {
+        if position < self.visible.len() {
+            self.scroll = position;
+        }
+    }
+
+    /// `path` as the list shows it: whole, or shortened to `width` columns with the home directory
+    /// as `~`. Never shorter than a few columns, so that narrow screens overflow rather than show
+    /// nothing useful.
+    pub fn display_path(&self, path: &Path, width: usize) -> String {
+        if self.full_paths {
+            path.display().to_string()
+        } else {
+            shorten_path(path, self.home.as_deref(), width.max(MIN_PATH_WIDTH))
+        }
+    }
+
+    /// The part of `rows` that fits in `height` lines, scrolled as little as possible since the
+    /// last frame to keep the selected module in view. Only these rows need to be drawn.
+    pub fn list_window(&mut self, rows: &[ListRow], height: usize) -> Range<usize> {
+        let height = height.max(1);
+        let selected = rows
+            .iter()
+            .position(|row| *row == ListRow::Module(self.scroll))
+            .unwrap_or(0);
+        self.list_offset = self
+            .list_offset
+            .min(rows.len().saturating_sub(height))
+            .clamp(selected.saturating_sub(height - 1), selected);
+        self.list_offset..(self.list_offset + height).min(rows.len())
+    }
+
+    /// The rows of the list. Sorted by project, the modules of a project holding several are
+    /// preceded by its subtotal.
+    pub fn rows(&self) -> Vec<ListRow> {
+        if !matches!(self.sort, Some(SortBy::Project)) {
+            return (0..self.visible.len()).map(ListRow::Module).collect();
+        }
+        let mut rows = Vec::new();
+        let mut position = 0;
+        let same_project =
+            |&a: &usize, &b: &usize| self.modules[a].path.parent() == self.modules[b].path.parent();
+        for group in self.visible.chunk_by(same_project) {
+            if group.len() > 1 {
+                let modules = group.iter().map(|&i| &self.modules[i]);
+                rows.push(ListRow::Project {
+                    path: self.modules[group[0]]
+                        .path
+                        .parent()
+                        .map(Path::to_path_buf)
+                        .unwrap_or_default(),
+                    size: modules
+                        .filter(|m| !m.sizing && m.error.is_none())
+                        .map(|m| m.size)
+                        .sum(),
+                    count: group.len(),
+                });
+            }
+            rows.extend((position..position + group.len()).map(ListRow::Module));
+            position += group.len();
+        }
+        rows
+    }
+
+    /// Selects `row` of [`App::rows`] when clicked at `at`. A second click on the same row soon
+    /// after lists its subdirectories, like Enter.
+    pub fn click_row(&mut self, row: usize, at: Instant) {
+        self.select_row(row);
+        match self.last_click.take() {
+            Some((last, when)) if last == row && at.duration_since(when) <= DOUBLE_CLICK => {
+                self.open_drill_down()
+            }
+            _ => self.last_click = Some((row, at)),
+        }
+    }
+
+    /// Selects the module on `row` of [`App::rows`], or the first below a project subtotal.
+    pub fn select_row(&mut self, row: usize) {
+        let module = self.rows().into_iter().skip(row).find_map(|row| match row {
+            ListRow::Module(position) => Some(position),
+            ListRow::Project { .. } => None,
+        });
+        if let Some(position) = module {
+            self.select_visible(position);
+        }
+    }
+
+    pub fn delete_module(&mut self) {
+        let Some(index) = self.selected() else {
+            return;
+        };
+        if let Some((_, members)) = self.workspace_members(index) {
+            // Deleted together after one confirmation, leaving marks alone.
+            let members: Vec<usize> = members
+                .into_iter()
+                .filter(|&member| Self::is_markable(&self.modules[member]))
+                .collect();
+            self.pending_workspace = (!members.is_empty()).then_some(members);
+            return;
+        }
+        if self.modules[index].sizing {
+            self.set_status(String::from(
+                "Still calculating the size, try again in a moment",
+            ));
+            return;
+        }
+
+        let confirmations = match self.modules[index].protection {
+            Some(ProtectLevel::Refuse) => return self.refuse_delete(index),
+            Some(ProtectLevel::DoubleConfirm) => 2,
+            _ if self.confirm_each => 1,
+            _ => 0,
+        };
+        if confirmations == 0 {
+            self.delete_at(index);
+        } else if !self.modules[index].is_removed() {
+            self.pending_delete = Some(index);
+            self.confirmations_left = confirmations;
+        }
+    }
+
+    fn refuse_delete(&mut self, index: usize) {
+        let path = &self.modules[index].path;
+        let message = match self.policy.reason(path) {
+            Some(reason) => format!("Refusing to delete {}: {reason}", path.display()),
+            None => format!("Refusing to delete {}", path.display()),
+        };
+        self.set_status(message);
+    }
+
+    fn delete_at(&mut self, index: usize) {
+        if self.modules[index].protection == Some(ProtectLevel::Refuse) {
+            return self.refuse_delete(index);
+        }
+        if self.modules[index].protection == Some(ProtectLevel::Warn) {
+            let message = format!(
+                "Warning: {} is in a protected location",
+                self.modules[index].path.display()
+            );
+            self.set_status(message);
+        }
+
+        if let Some(module) = self.modules.get_mut(index) {
+            if module.is_removed() || module.sizing {
+                return;
+            }
+
+            // A fast deletion that failed left the directory under its hidden name.
+            let path = match &module.state {
+                DeleteState::Failed(DeleteError {
+                    moved_to: Some(aside),
+                    ..
+                }) => aside.clone(),
+                _ => module.path.clone(),
+            };
+            module.state = DeleteState::Queued;
+            module.to_trash = self.deleter.use_trash;
+            self.deleter
+                .delete_reporting(index, path, self.delete_tx.clone());
+        }
+    }
+
+    /// Like [`delete_at`](Self::delete_at), counting the deletion in the running batch. A nested
+    /// directory whose parent is already being deleted is left to go with it.
+    fn delete_in_batch(&mut self, index: usize) {
+        let path = &self.modules[index].path;
+        if self.modules[index].nested
+            && self
+                .modules
+                .iter()
+                .any(|m| m.is_removed() && path != &m.path && path.starts_with(&m.path))
+        {
+            return;
+        }
+        self.delete_at(index);
+        let module = &mut self.modules[index];
+        if module.state != DeleteState::Queued || module.in_batch {
+            return;
+        }
+
+        module.in_batch = true;
+        let batch = self.batch.get_or_insert_with(DeleteBatch::new);
+        batch.total += 1;
+        batch.total_bytes += module.size;
+    }
+
+    /// Ends the batch once every deletion in it finished, unless `--delete-all` may still add
+    /// to it.
+    fn finish_batch(&mut self) {
+        let Some(batch) = &self.batch else {
+            return;
+        };
+        if batch.completed < batch.total || (self.auto_delete && self.scanning) {
+            return;
+        }
+
+        let elapsed = batch.started.elapsed();
+        let message = format!(
+            "Deleted {} of {} directories, reclaiming {} in {elapsed:.1?} ({})",
+            batch.total - batch.failed,
+            batch.total,
+            format_size(batch.reclaimed, SizeUnit::Auto),
+            format_rate(batch.reclaimed, elapsed, SizeUnit::Auto)
+        );
+        for module in &mut self.modules {
+            module.in_batch = false;
+        }
+        self.batch = None;
+        self.set_status(message);
+    }
+
+    /// Queues the highlighted module again if its deletion failed.
+    pub fn retry_selected(&mut self) {
+        if let Some(index) = self.selected() {
+            if matches!(self.modules[index].state, DeleteState::Failed(_)) {
+                self.delete_at(index);
+            }
+        }
+    }
+
+    /// Whether the deletion of `index` failed for lack of permission and may work as
+    /// administrator.
+    fn may_elevate(&self, index: usize) -> bool {
+        let module = &self.modules[index];
+        self.deleter.is_local()
+            && !module.to_trash
+            && matches!(&module.state, DeleteState::Failed(e) if e.is_denied())
+    }
+
+    /// Asks whether to remove the highlighted module as administrator, if its deletion was
+    /// denied.
+    pub fn request_elevation(&mut self) {
+        match self.selected() {
+            Some(index) if self.may_elevate(index) => self.pending_elevation = Some(index),
+            Some(_) => self.set_status(String::from(
+                "Only directories that could not be deleted for lack of permission can be deleted as administrator",
+            )),
+            None => {}
+        }
+    }
+
+    /// Takes the module confirmed for [removal as administrator](Self::request_elevation),
+    /// with the path it's at, which a failed fast deletion may have changed.
+    pub fn confirm_elevation(&mut self) -> Option<(usize, PathBuf)> {
+        let index = self.pending_elevation.take()?;
+        let module = &mut self.modules[index];
+        let path = match std::mem::replace(&mut module.state, DeleteState::Deleting) {
+            DeleteState::Failed(DeleteError {
+                moved_to: Some(aside),
+                ..
+            }) => aside,
+            _ => module.path.clone(),
+        };
+        Some((index, path))
+    }
+
+    /// Records the outcome of a removal as administrator.
+    pub fn finish_elevation(&mut self, index: usize, result: Result<(), String>) {
+        self.on_deleted(index, result.map_err(DeleteError::from));
+    }
+
+    /// Applies the progress reported by the deletion queue, without blocking.
+    pub fn drain_delete_events(&mut self) {
+        while let Ok(event) = self.delete_rx.try_recv() {
+            match event {
+                DeleteEvent::Started(index) => {
+                    let module = &mut self.modules[index];
+                    module.state = DeleteState::Deleting;
+                    if let Some(batch) = self.batch.as_mut().filter(|_| module.in_batch) {
+                        batch.current = Some(module.path.clone());
+                    }
+                }
+                DeleteEvent::Finished(index, result, elapsed) => {
+                    self.modules[index].delete_time = Some(elapsed);
+                    let module = &self.modules[index];
+                    if let Some(batch) = self.batch.as_mut().filter(|_| module.in_batch) {
+                        batch.completed += 1;
+                        batch.completed_bytes += module.size;
+                        match result {
+                            Ok(()) => batch.reclaimed += module.size,
+                            Err(_) => batch.failed += 1,
+                        }
+                    }
+                    self.on_deleted(index, result);
+                }
+            }
+        }
+        self.finish_batch();
+    }
+
+    fn on_deleted(&mut self, index: usize, result: Result<(), DeleteError>) {
+        let module = &self.modules[index];
+        record_deletion(&module.path, module.size, module.to_trash, &result);
+        match result {
+            Ok(()) => {
+                let module = &mut self.modules[index];
+                module.state = DeleteState::Deleted;
+                self.total_deleted += module.size;
+                if module.to_trash {
+                    self.trashed.push(index);
+                }
+                // A directory installed again at the same path starts untagged.
+                if module.tag.is_some() || module.note.is_some() {
+                    (module.tag, module.note) = (None, None);
+                    self.save_notes(&[index]);
+                }
+            }
+            Err(e) => {
+                let retry = if self.deleter.is_local() && !module.to_trash && e.is_denied() {
+                    "r to retry, S to delete as administrator"
+                } else {
+                    "r to retry"
+                };
+                let message = format!(
+                    "Could not delete {}: {e} ({retry})",
+                    self.modules[index].path.display()
+                );
+                self.modules[index].state = DeleteState::Failed(e);
+                // A retry isn't part of the batch any more.
+                self.modules[index].in_batch = false;
+                self.set_status(message);
+            }
+        }
+    }
+
+    /// Starts computing the breakdown of the highlighted module in the background, unless the
+    /// pane is hidden or it was already requested.
+    pub fn request_details(&mut self) {
+        if !self.show_details {
+            return;
+        }
+        if let Some(index) = self.selected() {
+            self.compute_details(index);
+        }
+    }
+
+    fn compute_details(&mut self, index: usize) {
+        if self.details.contains_key(&index) || self.modules[index].is_removed() {
+            return;
+        }
+
+        if !self.deleter.is_local() {
+            let unavailable = String::from("only available for directories on this machine");
+            self.details.insert(index, Some(Err(unavailable)));
+            return;
+        }
+        self.details.insert(index, None);
+        let path = self.modules[index].path.clone();
+        let tx = self.details_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = tx.send((index, dir_breakdown(&path), count_locked_packages(&path)));
+        });
+    }
+
+    /// Lists the subdirectories of the highlighted module by size, computing its breakdown if
+    /// the detail pane hasn't already.
+    pub fn open_drill_down(&mut self) {
+        let Some(index) = self.selected() else {
+            return;
+        };
+        if self.modules[index].is_removed() || !self.is_local("The breakdown") {
+            return;
+        }
+        self.compute_details(index);
+        self.drill_down = Some(index);
+        self.drill_scroll = 0;
+    }
+
+    fn on_drill_down_key(&mut self, key: KeyCode) {
+        let rows = match self.drill_down.and_then(|index| self.details.get(&index)) {
+            Some(Some(Ok(breakdown))) => breakdown.largest.len(),
+            _ => 0,
+        };
+        let last = rows.saturating_sub(1);
+        match key {
+            KeyCode::Up => self.drill_scroll = self.drill_scroll.saturating_sub(1),
+            KeyCode::Down => self.drill_scroll = (self.drill_scroll + 1).min(last),
+            KeyCode::PageUp => self.drill_scroll = self.drill_scroll.saturating_sub(self.page_size),
+            KeyCode::PageDown => self.drill_scroll = (self.drill_scroll + self.page_size).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.drill_scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => self.drill_scroll = last,
+            KeyCode::Enter | KeyCode::Esc | KeyCode::Left | KeyCode::Backspace => {
+                self.drill_down = None
+            }
+            _ => {}
+        }
+    }
+
+    /// Measures the highlighted module again in full when its size is partial.
+    pub fn recount_selected(&mut self) {
+        let Some(index) = self.selected() else {
+            return;
+        };
+        let module = &self.modules[index];
+        if !module.partial || module.is_removed() || self.sizing.contains_key(&module.path) {
+            return;
+        }
+        let path = module.path.clone();
+        if !self.is_local("Counting again") {
+            return;
+        }
+
+        self.set_status(format!("Counting everything in {}", path.display()));
+        self.sizing.insert(path.clone(), index);
+        let tx = self.recount_tx.clone();
+        let options = Arc::clone(&self.recount_options);
+        tokio::task::spawn_blocking(move || {
+            let _ = tx.send(measure(path, &options));
+        });
+    }
+
+    /// Updates the modules measured again since the last frame, without blocking.
+    pub fn drain_recounts(&mut self) {
+        while let Ok(dir) = self.recount_rx.try_recv() {
+            self.update_module(dir);
+        }
+    }
+
+    /// Stores the breakdowns finished since the last frame, without blocking.
+    pub fn drain_details(&mut self) {
+        while let Ok((index, breakdown, locked_packages)) = self.details_rx.try_recv() {
+            self.details.insert(index, Some(breakdown));
+            self.modules[index].dir.locked_packages = locked_packages;
+        }
+    }
+
+    /// Number of deletions queued or running.
+    pub fn deletions_in_flight(&self) -> usize {
+        self.modules
+            .iter()
+            .filter(|m| matches!(m.state, DeleteState::Queued | DeleteState::Deleting))
+            .count()
+    }
+
+    pub fn toggle_trash(&mut self) {
+        self.deleter.use_trash = !self.deleter.use_trash;
+        self.set_status(if self.deleter.use_trash {
+            String::from("Deleted directories will be moved to the trash")
+        } else {
+            String::from("Deleted directories will be removed permanently")
+        });
+    }
+
+    /// Restores the last directory moved to the trash this session.
+    pub fn restore_last(&mut self) {
+        let Some(index) = self.trashed.pop() else {
+            self.set_status(String::from("Nothing to restore"));
+            return;
+        };
+
+        let module = &mut self.modules[index];
+        match restore_from_trash(&module.path) {
+            Ok(()) => {
+                module.state = DeleteState::Present;
+                self.total_deleted = self.total_deleted.saturating_sub(module.size);
+                let message = format!("Restored {}", module.path.display());
+                self.set_status(message);
+            }
+            Err(e) => {
+                let message = format!("Could not restore {}: {e}", module.path.display());
+                self.trashed.push(index);
+                self.set_status(message);
+            }
+        }
+    }
+
+    #[inline]
+    fn is_markable(module: &NodeModule) -> bool {
+        module.state == DeleteState::Present && module.error.is_none() && !module.sizing
+    }
+
+    /// Marks or unmarks the highlighted module, or every directory of a collapsed workspace.
+    pub fn toggle_mark(&mut self) {
+        let Some(index) = self.selected() else {
+            return;
+        };
+        let marked = !self.modules[index].marked;
+        let members = self
+            .workspace_members(index)
+            .map_or_else(|| vec![index], |(_, members)| members);
+        for member in members {
+            let module = &mut self.modules[member];
+            if Self::is_markable(module) {
+                module.marked = marked;
+            }
+        }
+    }
+
+    /// Restores the tags and notes saved at `path` and saves them there from now on.
+    pub fn load_notes(&mut self, path: PathBuf) {
+        match cache::load_notes(&path) {
+            Ok(notes) => self.notes = notes,
+            Err(e) => self.set_status(format!("Could not read the tags: {e}")),
+        }
+        for module in &mut self.modules {
+            let note = self.notes.get(&module.path).cloned().unwrap_or_default();
+            (module.tag, module.note) = (note.tag, note.text);
+        }
+        self.notes_path = Some(path);
+    }
+
+    /// Saves the tags and notes of the modules at `indices`, next to those other sessions saved.
+    fn save_notes(&mut self, indices: &[usize]) {
+        let changes: HashMap<PathBuf, Note> = indices
+            .iter()
+            .map(|&index| {
+                let module = &self.modules[index];
+                let note = Note {
+                    tag: module.tag,
+                    text: module.note.clone(),
+                };
+                (module.path.clone(), note)
+            })
+            .collect();
+        for (dir, note) in &changes {
+            if note.is_empty() {
+                self.notes.remove(dir);
+            } else {
+                self.notes.insert(dir.clone(), note.clone());
+            }
+        }
+        if let Some(path) = &self.notes_path {
+            match cache::update_notes(path, changes) {
+                Ok(notes) => self.notes = notes,
+                Err(e) => self.set_status(format!("Could not save the tags: {e}")),
+            }
+        }
+    }
+
+    /// Gives the highlighted module `tag`, or takes it away if it already has it.
+    pub fn tag_selected(&mut self, tag: Tag) {
+        let Some(index) = self.selected() else {
+            return;
+        };
+        let tag = (self.modules[index].tag != Some(tag)).then_some(tag);
+        let members = self
+            .workspace_members(index)
+            .map_or_else(|| vec![index], |(_, members)| members);
+        for &member in &members {
+            self.modules[member].tag = tag;
+        }
+        self.save_notes(&members);
+    }
+
+    /// Starts typing a note for the highlighted module, from the one it has.
+    pub fn edit_note(&mut self) {
+        if let Some(index) = self.selected() {
+            self.note_input = Some(self.modules[index].note.clone().unwrap_or_default());
+        }
+    }
+
+    fn on_note_key(&mut self, key: KeyCode) {
+        let Some(input) = &mut self.note_input else {
+            return;
+        };
+        match key {
+            KeyCode::Esc => self.note_input = None,
+            KeyCode::Enter => {
+                let text = input.trim();
+                let note = (!text.is_empty()).then(|| text.to_string());
+                self.note_input = None;
+                let Some(index) = self.selected() else {
+                    return;
+                };
+                let members = self
+                    .workspace_members(index)
+                    .map_or_else(|| vec![index], |(_, members)| members);
+                for &member in &members {
+                    self.modules[member].note = note.clone();
+                }
+                self.save_notes(&members);
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
+    }
+
+    /// Writes the marked modules that bulk deletion would remove as a [script](crate::script),
+    /// leaving them and their marks alone. Only local directories can be deleted by one.
+    pub fn write_script(&mut self) {
+        if !self.deleter.is_local() {
+            return self.set_status(String::from(
+                "Scripts only delete local directories, not those of Docker or --ssh",
+            ));
+        }
+        let dirs: Vec<&FoundDir> = self
+            .modules
+            .iter()
+            .filter(|m| m.marked && Self::is_markable(m))
+            .filter(|m| !m.protection.is_some_and(ProtectLevel::blocks_bulk_delete))
+            .map(|m| &m.dir)
+            .collect();
+        if dirs.is_empty() {
+            return self.set_status(String::from("No directories are marked, m marks one"));
+        }
+        let message = match script::write(&self.script_path, &dirs) {
+            Ok(()) => format!(
+                "Wrote {} deletions to {}",
+                dirs.len(),
+                self.script_path.display()
+            ),
+            Err(e) => format!("Could not write {}: {e}", self.script_path.display()),
+        };
+        self.set_status(message);
+    }
+
+    /// Copies the path of the highlighted module to the system clipboard.
+    pub fn copy_selected_path(&mut self) {
+        if let Some(index) = self.selected() {
+            let path = self.modules[index].path.display().to_string();
+            self.copy(format!("Copied {path}"), path);
+        }
+    }
+
+    /// Copies the paths of the marked modules to the system clipboard, one per line.
+    pub fn copy_marked_paths(&mut self) {
+        let paths: Vec<String> = self
+            .modules
+            .iter()
+            .filter(|m| m.marked)
+            .map(|m| m.path.display().to_string())
+            .collect();
+        if paths.is_empty() {
+            return self.set_status(String::from("No directories are marked, m marks one"));
+        }
+        self.copy(format!("Copied {} paths", paths.len()), paths.join("\n"));
+    }
+
+    fn copy(&mut self, done: String, text: String) {
+        let copied = match &mut self.clipboard {
+            Some(clipboard) => clipboard.set_text(text),
+            None => arboard::Clipboard::new()
+                .and_then(|clipboard| self.clipboard.insert(clipboard).set_text(text)),
+        };
+        match copied {
+            Ok(()) => self.set_status(done),
+            Err(e) => self.set_status(format!("Could not copy to the clipboard: {e}")),
+        }
+    }
+
+    /// Marks every visible module, or clears the marks of all modules.
+    pub fn set_all_marks(&mut self, marked: bool) {
+        if marked {
+            let listed: Vec<usize> = self
+                .visible
+                .iter()
+                .flat_map(|&i| {
+                    self.workspace_members(i)
+                        .map_or_else(|| vec![i], |(_, m)| m)
+                })
+                .collect();
+            for index in listed {
+                let module = &mut self.modules[index];
+                module.marked = Self::is_markable(module);
+            }
+        } else {
+            for module in self.modules.iter_mut() {
+                module.marked = false;
+            }
+        }
+    }
+
+    /// Returns the number and total size of marked modules that can still be deleted.
+    pub fn marked_summary(&self) -> (usize, u64) {
+        self.modules
+            .iter()
+            .filter(|m| m.marked && Self::is_markable(m))
+            .fold((0, 0), |(count, size), m| (count + 1, size + m.size))
+    }
+
+    /// Deletes every marked module, skipping those that must be confirmed one at a time.
+    pub fn delete_marked(&mut self) {
+        let marked: Vec<usize> = (0..self.modules.len())
+            .filter(|&index| self.modules[index].marked)
+            .collect();
+        for module in &mut self.modules {
+            module.marked = false;
+        }
+        self.delete_together(marked);
+    }
+
+    /// Deletes the modules at `indices` in one batch, skipping those that must be confirmed
+    /// one at a time.
+    fn delete_together(&mut self, mut indices: Vec<usize>) {
+        // Parents first, so that the nested directories inside them are left to go with them.
+        indices.sort_by_key(|&index| nesting_depth(&self.modules[index].path, &self.targets));
+        let mut protected = 0;
+        for index in indices {
+            let module = &self.modules[index];
+            if !Self::is_markable(module) {
+                continue;
+            }
+            if module
+                .protection
+                .is_some_and(ProtectLevel::blocks_bulk_delete)
+            {
+                protected += 1;
+            } else {
+                self.delete_in_batch(index);
+            }
+        }
+        if protected > 0 {
+            self.set_status(format!(
+                "Skipped {protected} protected directories, delete them one at a time"
+            ));
+        }
+    }
+}
+
+/// Parses a human-readable size such as `100MB`, `1.5G` or `512` (bytes) into bytes.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{input}'"))?;
+    let shift = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        other => return Err(format!("unknown size unit '{other}'")),
+    };
+
+    Ok((number * (1u64 << shift) as f64) as u64)
+}
+
+/// Parses `--size-bands`: two [sizes](parse_size) separated by a comma, the smaller first.
+pub fn parse_size_bands(input: &str) -> Result<SizeBands, String> {
+    let (medium, large) = input
+        .split_once(',')
+        .ok_or_else(|| String::from("expected two sizes separated by a comma, e.g. 100MB,1GB"))?;
+    let bands = SizeBands {
+        medium: parse_size(medium)?,
+        large: parse_size(large)?,
+    };
+    if bands.medium >= bands.large {
+        return Err(String::from(
+            "the first size must be smaller than the second",
+        ));
+    }
+    Ok(bands)
+}
+
+/// Checks that an `--exclude` pattern is a valid glob, keeping it as written.
+pub fn parse_exclude(input: &str) -> Result<String, String> {
+    exclude_glob(input)
+        .map(|_| input.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Checks that a `--match` or `--exclude-regex` pattern is a valid regular expression, keeping
+/// it as written.
+pub fn parse_regex(input: &str) -> Result<String, String> {
+    regex::Regex::new(input)
+        .map(|_| input.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Parses a duration such as `30d`, `12h` or `2w` into seconds. A bare number means days.
+pub fn parse_age(input: &str) -> Result<i64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let number: i64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{input}'"))?;
+    let seconds = match unit.trim().to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 3600,
+        "" | "d" => 86400,
+        "w" => 7 * 86400,
+        "mo" => 30 * 86400,
+        "y" => 365 * 86400,
+        other => return Err(format!("unknown duration unit '{other}'")),
+    };
+
+    Ok(number * seconds)
+}
+
+impl From<&Args> for ScanOptions {
+    fn from(args: &Args) -> Self {
+        Self {
+            targets: args.targets(),
+            exclude_hidden: args.exclude_hidden,
+            exclude_paths: args.exclude_paths.clone(),
+            exclude_regex: args.exclude_regex.clone(),
+            match_regex: args.match_regex.clone(),
+            max_depth: args.max_depth,
+            respect_ignore_files: !args.no_ignore,
+            include_system: args.include_system,
+            require_manifest: if args.require_manifest {
+                ManifestCheck::All
+            } else if args.profile.is_some() {
+                ManifestCheck::Generic
+            } else {
+                ManifestCheck::Off
+            },
+            min_size: args.min_size,
+            older_than: args.older_than,
+            disk_usage: args.disk_usage,
+            active_within: args.active_within,
+            skip_active: args.skip_active,
+            follow_symlinks: args.follow_symlinks,
+            same_filesystem: args.same_filesystem,
+            io_nice: args.io_nice,
+            caches: args.caches,
+            size_budget: SizeBudget {
+                time: args
+                    .size_timeout
+                    .map(|seconds| Duration::from_secs(seconds.max(0) as u64)),
+                entries: args.size_max_entries,
+            },
+            use_atime: args.use_atime,
+            include_nested: args.include_nested,
+            project_roots: args.stdin,
+        }
+    }
+}
+
+impl Args {
+    /// A deleter for the directories found, local or on the `--ssh` host.
+    pub fn deleter(&self) -> Deleter {
+        Deleter::new(self.trash)
+            .with_remote(self.ssh.as_ref().map(|r| r.destination.clone()))
+            .with_docker((self.command() == Command::Docker).then(docker::socket_path))
+            .with_io_nice(self.io_nice)
+            .with_fast(self.fast_delete)
+            .with_force(self.force)
+    }
+
+    /// Returns the subcommand to run, defaulting to the interactive scan.
+    pub fn command(&self) -> Command {
+        self.command
+            .clone()
+            .unwrap_or(Command::Scan { roots: Vec::new() })
+    }
+
+    /// The roots given to `scan`, each with the name it was given by and its canonical path.
+    /// Names starting with `@` are looked up in the bookmarks of the config file.
+    pub fn roots(&self) -> Result<Vec<(String, PathBuf)>, String> {
+        let Command::Scan { roots } = self.command() else {
+            return Ok(Vec::new());
+        };
+        roots
+            .into_iter()
+            .map(|root| {
+                let path = match root.strip_prefix('@') {
+                    Some(name) => self.bookmarks.get(name).ok_or_else(|| {
+                        format!(
+                            "no root named {root}, bookmark it under [roots] in the config file"
+                        )
+                    })?,
+                    None => &root,
+                };
+                let path =
+                    std::fs::canonicalize(expand_home(path)).map_err(|e| format!("{root}: {e}"))?;
+                Ok((root, path))
+            })
+            .collect()
+    }
+
+    /// Whether results are printed to stdout instead of shown in the interactive interface,
+    /// which also needs stdout to be a terminal.
+    pub fn is_headless(&self) -> bool {
+        self.output_format().is_some()
+            || self.dry_run
+            || !matches!(self.command(), Command::Scan { .. } | Command::Docker)
+            || !std::io::stdout().is_terminal()
+    }
+
+    /// Refuses `--delete-all` on a scan where the interactive interface, which does the
+    /// deleting, wouldn't run. `--dry-run` lists what it would delete.
+    pub fn check_delete_all(&self) -> Result<(), String> {
+        let scans = matches!(self.command(), Command::Scan { .. } | Command::Docker);
+        if self.delete_all && scans && self.is_headless() && !self.dry_run {
+            return Err(String::from(
+                "--delete-all needs the interactive interface, so a terminal and no output \
+                 format; use `rskill clean --yes` to delete without it",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The format results are written in: `--output`, then `--json`, then the extension of
+    /// `--output-file`.
+    pub fn output_format(&self) -> Option<OutputFormat> {
+        self.output
+            .or(self.json.then_some(OutputFormat::Json))
+            .or_else(|| self.output_file.as_deref().map(OutputFormat::from_path))
+    }
+
+    /// The options that change what is scanned or how, as they would be written on the command
+    /// line.
+    pub fn flags_summary(&self) -> Vec<String> {
+        let mut flags = vec![match self.profile.and_then(|p| p.to_possible_value()) {
+            Some(profile) => format!("--profile {}", profile.get_name()),
+            None => format!("--target {}", self.targets().join(",")),
+        }];
+        if self.all_drives {
+            flags.push(String::from("--all-drives"));
+        } else if self.full {
+            flags.push(String::from("--full"));
+        } else if let Some(remote) = &self.ssh {
+            flags.push(format!("--ssh {remote}"));
+        } else {
+            for directory in &self.directory {
+                flags.push(format!("--directory {directory}"));
+            }
+        }
+        for pattern in &self.exclude_paths {
+            flags.push(format!("--exclude {pattern}"));
+        }
+        for pattern in &self.exclude_regex {
+            flags.push(format!("--exclude-regex {pattern}"));
+        }
+        for pattern in &self.match_regex {
+            flags.push(format!("--match {pattern}"));
+        }
+        if let Some(depth) = self.max_depth {
+            flags.push(format!("--max-depth {depth}"));
+        }
+        if let Some(size) = self.min_size {
+            flags.push(format!("--min-size {size}B"));
+        }
+        if let Some(bands) = self.size_bands {
+            flags.push(format!("--size-bands {}B,{}B", bands.medium, bands.large));
+        }
+        for rule in &self.protect {
+            flags.push(format!("--protect {}:{}", rule.level, rule.pattern));
+        }
+        if let Some(age) = self.older_than {
+            flags.push(format!("--older-than {}d", age / 86400));
+        }
+        if let Some(threads) = self.threads {
+            flags.push(format!("--threads {threads}"));
+        }
+        if let Some(threads) = self.per_disk_threads {
+            flags.push(format!("--per-disk-threads {threads}"));
+        }
+        if let Some(seconds) = self.size_timeout {
+            flags.push(format!("--size-timeout {seconds}s"));
+        }
+        if let Some(entries) = self.size_max_entries {
+            flags.push(format!("--size-max-entries {entries}"));
+        }
+        if let Some(path) = &self.emit_script {
+            flags.push(format!("--emit-script {}", path.display()));
+        }
+        let switches = [
+            (self.exclude_hidden, "--exclude-hidden-directories"),
+            (self.no_ignore, "--no-ignore"),
+            (self.include_system, "--include-system"),
+            (self.include_nested, "--include-nested"),
+            (self.stdin, "--stdin"),
+            (self.require_manifest, "--require-manifest"),
+            (self.allow_global, "--allow-global"),
+            (self.follow_symlinks, "--follow-symlinks"),
+            (self.same_filesystem, "--same-filesystem"),
+            (self.io_nice, "--io-nice"),
+            (self.disk_usage, "--disk-usage"),
+            (self.use_atime, "--use-atime"),
+            (self.skip_active, "--skip-active"),
+            (self.cached, "--cached"),
+            (self.watch, "--watch"),
+            (self.caches, "--caches"),
+            (self.trash, "--trash"),
+            (self.fast_delete, "--fast-delete"),
+            (self.force, "--force"),
+            (self.confirm_each, "--confirm-each"),
+            (self.delete_all, "--delete-all"),
+            (self.summary, "--summary"),
+        ];
+        flags.extend(
+            switches
+                .iter()
+                .filter(|(set, _)| *set)
+                .map(|(_, flag)| flag.to_string()),
+        );
+        if self.unit() != SizeUnit::Auto {
+            flags.push(format!("--unit {}", self.unit().label().to_lowercase()));
+        }
+        flags
+    }
+
+    /// The unit sizes are shown in: `--unit`, or gigabytes under the old `--gb`.
+    pub fn unit(&self) -> SizeUnit {
+        if self.in_gb {
+            SizeUnit::Gb
+        } else {
+            self.unit
+        }
+    }
+
+    /// Returns the target directory names passed to `--target`, or those of `--profile`.
+    pub fn targets(&self) -> Vec<String> {
+        if let Some(profile) = self.profile {
+            return profile.targets().iter().map(|t| t.to_string()).collect();
+        }
+
+        self.target
+            .split(',')
+            .map(str::trim)
+            .filter(|target| !target.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Find directories and pick which ones to delete interactively (the default)
+    Scan {
+        /// Roots to scan instead of --directory: @name for one bookmarked under [roots] in the config file, or a directory.
+        #[arg(value_name = "ROOT")]
+        roots: Vec<String>,
+    },
+    /// Delete every directory found without opening the interactive interface
+    Clean {
+        /// Confirm that every directory found should be deleted
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Print the directories found without deleting anything. Combine with --json for scripting
+    List,
+    /// Print the number and size of the directories found, per target
+    Stats,
+    /// Print the package versions installed in the node_modules of several projects and the space the copies waste
+    Dupes,
+    /// Replace identical files across the directories found with reflinks to one copy. --dry-run only prints the savings
+    Dedupe {
+        /// Hard-link the duplicates on filesystems that can't make reflinks. Hard links share their timestamps and see each other's changes
+        #[arg(long)]
+        hard_links: bool,
+    },
+    /// Print the projects whose directories grew the most since the last scan measured them
+    Growth,
+    /// Print a completion script for bash, zsh, fish, elvish or powershell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Run the install or build command of every project in a restore manifest written by --delete-all or clean
+    Reinstall {
+        /// The restore manifest to read.
+        #[arg(long, value_name = "FILE")]
+        manifest: PathBuf,
+    },
+    /// Print past cleanup sessions from the --log-file and the space they reclaimed
+    History,
+    /// Pick dangling images, stopped containers, unused volumes and build cache to remove from Docker
+    Docker,
+    /// Keep running and delete every directory found at the times given by --schedule
+    Daemon {
+        /// When to clean up, as a cron expression such as "0 3 * * 0" for Sundays at 3am.
+        #[arg(long, value_parser = daemon::parse_schedule)]
+        schedule: String,
+        /// Print a service definition running this daemon at login instead of starting it.
+        #[arg(long, value_enum)]
+        service: Option<ServiceKind>,
+        /// Serve Prometheus metrics at /metrics on this address, e.g. 127.0.0.1:9184.
+        #[arg(long, value_name = "ADDR")]
+        metrics: Option<SocketAddr>,
+    },
+}
+
+/// Writes the completion script for `shell` to `out`.
+pub fn write_completions(shell: Shell, out: &mut impl std::io::Write) {
+    let mut command = <Args as CommandFactory>::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, out);
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    ///Set the directory from which to begin searching. Can be repeated to search several, each directory being walked once even when they overlap. By default, starting-point is .
+    #[arg(short, long, default_value = ".", global = true)]
+    pub directory: Vec<String>,
+
+    ///Exclude directories from search (directory list must be inside double quotes "", each directory separated by ',' ) Example: "ignore1, ignore2"
+    #[arg(
+        short = 'x',
+        long = "exclude-hidden-directories",
+        default_value_t = false,
+        global = true
+    )]
+    pub exclude_hidden: bool,
+
+    ///Specify the name of the directories you want to search (by default, is node_modules). Several names can be separated by ',' Example: "node_modules,target,.venv"
+    #[arg(short, long, default_value_t = String::from("node_modules"), global = true)]
+    pub target: String,
+
+    ///Start searching from the home of the user (example: "/home/user" in linux, "C:\Users\user" in Windows)
+    #[arg(short, long, default_value_t = false, global = true)]
+    pub full: bool,
+
+    /// Search every fixed drive (every non-removable disk on Unix) instead of a single directory
+    #[arg(long = "all-drives", conflicts_with = "full", global = true)]
+    pub all_drives: bool,
+
+    /// Read the project directories to check from stdin, one per line (e.g. from fd or git), and only look for targets directly inside them instead of walking a directory tree
+    #[arg(long, conflicts_with_all = ["full", "all_drives", "pick_root"], global = true)]
+    pub stdin: bool,
+
+    /// Colors of the interface: dark (the default), light, solarized, or custom to only use those of the config file. NO_COLOR turns colors off unless a theme is given.
+    #[arg(long, value_enum, global = true)]
+    pub theme: Option<ThemeName>,
+
+    /// Sizes from which rows show their size in yellow and in red, e.g. "100MB,1GB" (the default)
+    #[arg(long = "size-bands", value_name = "MEDIUM,LARGE", value_parser = parse_size_bands, global = true)]
+    pub size_bands: Option<SizeBands>,
+
+    /// Color overrides from the config file.
+    #[arg(skip)]
+    pub colors: ColorOverrides,
+
+    /// Roots bookmarked in the config file, by name.
+    #[arg(skip)]
+    pub bookmarks: BTreeMap<String, String>,
+
+    /// Browse to the directory to search from in the terminal before scanning, starting at the
+    /// one --directory.
+    #[arg(long = "pick-root", conflicts_with_all = ["full", "all_drives"], global = true)]
+    pub pick_root: bool,
+
+    /// Show every size in this unit instead of scaling each one: b, kb, mb, gb or tb.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "UNIT",
+        default_value = "auto",
+        global = true
+    )]
+    pub unit: SizeUnit,
+
+    /// Same as --unit gb; kept for compatibility.
+    #[arg(long = "gb", hide = true, conflicts_with = "unit", global = true)]
+    pub in_gb: bool,
+
+    /// Exclude paths matching a glob, e.g. "**/vendor/**" or "~/work/*". Can be repeated
+    #[arg(long = "exclude", short = 'E', value_name = "GLOB", value_parser = parse_exclude, global = true)]
+    pub exclude_paths: Vec<String>,
+
+    /// Exclude paths matching a regular expression, e.g. "/(vendor|third_party)/". Can be repeated
+    #[arg(long = "exclude-regex", value_name = "REGEX", value_parser = parse_regex, global = true)]
+    pub exclude_regex: Vec<String>,
+
+    /// Only list directories whose path matches a regular expression, e.g. "/clients/[^/]+/app/". Can be repeated
+    #[arg(long = "match", value_name = "REGEX", value_parser = parse_regex, global = true)]
+    pub match_regex: Vec<String>,
+
+    /// Sort results by: size, path, last-mod or project
+    #[arg(long, short, value_enum, global = true)]
+    pub sort: Option<SortBy>,
+
+    /// Automatically delete all node_modules folders that are found. Suggested to be used together with -x.
+    #[arg(long = "delete-all", short = 'D', global = true)]
+    pub delete_all: bool,
+
+    /// Maximum depth to descend below the starting directory. A depth of 1 means only its immediate children are checked. Unbounded by default.
+    #[arg(
+        long = "max-depth",
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        global = true
+    )]
+    pub max_depth: Option<usize>,
+
+    /// Don't skip directories ignored by .gitignore, .ignore, .rskillignore and global git excludes.
+    #[arg(long = "no-ignore", default_value_t = false, global = true)]
+    pub no_ignore: bool,
+
+    /// Ignore files are respected by default; kept for compatibility.
+    #[arg(
+        long = "respect-gitignore",
+        hide = true,
+        conflicts_with = "no_ignore",
+        global = true
+    )]
+    pub respect_gitignore: bool,
+
+    /// Include directories that belong to globally installed tooling (nvm, volta, npm/yarn/pnpm global prefixes, homebrew, /usr/lib/node_modules). Hidden by default.
+    #[arg(long = "include-system", default_value_t = false, global = true)]
+    pub include_system: bool,
+
+    /// List target directories inside other ones too, such as the node_modules of a vendored package, indented by how deep they are nested. Their size is also counted in the directories holding them.
+    #[arg(long = "include-nested", default_value_t = false, global = true)]
+    pub include_nested: bool,
+
+    /// Print the scan results as a JSON array to stdout instead of opening the interactive interface. Nothing is deleted.
+    #[arg(long, default_value_t = false, global = true)]
+    pub json: bool,
+
+    /// Write the scan results in this format instead of opening the interactive interface. Nothing is deleted.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        conflicts_with = "json",
+        global = true
+    )]
+    pub output: Option<OutputFormat>,
+
+    /// Write the results to this file instead of stdout. The format defaults to the file extension (csv, ndjson or json).
+    #[arg(long = "output-file", value_name = "PATH", global = true)]
+    pub output_file: Option<PathBuf>,
+
+    /// Print the directories that would be deleted and how much space would be reclaimed, without opening the interactive interface or deleting anything.
+    #[arg(long = "dry-run", default_value_t = false, global = true)]
+    pub dry_run: bool,
+
+    /// Move deleted directories to the system trash instead of removing them permanently. Can be toggled in the interface with 'T'.
+    #[arg(long, default_value_t = false, global = true)]
+    pub trash: bool,
+
+    /// Rename each directory out of its project before removing it, so it disappears at once, then remove it with parallel workers. Faster than the default on huge trees.
+    #[arg(long = "fast-delete", global = true)]
+    pub fast_delete: bool,
+
+    /// When removing a directory is denied, give yourself write access to every directory in it (clearing read-only attributes on Windows) and try again. Needed for read-only pnpm stores and some checkouts.
+    #[arg(long, global = true)]
+    pub force: bool,
+
+    /// Ask for confirmation, showing the path, size and age, before deleting each directory.
+    #[arg(long = "confirm-each", default_value_t = false, global = true)]
+    pub confirm_each: bool,
+
+    /// Search for the build artifacts of an ecosystem instead of --target: node, rust, python, java or all. Generic names such as 'target' and 'build' are only listed next to their project manifest.
+    #[arg(long, value_enum, conflicts_with = "target", global = true)]
+    pub profile: Option<Profile>,
+
+    /// Only list target directories next to the manifest of their project: package.json for node_modules, Cargo.toml for target, pyproject.toml or requirements.txt for virtualenvs.
+    #[arg(long = "require-manifest", global = true)]
+    pub require_manifest: bool,
+
+    /// Only list directories at least this big, e.g. 100MB or 1.5GB.
+    #[arg(long = "min-size", value_name = "SIZE", value_parser = parse_size, global = true)]
+    pub min_size: Option<u64>,
+
+    /// Only list directories whose project hasn't been modified for this long, e.g. 30d, 12h, 2w, 6mo or 1y.
+    #[arg(long = "older-than", value_name = "AGE", value_parser = parse_age, global = true)]
+    pub older_than: Option<i64>,
+
+    /// Report the space allocated on disk instead of the apparent size, counting hard-linked files only when deleting frees them.
+    #[arg(long = "disk-usage", global = true)]
+    pub disk_usage: bool,
+
+    /// Date directories by when a file inside was last read instead of when their project was last modified. Needs a filesystem mounted with access times (relatime or strictatime).
+    #[arg(long = "use-atime", global = true)]
+    pub use_atime: bool,
+
+    /// Stop measuring a directory after this long and show the size counted so far as partial, e.g. 5s. F recounts it in full.
+    #[arg(long = "size-timeout", value_name = "DURATION", value_parser = parse_age, global = true)]
+    pub size_timeout: Option<i64>,
+
+    /// Stop measuring a directory after this many files and directories and show the size counted so far as partial.
+    #[arg(long = "size-max-entries", value_name = "N", global = true)]
+    pub size_max_entries: Option<u64>,
+
+    /// Projects modified within this long, or whose lockfile changed after the directory was built, count as active.
+    #[arg(long = "active-within", value_name = "AGE", value_parser = parse_age, default_value = "7d", global = true)]
+    pub active_within: i64,
+
+    /// Hide directories of active projects.
+    #[arg(long = "skip-active", global = true)]
+    pub skip_active: bool,
+
+    /// Load the results of the last scan of the same directories instead of walking them again. Projects modified since are measured again, but new ones aren't found.
+    #[arg(long, global = true)]
+    pub cached: bool,
+
+    /// Walk into symlinked directories, walking each directory only once to avoid cycles.
+    #[arg(long = "follow-symlinks", global = true)]
+    pub follow_symlinks: bool,
+
+    /// Stay on the filesystem of each starting directory, like `du -x`, skipping mounted drives and network or FUSE filesystems below it.
+    #[arg(long = "same-filesystem", global = true)]
+    pub same_filesystem: bool,
+
+    /// Allow deleting directories of globally installed tooling (nvm, volta, pnpm stores, the cargo registry, /usr/lib/node_modules), which are refused otherwise. They are only listed with --include-system.
+    #[arg(long = "allow-global", global = true)]
+    pub allow_global: bool,
+
+    /// Also list the npm, yarn, pnpm, pip, cargo and Gradle caches of this machine.
+    #[arg(long, global = true)]
+    pub caches: bool,
+
+    /// Print a JSON line with what was found, deleted and reclaimed to stderr when the interface exits.
+    #[arg(long, global = true)]
+    pub summary: bool,
+
+    /// Keep watching the searched directories after the scan, listing target directories as they are created.
+    #[arg(long, global = true)]
+    pub watch: bool,
+
+    /// Where --delete-all and clean list each directory, and how to reinstall its project, before deleting it. A new file in the state directory by default.
+    #[arg(long = "restore-manifest", value_name = "PATH", global = true)]
+    pub restore_manifest: Option<PathBuf>,
+
+    /// Write deletions as an `rm -rf` shell script at PATH instead of making them: `clean` writes every directory it would delete, and 'W' in the interface the marked ones (to rskill-delete.sh without this option).
+    #[arg(long = "emit-script", value_name = "PATH", global = true)]
+    pub emit_script: Option<PathBuf>,
+
+    /// After each cleanup of clean or daemon, post a JSON summary of the directories deleted and the space reclaimed to this URL. Needs curl.
+    #[arg(long = "notify-webhook", value_name = "URL", value_parser = notify::parse_webhook, global = true)]
+    pub notify_webhook: Option<String>,
+
+    /// Scan a directory on another machine over SSH, e.g. user@host:/srv/projects, and delete there. Needs key or agent authentication.
+    #[arg(long, value_name = "USER@HOST:PATH", value_parser = parse_remote, conflicts_with_all = ["full", "all_drives", "pick_root", "watch", "cached", "trash"], global = true)]
+    pub ssh: Option<RemoteRoot>,
+
+    /// Scan and delete at low CPU and disk priority, pausing between batches of files, so other programs aren't slowed down.
+    #[arg(long = "io-nice", global = true)]
+    pub io_nice: bool,
+
+    /// Walk and measure directories with at most this many threads. Defaults to one per CPU.
+    #[arg(long, value_name = "N", global = true)]
+    pub threads: Option<NonZeroUsize>,
+
+    /// Walk the directories of each disk with this many threads of their own. By default, directories on several disks are walked with two threads per spinning disk and one per CPU for others.
+    #[arg(
+        long = "per-disk-threads",
+        value_name = "N",
+        conflicts_with = "threads",
+        global = true
+    )]
+    pub per_disk_threads: Option<NonZeroUsize>,
+
+    /// Record every deletion with its time, size and outcome in this file instead of the default history log.
+    #[arg(long = "log-file", value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// Protect paths matching a glob from deletion, as [LEVEL:]GLOB where LEVEL is warn, double-confirm or refuse (the default). Adds to the rules of the config file. Can be repeated.
+    #[arg(long, value_name = "RULE", value_parser = parse_protect, global = true)]
+    pub protect: Vec<ProtectRule>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_scan_roots_resolve_bookmarks_and_label_rows() {
+        let dir = TempDir::new("roots");
+        std::fs::create_dir_all(dir.join("work/api")).unwrap();
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let mut args = Args::parse_from(["rskill", "scan", "@work", "@oss"]);
+        assert!(args.roots().unwrap_err().contains("no root named @work"));
+
+        args.bookmarks = BTreeMap::from([
+            (String::from("work"), dir.join("work").display().to_string()),
+            (String::from("oss"), dir.join("src").display().to_string()),
+        ]);
+        let roots = args.roots().unwrap();
+        assert_eq!(roots.len(), 2);
+        let app = App::new(&args, None, Instant::now());
+        let work = roots[0].1.join("api/node_modules");
+        assert_eq!(app.root_label(&work), Some("@work"));
+        assert_eq!(app.root_label(Path::new("/elsewhere")), None);
+    }
+
+    #[test]
+    fn test_subcommands_accept_global_options() {
+        let args = Args::parse_from(["rskill", "clean", "--yes", "-d", "/tmp"]);
+        assert_eq!(args.command(), Command::Clean { yes: true });
+        assert_eq!(args.directory, ["/tmp"]);
+        assert!(args.is_headless());
+
+        let args = Args::parse_from(["rskill", "--json", "list"]);
+        assert_eq!(args.command(), Command::List);
+        assert_eq!(args.directory, ["."]);
+        let args = Args::parse_from(["rskill", "list", "-d", "/work", "-d", "/srv/builds"]);
+        assert_eq!(args.directory, ["/work", "/srv/builds"]);
+        assert_eq!(
+            Args::parse_from(["rskill"]).command(),
+            Command::Scan { roots: Vec::new() }
+        );
+    }
+
+    #[test]
+    fn test_completions_include_profile_names() {
+        let args = Args::parse_from(["rskill", "completions", "bash"]);
+        assert_eq!(args.command(), Command::Completions { shell: Shell::Bash });
+
+        let mut script = Vec::new();
+        write_completions(Shell::Bash, &mut script);
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("--profile"));
+        assert!(script.contains("node rust python java all"));
+    }
+
+    #[test]
+    fn test_daemon_takes_scan_filters() {
+        let args = Args::parse_from([
+            "rskill",
+            "daemon",
+            "--older-than",
+            "60d",
+            "--min-size",
+            "200MB",
+            "--schedule",
+            "0 3 * * 0",
+        ]);
+        assert!(
+            matches!(args.command(), Command::Daemon { schedule, .. } if schedule == "0 3 * * 0")
+        );
+        assert_eq!(args.min_size, Some(200 * 1024 * 1024));
+        assert!(Args::try_parse_from(["rskill", "daemon", "--schedule", "weekly"]).is_err());
+    }
+
+    #[test]
+    fn test_flags_summary_lists_options_in_effect() {
+        let args = Args::parse_from([
+            "rskill",
+            "-t",
+            "node_modules,target",
+            "--min-size",
+            "1KB",
+            "--trash",
+        ]);
+        assert_eq!(
+            args.flags_summary(),
+            [
+                "--target node_modules,target",
+                "--directory .",
+                "--min-size 1024B",
+                "--trash"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_delete_all_is_refused_without_the_interface() {
+        let check =
+            |args: &[&str]| Args::parse_from(["rskill"].iter().chain(args)).check_delete_all();
+        assert!(check(&["-D", "--json"]).is_err());
+        assert!(check(&["-D", "--output", "csv"]).is_err());
+        assert!(check(&["-D", "--json", "--dry-run"]).is_ok());
+        assert!(check(&["--json"]).is_ok());
+    }
+
+    #[test]
+    fn test_pending_modules_move_once_sized() {
+        let mut app = App::new(
+            &Args::parse_from(["rskill", "-s", "size"]),
+            None,
+            Instant::now(),
+        );
+        for path in ["/a/node_modules", "/b/node_modules", "/c/node_modules"] {
+            app.push_pending(PathBuf::from(path));
+        }
+        assert!(app.modules.iter().all(|m| m.sizing));
+        app.scroll = 2;
+
+        let sized = |path: &str, size| FoundDir {
+            size,
+            ..FoundDir::pending(PathBuf::from(path))
+        };
+        app.update_module(sized("/c/node_modules", 5));
+        assert_eq!(app.visible, vec![2, 0, 1]);
+        assert_eq!(app.selected(), Some(2), "the updated row stays highlighted");
+        assert_eq!(app.total_size, 5);
+
+        app.drop_module(Path::new("/a/node_modules"));
+        assert_eq!(app.visible, vec![2, 1]);
+        assert!(app.modules[0].hidden);
+
+        app.update_module(sized("/b/node_modules", 7));
+        assert_eq!(app.visible, vec![1, 2]);
+        assert_eq!(app.selected(), Some(2));
+        assert_eq!(app.total_size, 12);
+    }
+
+    #[test]
+    fn test_protected_modules_need_double_confirmation() {
+        let mut app = App::new(
+            &Args::parse_from([
+                "rskill",
+                "--protect",
+                "refuse:/prod",
+                "--protect",
+                "double-confirm:/shared",
+            ]),
+            None,
+            Instant::now(),
+        );
+        for path in ["/prod/node_modules", "/shared/node_modules"] {
+            app.push_module(NodeModule::from(FoundDir::pending(PathBuf::from(path))));
+        }
+
+        app.delete_module();
+        assert_eq!(app.pending_delete, None);
+        assert_eq!(app.modules[0].state, DeleteState::Present);
+
+        app.scroll = 1;
+        app.set_all_marks(true);
+        app.delete_marked();
+        assert_eq!(app.modules[1].state, DeleteState::Present);
+
+        app.delete_module();
+        app.on_key(KeyCode::Char('y'));
+        assert_eq!(app.pending_delete, Some(1), "one confirmation isn't enough");
+        app.on_key(KeyCode::Char('n'));
+        assert_eq!(app.pending_delete, None);
+        assert_eq!(app.modules[1].state, DeleteState::Present);
+    }
+
+    #[tokio::test]
+    async fn test_enter_lists_dependencies_by_size() {
+        let root = TempDir::new("drill");
+        let modules = root.join("app/node_modules");
+        for (package, size) in [("left-pad", 10), ("react", 500), ("lodash", 200)] {
+            std::fs::create_dir_all(modules.join(package)).unwrap();
+            std::fs::write(modules.join(package).join("index.js"), "x".repeat(size)).unwrap();
+        }
+
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        app.push_module(NodeModule::from(FoundDir::pending(modules)));
+        app.on_key(KeyCode::Enter);
+        assert_eq!(app.drill_down, Some(0));
+        while !matches!(app.details.get(&0), Some(Some(_))) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            app.drain_details();
+        }
+
+        let Some(Some(Ok(breakdown))) = app.details.get(&0) else {
+            panic!("the breakdown should be readable");
+        };
+        let names: Vec<&str> = breakdown.largest.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["react", "lodash", "left-pad"]);
+
+        app.on_key(KeyCode::End);
+        app.on_key(KeyCode::Down);
+        assert_eq!(app.drill_scroll, 2);
+        app.on_key(KeyCode::Esc);
+        assert_eq!(app.drill_down, None);
+        assert!(!app.cancel.is_cancelled(), "Esc only closes the drill-down");
+    }
+
+    #[tokio::test]
+    async fn test_nested_directories_go_with_their_parent() {
+        let mut app = App::new(
+            &Args::parse_from(["rskill", "--include-nested"]),
+            None,
+            Instant::now(),
+        );
+        for (path, size) in [
+            ("/gone/app/node_modules/pkg/node_modules", 4),
+            ("/gone/app/node_modules", 10),
+            ("/gone/lib/node_modules", 3),
+        ] {
+            app.push_module(NodeModule::from(FoundDir {
+                size,
+                ..FoundDir::pending(PathBuf::from(path))
+            }));
+        }
+        assert_eq!(app.total_size, 13);
+
+        app.set_all_marks(true);
+        app.delete_marked();
+        assert_eq!(app.modules[0].state, DeleteState::Present);
+        assert!(app.modules[1].is_removed() && app.modules[2].is_removed());
+        assert_eq!(app.batch.as_ref().unwrap().total, 2);
+    }
+
+    #[tokio::test]
+    async fn test_delete_all_tracks_batch_progress() {
+        let root = TempDir::new("batch");
+        std::fs::create_dir_all(root.join("app/node_modules")).unwrap();
+
+        let mut app = App::new(
+            &Args::parse_from(["rskill", "--delete-all"]),
+            None,
+            Instant::now(),
+        );
+        app.push_module(NodeModule::from(FoundDir {
+            size: 10,
+            ..FoundDir::pending(root.join("app/node_modules"))
+        }));
+        let batch = app.batch.as_ref().unwrap();
+        assert_eq!(
+            (batch.total, batch.total_bytes, batch.ratio()),
+            (1, 10, 0.0)
+        );
+
+        app.finish_scan();
+        while app.batch.is_some() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            app.drain_delete_events();
+        }
+        assert_eq!(app.modules[0].state, DeleteState::Deleted);
+        assert_eq!(app.total_deleted, 10);
+        assert_eq!(
+            app.summary(),
+            SessionSummary {
+                found: 1,
+                deleted: 1,
+                failed: 0,
+                reclaimed: 10,
+            }
+        );
+        assert_eq!(app.summary().exit_code(), EXIT_CLEANED);
+        assert_eq!(SessionSummary::default().exit_code(), EXIT_NOTHING_FOUND);
+        let looked = SessionSummary {
+            found: 1,
+            ..SessionSummary::default()
+        };
+        assert_eq!(looked.exit_code(), 0);
+    }
+
+    #[test]
+    fn test_root_picker_browses_subdirectories() {
+        let root = TempDir::new("picker");
+        for dir in ["Work/api", "apps", ".config"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("notes.txt"), "").unwrap();
+
+        let mut picker = RootPicker::new(root.to_path_buf());
+        assert_eq!(picker.entries, ["apps", "Work", ".config"]);
+
+        picker.on_key(KeyCode::Down);
+        picker.on_key(KeyCode::Enter);
+        assert_eq!(picker.dir, root.join("Work"));
+        assert_eq!(picker.entries, ["api"]);
+
+        picker.on_key(KeyCode::Left);
+        assert_eq!(picker.dir, *root);
+        assert_eq!(
+            picker.selected, 1,
+            "the directory just left stays highlighted"
+        );
+        assert_eq!(
+            picker.on_key(KeyCode::Char('s')),
+            PickerAction::Scan(root.to_path_buf())
+        );
+        assert_eq!(picker.on_key(KeyCode::Esc), PickerAction::Cancel);
+    }
+
+    #[test]
+    fn test_hide_selected_removes_the_row() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        for path in ["/a/node_modules", "/b/node_modules"] {
+            app.push_module(NodeModule::from(FoundDir::pending(PathBuf::from(path))));
+        }
+        app.scroll = 1;
+
+        app.hide_selected();
+        assert_eq!(app.visible, vec![0]);
+        assert_eq!(app.pending_exclude, Some(1));
+        app.on_key(KeyCode::Char('n'));
+        assert_eq!(app.pending_exclude, None);
+
+        app.apply_filter();
+        assert_eq!(app.visible, vec![0], "hidden rows stay hidden");
+    }
+
+    #[test]
+    fn test_scripts_are_only_written_for_local_directories() {
+        let dir = TempDir::new("remote");
+        let script = dir.join("clean.sh");
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        app.push_module(NodeModule::from(FoundDir::pending(PathBuf::from(
+            "/srv/app/node_modules",
+        ))));
+        app.modules[0].marked = true;
+        app.script_path = script.clone();
+        app.deleter = Deleter::default().with_remote(Some(String::from("build@ci")));
+
+        app.write_script();
+        assert!(!script.exists());
+        assert!(app.status.as_ref().unwrap().0.contains("local directories"));
+    }
+
+    #[test]
+    fn test_local_actions_are_refused_for_remote_directories() {
+        let args = Args::parse_from([
+            "rskill",
+            "--ssh",
+            "build@ci:/srv",
+            "--restore-manifest",
+            "m",
+        ]);
+        let mut app = App::new(&args, None, Instant::now());
+        assert!(app.restore_manifest.is_none());
+        for path in ["/srv/app/node_modules", "/srv/lib/node_modules"] {
+            app.push_module(NodeModule::from(FoundDir::pending(PathBuf::from(path))));
+        }
+
+        app.status = None;
+        app.on_key(KeyCode::Char('o'));
+        assert!(app.status.as_ref().unwrap().0.contains("local directories"));
+        app.on_key(KeyCode::Enter);
+        assert!(app.drill_down.is_none());
+        app.on_key(KeyCode::Char('x'));
+        assert!(app.pending_exclude.is_none());
+        assert!(app.modules[0].hidden);
+    }
+
+    #[test]
+    fn test_denied_deletions_can_be_retried_as_administrator() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        for path in ["/a/node_modules", "/b/node_modules"] {
+            app.push_module(NodeModule::from(FoundDir::pending(PathBuf::from(path))));
+        }
+        let aside = PathBuf::from("/a/.node_modules.rskill-deleting-1-0");
+        app.modules[0].state = DeleteState::Failed(DeleteError {
+            message: String::from("Permission denied"),
+            kind: Some(std::io::ErrorKind::PermissionDenied),
+            moved_to: Some(aside.clone()),
+        });
+        app.modules[1].state = DeleteState::Failed(String::from("Permission denied").into());
+
+        app.scroll = 1;
+        app.on_key(KeyCode::Char('S'));
+        assert_eq!(
+            app.pending_elevation, None,
+            "only denied deletions are offered"
+        );
+        app.scroll = 0;
+        app.on_key(KeyCode::Char('S'));
+        assert_eq!(app.pending_elevation, Some(0));
+        app.on_key(KeyCode::Char('n'));
+        assert_eq!(app.pending_elevation, None);
+
+        app.on_key(KeyCode::Char('S'));
+        let (index, path) = app.confirm_elevation().unwrap();
+        assert_eq!(path, aside, "the directory is removed where it was moved");
+        app.finish_elevation(index, Ok(()));
+        assert_eq!(app.modules[0].state, DeleteState::Deleted);
+    }
+
+    #[tokio::test]
+    async fn test_double_click_lists_subdirectories() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        for path in ["/a/node_modules", "/b/node_modules"] {
+            app.push_module(NodeModule::from(FoundDir::pending(PathBuf::from(path))));
+        }
+        let now = Instant::now();
+
+        app.click_row(1, now);
+        assert_eq!((app.scroll, app.drill_down), (1, None));
+        app.click_row(0, now + Duration::from_millis(100));
+        assert_eq!((app.scroll, app.drill_down), (0, None), "another row");
+        app.click_row(0, now + Duration::from_secs(1));
+        assert_eq!(app.drill_down, None, "too slow");
+        app.click_row(0, now + Duration::from_millis(1_200));
+        assert_eq!(app.drill_down, Some(0));
+    }
+
+    #[test]
+    fn test_copy_marked_paths_needs_marks() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        app.push_module(NodeModule::from(FoundDir::pending(PathBuf::from(
+            "/a/node_modules",
+        ))));
+
+        app.on_key(KeyCode::Char('Y'));
+        let status = app.status.as_ref().map(|(message, _)| message.as_str());
+        assert_eq!(status, Some("No directories are marked, m marks one"));
+    }
+
+    #[test]
+    fn test_cycle_sort_keeps_selection() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        for (path, size) in [
+            ("/a/node_modules", 1),
+            ("/b/node_modules", 3),
+            ("/c/node_modules", 2),
+        ] {
+            app.push_module(NodeModule::from(FoundDir {
+                size,
+                ..FoundDir::new(PathBuf::from(path), Err(String::new()))
+            }));
+        }
+        app.scroll = 1;
+        assert_eq!(app.selected(), Some(1));
+
+        app.cycle_sort();
+        assert_eq!(app.visible, vec![1, 2, 0]);
+        assert_eq!(app.selected(), Some(1));
+
+        app.cycle_sort();
+        assert_eq!(app.visible, vec![0, 2, 1]);
+        assert_eq!(app.selected(), Some(1));
+    }
+
+    #[test]
+    fn test_project_sort_groups_targets_with_subtotals() {
+        let args = Args::parse_from(["rskill", "--sort", "project"]);
+        let mut app = App::new(&args, None, Instant::now());
+        for (path, size) in [
+            ("/work/web/node_modules", 1),
+            ("/src/Api/target", 5),
+            ("/work/api/node_modules", 3),
+            ("/src/Api/node_modules", 2),
+        ] {
+            app.push_module(NodeModule::from(FoundDir::with_size(
+                PathBuf::from(path),
+                size,
+                std::time::SystemTime::now(),
+            )));
+        }
+
+        assert_eq!(app.visible, vec![3, 1, 2, 0]);
+        let rows = app.rows();
+        assert_eq!(
+            rows[0],
+            ListRow::Project {
+                path: PathBuf::from("/src/Api"),
+                size: 7,
+                count: 2
+            }
+        );
+        assert_eq!(rows[1..], [0, 1, 2, 3].map(ListRow::Module));
+        app.select_row(0);
+        assert_eq!(app.selected(), Some(3));
+        app.select_row(4);
+        assert_eq!(app.selected(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_workspace_members_collapse_into_one_row() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        for (path, workspace) in [
+            ("/mono/node_modules", Some("/mono")),
+            ("/alone/node_modules", None),
+            ("/mono/packages/a/node_modules", Some("/mono")),
+            ("/mono/packages/b/node_modules", Some("/mono")),
+        ] {
+            app.push_module(NodeModule::from(FoundDir {
+                workspace: workspace.map(PathBuf::from),
+                ..FoundDir::with_size(PathBuf::from(path), 1, std::time::SystemTime::now())
+            }));
+        }
+
+        assert_eq!(app.visible, vec![0, 1]);
+        let (root, members) = app.workspace_members(0).unwrap();
+        assert_eq!((root, members), (Path::new("/mono"), vec![0, 2, 3]));
+        app.on_key(KeyCode::Char(' '));
+        assert_eq!(app.pending_workspace, Some(vec![0, 2, 3]));
+        assert_eq!(app.marked_summary(), (0, 0), "deleting marks nothing");
+        app.on_key(KeyCode::Esc);
+        assert_eq!(app.pending_workspace, None);
+        app.on_key(KeyCode::Char('m'));
+        assert_eq!(app.marked_summary(), (3, 3));
+        app.on_key(KeyCode::Char('m'));
+        assert_eq!(app.marked_summary(), (0, 0));
+
+        app.on_key(KeyCode::Char('w'));
+        assert_eq!(app.visible, vec![0, 1, 2, 3]);
+        app.select_visible(3);
+        app.on_key(KeyCode::Char('w'));
+        assert_eq!(app.visible, vec![0, 1]);
+        assert_eq!(app.selected(), Some(0));
+
+        app.modules[0].hidden = true;
+        app.apply_filter();
+        assert_eq!(app.visible, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_deleting_a_workspace_leaves_other_marks_alone() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        for (path, workspace) in [
+            ("/mono/a/node_modules", Some("/mono")),
+            ("/alone/node_modules", None),
+            ("/mono/b/node_modules", Some("/mono")),
+        ] {
+            app.push_module(NodeModule::from(FoundDir {
+                workspace: workspace.map(PathBuf::from),
+                ..FoundDir::with_size(PathBuf::from(path), 1, std::time::SystemTime::now())
+            }));
+        }
+        app.select_visible(1);
+        app.on_key(KeyCode::Char('m'));
+        app.select_visible(0);
+        app.on_key(KeyCode::Char(' '));
+        app.on_key(KeyCode::Char('y'));
+
+        assert!(app.modules[0].is_removed() && app.modules[2].is_removed());
+        assert_eq!(app.modules[1].state, DeleteState::Present);
+        assert!(app.modules[1].marked);
+    }
+
+    #[test]
+    fn test_largest_skips_unmeasured_and_deleted_directories() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        for (path, size) in [
+            ("/a/node_modules", 3),
+            ("/b/node_modules", 9),
+            ("/c/node_modules", 5),
+        ] {
+            app.push_module(NodeModule::from(FoundDir::with_size(
+                PathBuf::from(path),
+                size,
+                std::time::SystemTime::now(),
+            )));
+        }
+        app.push_pending(PathBuf::from("/d/node_modules"));
+        app.modules[2].state = DeleteState::Deleted;
+
+        assert_eq!(app.largest(5), [1, 0]);
+        assert_eq!(app.largest(1), [1]);
+        app.on_key(KeyCode::Char('b'));
+        assert!(app.show_chart);
+
+        let selected = app.selected();
+        for key in [KeyCode::Down, KeyCode::Char(' '), KeyCode::Char('d')] {
+            app.on_key(key);
+        }
+        assert_eq!(app.selected(), selected);
+        assert!(app.modules.iter().all(|m| m.state != DeleteState::Queued));
+        assert!(!app.confirm_delete_marked);
+        app.on_key(KeyCode::Char('b'));
+        app.on_key(KeyCode::Down);
+        assert_ne!(app.selected(), selected);
+    }
+
+    #[test]
+    fn test_tags_are_saved_and_restored_by_the_next_session() {
+        let dir = TempDir::new("app-tags");
+        let path = dir.join("tags.json");
+        let found = || NodeModule::from(FoundDir::pending(PathBuf::from("/a/node_modules")));
+
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        app.load_notes(path.clone());
+        app.push_module(found());
+        app.on_key(KeyCode::Char('2'));
+        assert_eq!(app.modules[0].tag, Some(Tag::Later));
+        app.on_key(KeyCode::Char('n'));
+        for key in "ask Ana".chars() {
+            app.on_key(KeyCode::Char(key));
+        }
+        app.on_key(KeyCode::Enter);
+        assert_eq!(app.modules[0].note.as_deref(), Some("ask Ana"));
+
+        let mut next = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        next.load_notes(path.clone());
+        next.push_module(found());
+        assert_eq!(next.modules[0].tag, Some(Tag::Later));
+        assert_eq!(next.modules[0].note.as_deref(), Some("ask Ana"));
+        next.on_key(KeyCode::Char('2'));
+        assert_eq!(next.modules[0].tag, None, "the same key untags");
+        assert!(cache::load_notes(&path).unwrap()[&found().path]
+            .tag
+            .is_none());
+
+        // Another session tags a directory in the meantime.
+        app.push_module(NodeModule::from(FoundDir::pending(PathBuf::from(
+            "/b/node_modules",
+        ))));
+        app.scroll = app.visible.iter().position(|&i| i == 1).unwrap();
+        app.on_key(KeyCode::Char('1'));
+        next.on_deleted(0, Ok(()));
+        let saved = cache::load_notes(&path).unwrap();
+        assert_eq!(saved.len(), 1, "the deleted directory loses its note");
+        assert_eq!(saved[Path::new("/b/node_modules")].tag, Some(Tag::Keep));
+    }
+
+    #[test]
+    fn test_list_window_scrolls_only_to_keep_the_selection_in_view() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        for i in 0..1_000 {
+            let path = PathBuf::from(format!("/p{i}/node_modules"));
+            app.push_module(NodeModule::from(FoundDir::pending(path)));
+        }
+        let rows = app.rows();
+        assert_eq!(app.list_window(&rows, 10), 0..10);
+
+        app.scroll = 12;
+        assert_eq!(app.list_window(&rows, 10), 3..13);
+        app.scroll = 8;
+        assert_eq!(app.list_window(&rows, 10), 3..13, "still in view");
+        app.scroll = 999;
+        assert_eq!(app.list_window(&rows, 10), 990..1_000);
+        assert_eq!(app.list_window(&rows, 100), 900..1_000);
+
+        // The window shrinking keeps the selection in view.
+        app.scroll = 950;
+        assert_eq!(app.list_window(&rows, 10), 941..951);
+    }
+
+    #[test]
+    fn test_status_bar_follows_the_state() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        app.push_module(NodeModule::from(FoundDir::with_size(
+            PathBuf::from("/a/node_modules"),
+            1,
+            std::time::SystemTime::now(),
+        )));
+        assert!(app.key_hints().contains("Esc stop the scan"));
+        assert_eq!(app.state_summary(), "");
+
+        app.finish_scan();
+        app.on_key(KeyCode::Char('m'));
+        assert!(app.key_hints().starts_with("d delete marked"));
+        app.on_key(KeyCode::Char('d'));
+        assert!(app.key_hints().starts_with("y confirm"));
+        app.on_key(KeyCode::Char('n'));
+
+        app.on_key(KeyCode::Char('/'));
+        app.on_key(KeyCode::Char('a'));
+        app.on_key(KeyCode::Enter);
+        app.cycle_sort();
+        assert_eq!(app.state_summary(), "Filter: a · Sort: size desc");
+    }
+
+    #[test]
+    fn test_tabs_filter_by_ecosystem_with_their_own_sort() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        for (path, size) in [
+            ("/a/node_modules", 1),
+            ("/a/target", 5),
+            ("/b/node_modules", 3),
+            ("/b/.venv", 2),
+        ] {
+            app.push_module(NodeModule::from(FoundDir::with_size(
+                PathBuf::from(path),
+                size,
+                std::time::SystemTime::now(),
+            )));
+        }
+        assert_eq!(
+            app.tabs(),
+            [
+                (String::from("node"), 4),
+                (String::from("python"), 2),
+                (String::from("rust"), 5)
+            ]
+        );
+
+        app.switch_tab(1);
+        assert_eq!(app.tab.as_deref(), Some("node"));
+        assert_eq!(app.visible, vec![0, 2]);
+        app.cycle_sort();
+        assert_eq!(app.visible, vec![2, 0]);
+
+        app.switch_tab(-1);
+        assert_eq!(app.tab, None);
+        assert!(app.sort.is_none(), "the first tab keeps its own sort");
+        assert_eq!(app.visible, vec![0, 1, 2, 3]);
+        app.switch_tab(-1);
+        assert_eq!(app.tab.as_deref(), Some("rust"));
+        assert_eq!(app.visible, vec![1]);
+        app.switch_tab(-2);
+        assert_eq!(app.tab.as_deref(), Some("node"));
+        assert_eq!(app.visible, vec![2, 0], "the sort of a tab is kept");
+    }
+
+    #[test]
+    fn test_restart_scan_keeps_marks_and_drops_missing_rows() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        let sized = |path: &str, size| {
+            FoundDir::with_size(PathBuf::from(path), size, std::time::SystemTime::now())
+        };
+        app.update_module(sized("/a/node_modules", 1));
+        app.update_module(sized("/b/node_modules", 2));
+        app.finish_scan();
+        app.scroll = 1;
+        app.toggle_mark();
+
+        app.restart_scan(CancelToken::default());
+        assert!(app.scanning);
+        app.push_pending(PathBuf::from("/b/node_modules"));
+        app.update_module(sized("/b/node_modules", 5));
+        app.update_module(sized("/c/node_modules", 3));
+        app.finish_scan();
+
+        assert_eq!(app.modules.len(), 3);
+        assert!(app.modules[1].marked);
+        assert!(app.modules[0].hidden, "rows not found again are gone");
+        assert_eq!(app.visible, vec![1, 2]);
+        assert_eq!(app.selected(), Some(1));
+        assert_eq!(app.total_size, 8);
+    }
+
+    #[test]
+    fn test_t_scans_for_other_targets() {
+        let mut app = App::new(&Args::parse_from(["rskill"]), None, Instant::now());
+        let sized = |path: &str, size| {
+            FoundDir::with_size(PathBuf::from(path), size, std::time::SystemTime::now())
+        };
+        app.update_module(sized("/a/node_modules", 1));
+        app.finish_scan();
+
+        app.on_key(KeyCode::Char('t'));
+        assert_eq!(app.target_input.as_deref(), Some("node_modules"));
+        for _ in 0.."node_modules".len() {
+            app.on_key(KeyCode::Backspace);
+        }
+        for c in "target, .venv".chars() {
+            app.on_key(KeyCode::Char(c));
+        }
+        app.on_key(KeyCode::Enter);
+        let targets = app.retarget.take().unwrap();
+        assert_eq!(targets, ["target", ".venv"]);
+
+        app.retarget_scan(targets, CancelToken::default());
+        assert!(app.visible.is_empty());
+        assert_eq!(app.total_size, 0);
+        app.update_module(sized("/a/target", 7));
+        app.finish_scan();
+        assert_eq!(app.visible, vec![1]);
+        assert_eq!(app.total_size, 7);
+    }
+
+    #[test]
+    fn test_max_depth_must_be_positive() {
+        assert_eq!(
+            Args::parse_from(["rskill", "--max-depth", "2"]).max_depth,
+            Some(2)
+        );
+        assert!(Args::try_parse_from(["rskill", "--max-depth", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_exclude_patterns_are_repeatable_globs() {
+        let args = Args::parse_from(["rskill", "-E", "**/vendor/**", "-E", "a,b"]);
+        assert_eq!(args.exclude_paths, ["**/vendor/**", "a,b"]);
+        assert!(Args::try_parse_from(["rskill", "-E", "[unclosed"]).is_err());
+        assert!(Args::try_parse_from(["rskill", "--match", "(unclosed"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_size_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("2KB"), Ok(2048));
+        assert_eq!(parse_size("100MB"), Ok(100 << 20));
+        assert_eq!(parse_size("1.5g"), Ok(3 << 29));
+        assert_eq!(parse_size("1 TB"), Ok(1 << 40));
+    }
+
+    #[test]
+    fn test_parse_size_rejects_garbage() {
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("10XB").is_err());
+    }
+
+    #[test]
+    fn test_parse_size_bands() {
+        assert_eq!(
+            parse_size_bands("500MB,5GB"),
+            Ok(SizeBands {
+                medium: 500 << 20,
+                large: 5 << 30
+            })
+        );
+        assert!(parse_size_bands("1GB,100MB").is_err());
+        assert!(parse_size_bands("100MB").is_err());
+    }
+
+    #[test]
+    fn test_parse_age_units() {
+        assert_eq!(parse_age("30d"), Ok(30 * 86400));
+        assert_eq!(parse_age("12h"), Ok(12 * 3600));
+        assert_eq!(parse_age("2w"), Ok(14 * 86400));
+        assert_eq!(parse_age("7"), Ok(7 * 86400));
+    }
+
+    #[test]
+    fn test_parse_age_rejects_garbage() {
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("3 fortnights").is_err());
+    }
+
+    #[test]
+    fn test_fuzzy_match_subsequence() {
+        assert!(fuzzy_match("/home/user/app/node_modules", "appnm"));
+    }
+
+    #[test]
+    fn test_fuzzy_match_case_insensitive() {
+        assert!(fuzzy_match("/home/user/MyApp/node_modules", "myapp"));
+    }
+
+    #[test]
+    fn test_fuzzy_match_out_of_order() {
+        assert!(!fuzzy_match("/home/user/app/node_modules", "nmapp"));
+    }
+
+    #[test]
+    fn test_fuzzy_match_empty_needle() {
+        assert!(fuzzy_match("/home/user/app/node_modules", ""));
+    }
 }
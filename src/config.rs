@@ -0,0 +1,175 @@
+//! Settings read from `config.toml` in the user's config directory.
+//!
+//! ```toml
+//! exclude = ["~/work/legacy/**"]
+//! theme = "light"
+//! size_bands = "500MB,5GB"
+//!
+//! [roots]
+//! work = "~/work"
+//!
+//! [colors]
+//! dangerous = "#b58900"
+//!
+//! [[protect]]
+//! pattern = "~/production"
+//! level = "refuse"
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use toml_edit::{value, Array, DocumentMut};
+
+use crate::{
+    cli::parse_size_bands,
+    fs::home_dir,
+    policy::ProtectRule,
+    theme::{ColorOverrides, ThemeName},
+};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Paths protected from deletion, on top of those given with `--protect`.
+    pub protect: Vec<ProtectRule>,
+    /// Globs left out of every scan, on top of those given with `--exclude`.
+    pub exclude: Vec<String>,
+    /// The theme used when `--theme` isn't given.
+    pub theme: Option<ThemeName>,
+    /// Colors replacing those of the theme.
+    pub colors: ColorOverrides,
+    /// The size bands of the list when `--size-bands` isn't given, as `MEDIUM,LARGE`.
+    pub size_bands: Option<String>,
+    /// Directories bookmarked by name, scanned with `rskill scan @name`.
+    pub roots: BTreeMap<String, String>,
+}
+
+/// `$XDG_CONFIG_HOME/rskill`, `%APPDATA%\rskill` on Windows, or `~/.config/rskill`.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+    };
+    base.filter(|dir| dir.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(".config")))
+        .map(|dir| dir.join("rskill"))
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+impl Config {
+    /// Reads the config at `path`. A missing file gives the defaults.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |message: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {message}", path.display()),
+            )
+        };
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                let config: Self = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+                config.colors.validate().map_err(invalid)?;
+                if let Some(bands) = &config.size_bands {
+                    parse_size_bands(bands).map_err(|e| invalid(format!("size_bands: {e}")))?;
+                }
+                Ok(config)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Appends `pattern` to the `exclude` list of the config at `path`, creating the file if needed
+/// and keeping the rest of it as written.
+pub fn add_exclude(path: &Path, pattern: &str) -> io::Result<()> {
+    let invalid = |message: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {message}", path.display()),
+        )
+    };
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    let mut document: DocumentMut = text.parse().map_err(|e| invalid(format!("{e}")))?;
+    document
+        .entry("exclude")
+        .or_insert_with(|| value(Array::new()))
+        .as_array_mut()
+        .ok_or_else(|| invalid(String::from("exclude must be a list")))?
+        .push(pattern);
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, document.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::ProtectLevel;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_load_protect_rules() {
+        let dir = TempDir::new("config");
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            "[[protect]]\npattern = \"~/production\"\n\n\
+             [[protect]]\npattern = \"/mnt/share\"\nlevel = \"double-confirm\"\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap();
+        let levels: Vec<_> = config.protect.iter().map(|rule| rule.level).collect();
+        assert_eq!(levels, [ProtectLevel::Refuse, ProtectLevel::DoubleConfirm]);
+        assert!(Config::load(&dir.join("missing.toml"))
+            .unwrap()
+            .protect
+            .is_empty());
+
+        std::fs::write(&path, "protect = 3").unwrap();
+        assert!(Config::load(&path).is_err());
+        std::fs::write(&path, "theme = \"light\"\n[colors]\nactive = \"grene\"\n").unwrap();
+        assert!(Config::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_add_exclude_keeps_existing_settings() {
+        let dir = TempDir::new("exclude");
+        let path = dir.join("rskill").join("config.toml");
+
+        add_exclude(&path, "/work/a/node_modules").unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(
+            &path,
+            format!("# mine\n{text}[[protect]]\npattern = \"/prod\"\n"),
+        )
+        .unwrap();
+        add_exclude(&path, "/work/b/node_modules").unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# mine\n"));
+        let config = Config::load(&path).unwrap();
+        assert_eq!(
+            config.exclude,
+            ["/work/a/node_modules", "/work/b/node_modules"]
+        );
+        assert_eq!(config.protect.len(), 1);
+    }
+}
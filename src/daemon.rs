@@ -0,0 +1,291 @@
+//! Unattended cleanups on a cron schedule, for `rskill daemon`.
+
+use std::{
+    fmt::Display,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+use croner::Cron;
+use tokio::net::TcpListener;
+
+use crate::{
+    cli::Args,
+    deleter,
+    metrics::{self, Metrics},
+    notify::{self, CleanReport},
+    output,
+    policy::Policy,
+    scanner::Scanner,
+};
+
+/// The longest the daemon sleeps before looking at the clock again, so that a machine waking
+/// from suspend doesn't postpone a cleanup that is due.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// Checks that `input` is a five field cron expression such as `0 3 * * 0`.
+pub fn parse_schedule(input: &str) -> Result<String, String> {
+    schedule(input)?;
+    Ok(input.trim().to_string())
+}
+
+fn schedule(input: &str) -> Result<Cron, String> {
+    Cron::new(input.trim())
+        .parse()
+        .map_err(|e| format!("invalid schedule '{input}': {e}"))
+}
+
+/// The first time matching `schedule` strictly after `after`.
+pub fn next_run(schedule: &Cron, after: &DateTime<Local>) -> Option<DateTime<Local>> {
+    schedule.find_next_occurrence(after, false).ok()
+}
+
+/// Prints `message` on its own timestamped line. Deletions are also recorded in the history
+/// log.
+fn log(message: impl Display) {
+    println!("{} {message}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+}
+
+/// Scans `start_dirs` and deletes what `scanner` finds at every time matching `schedule`,
+/// until the process is stopped, serving the [metrics](crate::metrics) of each cleanup at
+/// `metrics_addr` if given.
+pub async fn run(
+    schedule: &str,
+    metrics_addr: Option<SocketAddr>,
+    scanner: &Scanner,
+    start_dirs: &[PathBuf],
+    args: &Args,
+) -> io::Result<()> {
+    let cron = self::schedule(schedule).map_err(io::Error::other)?;
+    let deleter = args.deleter();
+    let policy = Policy::new(&args.protect, args.allow_global);
+    let metrics = Arc::new(Mutex::new(Metrics::default()));
+    if let Some(addr) = metrics_addr {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| io::Error::new(e.kind(), format!("could not listen on {addr}: {e}")))?;
+        metrics::serve(listener, Arc::clone(&metrics));
+        log(format_args!("serving metrics at http://{addr}/metrics"));
+    }
+
+    loop {
+        let Some(next) = next_run(&cron, &Local::now()) else {
+            log(format_args!("'{schedule}' never matches again, stopping"));
+            return Ok(());
+        };
+        log(format_args!(
+            "next cleanup at {}",
+            next.format("%Y-%m-%d %H:%M")
+        ));
+        while let Ok(left) = (next - Local::now()).to_std() {
+            if left.is_zero() {
+                break;
+            }
+            tokio::time::sleep(left.min(MAX_SLEEP)).await;
+        }
+
+        let scan_started = Instant::now();
+        let found = match scanner.scan_all(start_dirs).await {
+            Ok((found, _)) => found,
+            Err(e) => {
+                log(format_args!("scan failed: {e}"));
+                continue;
+            }
+        };
+        if let Ok(mut metrics) = metrics.lock() {
+            metrics.record_scan(&found, scan_started.elapsed());
+        }
+        let mut report = CleanReport::default();
+        let (count, reclaimed) = deleter::clean(
+            &found,
+            &deleter,
+            &policy,
+            args.restore_manifest.as_deref(),
+            |event| {
+                report.add(&event);
+                match event.timing() {
+                    Some(timing) => log(format_args!("{event} in {timing}")),
+                    None => log(event),
+                }
+            },
+        )
+        .await;
+        if let Ok(mut metrics) = metrics.lock() {
+            metrics.record_cleanup(count, reclaimed, report.failed);
+        }
+        log(format_args!(
+            "deleted {count} directories, reclaiming {}",
+            output::format_size(reclaimed, args.unit())
+        ));
+        if let Some(url) = &args.notify_webhook {
+            if let Err(e) = report.post(url).await {
+                log(format_args!(
+                    "could not notify {}: {e}",
+                    notify::redact(url)
+                ));
+            }
+        }
+    }
+}
+
+/// The kinds of service definition `rskill daemon --service` can print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ServiceKind {
+    /// A systemd user unit, for `~/.config/systemd/user/rskill.service`.
+    Systemd,
+    /// A launchd agent, for `~/Library/LaunchAgents/rskill.plist`.
+    Launchd,
+}
+
+/// The command line arguments of this process without `--service`, so that the service runs
+/// the daemon it was generated from.
+pub fn service_args(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--service" {
+            args.next();
+        } else if !arg.starts_with("--service=") {
+            kept.push(arg);
+        }
+    }
+    kept
+}
+
+/// A service definition running `exe` with `args` from `working_dir`, restarted if it stops.
+pub fn service_file(kind: ServiceKind, exe: &Path, args: &[String], working_dir: &Path) -> String {
+    let command: Vec<String> = std::iter::once(exe.display().to_string())
+        .chain(args.iter().cloned())
+        .collect();
+
+    match kind {
+        ServiceKind::Systemd => format!(
+            "[Unit]\n\
+             Description=rskill scheduled cleanup\n\
+             \n\
+             [Service]\n\
+             ExecStart={}\n\
+             WorkingDirectory={}\n\
+             Restart=on-failure\n\
+             \n\
+             [Install]\n\
+             WantedBy=default.target\n",
+            command
+                .iter()
+                .map(|arg| systemd_quote(arg))
+                .collect::<Vec<_>>()
+                .join(" "),
+            systemd_quote(&working_dir.display().to_string()),
+        ),
+        ServiceKind::Launchd => format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+             \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n\
+             <dict>\n\
+             \x20   <key>Label</key>\n\
+             \x20   <string>rskill</string>\n\
+             \x20   <key>ProgramArguments</key>\n\
+             \x20   <array>\n\
+             {}\
+             \x20   </array>\n\
+             \x20   <key>WorkingDirectory</key>\n\
+             \x20   <string>{}</string>\n\
+             \x20   <key>RunAtLoad</key>\n\
+             \x20   <true/>\n\
+             \x20   <key>KeepAlive</key>\n\
+             \x20   <true/>\n\
+             </dict>\n\
+             </plist>\n",
+            command
+                .iter()
+                .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+                .collect::<String>(),
+            xml_escape(&working_dir.display().to_string()),
+        ),
+    }
+}
+
+/// Quotes `arg` for an `ExecStart=` line when it needs it. `%` starts a specifier there.
+fn systemd_quote(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "\"'\\;$".contains(c)) {
+        return arg;
+    }
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "$$");
+    format!("\"{escaped}\"")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone, Timelike, Weekday};
+
+    #[test]
+    fn test_next_run_follows_schedule() {
+        assert!(parse_schedule("0 3 * * 0").is_ok());
+        assert!(parse_schedule("every sunday").is_err());
+        assert!(parse_schedule("0 25 * * *").is_err());
+
+        let cron = schedule("0 3 * * 0").unwrap();
+        // A Wednesday.
+        let after = Local.with_ymd_and_hms(2025, 1, 15, 12, 0, 0).unwrap();
+        let next = next_run(&cron, &after).unwrap();
+        assert_eq!(next.weekday(), Weekday::Sun);
+        assert_eq!((next.day(), next.hour(), next.minute()), (19, 3, 0));
+        assert!(next_run(&cron, &next).unwrap() > next);
+    }
+
+    #[test]
+    fn test_service_args_drop_service_flag() {
+        let args = [
+            "daemon",
+            "--schedule",
+            "0 3 * * 0",
+            "--service",
+            "systemd",
+            "--trash",
+        ];
+        assert_eq!(
+            service_args(args.map(String::from)),
+            ["daemon", "--schedule", "0 3 * * 0", "--trash"]
+        );
+        assert_eq!(
+            service_args(["daemon", "--service=launchd"].map(String::from)),
+            ["daemon"]
+        );
+    }
+
+    #[test]
+    fn test_service_files_quote_arguments() {
+        let args = service_args(["daemon", "--schedule", "0 3 * * 0"].map(String::from));
+        let exe = Path::new("/usr/bin/rskill");
+
+        let unit = service_file(ServiceKind::Systemd, exe, &args, Path::new("/home/me"));
+        assert!(unit.contains("ExecStart=/usr/bin/rskill daemon --schedule \"0 3 * * 0\"\n"));
+        assert!(unit.contains("WorkingDirectory=/home/me\n"));
+
+        let plist = service_file(
+            ServiceKind::Launchd,
+            exe,
+            &[String::from("a<b")],
+            Path::new("/Users/me"),
+        );
+        assert!(plist.contains("<string>/usr/bin/rskill</string>"));
+        assert!(plist.contains("<string>a&lt;b</string>"));
+    }
+}
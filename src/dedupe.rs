@@ -0,0 +1,486 @@
+//! Identical files across target directories turned into links to one copy, for
+//! `rskill dedupe`.
+//!
+//! A duplicate is replaced by a copy-on-write clone of the first copy where the filesystem can
+//! make one (btrfs, XFS, APFS), keeping its owner, permissions and modification time. Elsewhere
+//! it's only replaced with `--hard-links`, by a hard link, which then shares the timestamps of
+//! the first copy and sees its changes. Files are only grouped with others on the same device
+//! and with the same owner and permissions, and are compared byte for byte right before being
+//! replaced.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    hash::{DefaultHasher, Hasher},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use crate::output::{format_size, SizeUnit};
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Files with the same contents, the first being the copy the others will point to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub files: Vec<PathBuf>,
+    /// Bytes freed once the others point to the first copy.
+    pub savings: u64,
+    /// Whether the filesystem can clone the first copy, or the others need hard links.
+    pub reflinks: bool,
+}
+
+/// What [`apply`] would do to the files below the scanned directories.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DedupePlan {
+    pub groups: Vec<DuplicateGroup>,
+    /// Bytes freed once every duplicate points to its group's first copy.
+    pub savings: u64,
+}
+
+/// How a duplicate was replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Reflink,
+    HardLink,
+}
+
+/// What [`apply`] did.
+#[derive(Debug, Default)]
+pub struct DedupeOutcome {
+    pub reflinked: usize,
+    pub hard_linked: usize,
+    /// Duplicates left alone because only a hard link could replace them.
+    pub skipped: usize,
+    pub saved: u64,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// The device, size, owner and permissions a file must share with its duplicates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FileKey {
+    device: u64,
+    size: u64,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+}
+
+fn file_identity(metadata: &std::fs::Metadata, index: usize) -> (FileKey, u64) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let _ = index;
+        let key = FileKey {
+            device: metadata.dev(),
+            size: metadata.len(),
+            mode: metadata.mode(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+        };
+        (key, metadata.ino())
+    }
+    #[cfg(not(unix))]
+    {
+        let key = FileKey {
+            device: 0,
+            size: metadata.len(),
+            mode: u32::from(metadata.permissions().readonly()),
+            uid: 0,
+            gid: 0,
+        };
+        (key, index as u64)
+    }
+}
+
+fn content_hash(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.write(&buffer[..read]);
+    }
+}
+
+fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let (mut left, mut right) = (vec![0; READ_BUFFER_SIZE], vec![0; READ_BUFFER_SIZE]);
+    loop {
+        let read = a.read(&mut left)?;
+        if read == 0 {
+            return Ok(b.read(&mut right[..1])? == 0);
+        }
+        if b.read_exact(&mut right[..read]).is_err() || left[..read] != right[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Finds the files below `roots` whose contents are duplicated. Empty files and files already
+/// linked to each other aren't counted. Whether each device can make reflinks is found by
+/// cloning a file of it once, into a temporary file that is removed right away.
+pub fn plan(roots: &[PathBuf]) -> DedupePlan {
+    let mut by_key: HashMap<FileKey, Vec<(PathBuf, u64)>> = HashMap::new();
+    let files = roots
+        .iter()
+        .flat_map(|root| WalkDir::new(root).into_iter().flatten())
+        .filter(|entry| entry.file_type().is_file());
+    for (index, entry) in files.enumerate() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.len() == 0 {
+            continue;
+        }
+        let (key, inode) = file_identity(&metadata, index);
+        by_key
+            .entry(key)
+            .or_default()
+            .push((entry.into_path(), inode));
+    }
+
+    let candidates: Vec<(FileKey, Vec<(PathBuf, u64)>)> = by_key
+        .into_iter()
+        .filter(|(_, files)| {
+            files
+                .iter()
+                .map(|(_, inode)| inode)
+                .collect::<HashSet<_>>()
+                .len()
+                > 1
+        })
+        .collect();
+
+    let mut groups: Vec<(DuplicateGroup, u64)> = candidates
+        .into_par_iter()
+        .flat_map_iter(|(key, files)| {
+            let size = key.size;
+            let mut by_hash: HashMap<u64, Vec<(PathBuf, u64)>> = HashMap::new();
+            for (path, inode) in files {
+                if let Ok(hash) = content_hash(&path) {
+                    by_hash.entry(hash).or_default().push((path, inode));
+                }
+            }
+            by_hash.into_values().filter_map(move |mut files| {
+                files.sort();
+                let source_inode = files.first()?.1;
+                let others: HashSet<u64> = files
+                    .iter()
+                    .map(|(_, inode)| *inode)
+                    .filter(|inode| *inode != source_inode)
+                    .collect();
+                if others.is_empty() {
+                    return None;
+                }
+                let savings = others.len() as u64 * size;
+                let files = files
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(i, (_, inode))| *i == 0 || *inode != source_inode)
+                    .map(|(_, (path, _))| path)
+                    .collect();
+                let group = DuplicateGroup {
+                    size,
+                    files,
+                    savings,
+                    reflinks: false,
+                };
+                Some((group, key.device))
+            })
+        })
+        .collect();
+    groups.sort_by(|a, b| a.0.files[0].cmp(&b.0.files[0]));
+
+    let mut reflinks: HashMap<u64, bool> = HashMap::new();
+    for (group, device) in &mut groups {
+        group.reflinks = *reflinks
+            .entry(*device)
+            .or_insert_with(|| can_reflink(&group.files[0], &group.files[1]));
+    }
+    DedupePlan {
+        savings: groups.iter().map(|(group, _)| group.savings).sum(),
+        groups: groups.into_iter().map(|(group, _)| group).collect(),
+    }
+}
+
+impl DedupePlan {
+    /// The number of files that would be replaced.
+    pub fn duplicates(&self) -> usize {
+        self.groups.iter().map(|group| group.files.len() - 1).sum()
+    }
+
+    /// The summary printed by `dedupe --dry-run`, with `--hard-links` if `hard_links` is set.
+    pub fn report(&self, unit: SizeUnit, hard_links: bool) -> String {
+        let duplicates = |groups: &[&DuplicateGroup]| -> usize {
+            groups.iter().map(|group| group.files.len() - 1).sum()
+        };
+        let (linked, skipped): (Vec<_>, Vec<_>) = self
+            .groups
+            .iter()
+            .partition(|group| group.reflinks || hard_links);
+        let hard_linked: Vec<_> = linked
+            .iter()
+            .copied()
+            .filter(|group| !group.reflinks)
+            .collect();
+
+        let mut report = format!(
+            "Would link {} duplicate files in {} groups, saving {}\n",
+            duplicates(&linked),
+            linked.len(),
+            format_size(linked.iter().map(|group| group.savings).sum(), unit)
+        );
+        if !hard_linked.is_empty() {
+            report.push_str(&format!(
+                "{} of them as hard links, which share the timestamps of the copy they point to and see its changes\n",
+                duplicates(&hard_linked)
+            ));
+        }
+        if !skipped.is_empty() {
+            report.push_str(&format!(
+                "Would skip {} duplicate files on filesystems without reflinks, saving {} more with --hard-links\n",
+                duplicates(&skipped),
+                format_size(skipped.iter().map(|group| group.savings).sum(), unit)
+            ));
+        }
+        report
+    }
+}
+
+/// Creates `clone` as a copy-on-write clone of `source`.
+#[cfg(target_os = "linux")]
+fn reflink(source: &Path, clone: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let source = File::open(source)?;
+    let clone = File::create(clone)?;
+    // SAFETY: both descriptors are open for the duration of the call.
+    match unsafe { libc::ioctl(clone.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn reflink(source: &Path, clone: &Path) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let source = CString::new(source.as_os_str().as_bytes())?;
+    let clone = CString::new(clone.as_os_str().as_bytes())?;
+    // SAFETY: both paths are valid, NUL-terminated strings.
+    match unsafe { libc::clonefile(source.as_ptr(), clone.as_ptr(), 0) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_source: &Path, _clone: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Whether `source` can be cloned next to `target`, found by trying.
+fn can_reflink(source: &Path, target: &Path) -> bool {
+    let probe = target.with_file_name(format!(".rskill-dedupe-probe-{}", std::process::id()));
+    let cloned = reflink(source, &probe).is_ok();
+    let _ = std::fs::remove_file(&probe);
+    cloned
+}
+
+/// Gives `clone` the owner, permissions and modification time of the file it replaces.
+fn copy_attributes(original: &std::fs::Metadata, clone: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // Only root may give a file away, and then the owner must be kept.
+        let _ = std::os::unix::fs::chown(clone, Some(original.uid()), Some(original.gid()));
+    }
+    std::fs::set_permissions(clone, original.permissions())?;
+    File::options()
+        .write(true)
+        .open(clone)?
+        .set_modified(original.modified()?)
+}
+
+/// Replaces `target` with a clone of `source`, or a hard link to it if `hard_links` is set and
+/// the filesystem can't clone, through a temporary file next to it so that `target` is never
+/// missing.
+fn replace(source: &Path, target: &Path, hard_links: bool) -> Result<LinkKind, String> {
+    if !same_contents(source, target).map_err(|e| e.to_string())? {
+        return Err(String::from("changed since it was scanned"));
+    }
+    let original = std::fs::metadata(target).map_err(|e| e.to_string())?;
+    let name = target.file_name().ok_or("not a file")?.to_string_lossy();
+    let temporary = target.with_file_name(format!(".{name}.rskill-dedupe"));
+    let _ = std::fs::remove_file(&temporary);
+
+    let linked = match reflink(source, &temporary) {
+        Ok(()) => copy_attributes(&original, &temporary).map(|()| LinkKind::Reflink),
+        Err(e) if !hard_links => Err(e),
+        Err(_) => {
+            let _ = std::fs::remove_file(&temporary);
+            std::fs::hard_link(source, &temporary).map(|()| LinkKind::HardLink)
+        }
+    };
+    let kind = linked.map_err(|e| {
+        let _ = std::fs::remove_file(&temporary);
+        e.to_string()
+    })?;
+    std::fs::rename(&temporary, target).map_err(|e| {
+        let _ = std::fs::remove_file(&temporary);
+        e.to_string()
+    })?;
+    Ok(kind)
+}
+
+/// Points every duplicate in `plan` to the first copy of its group. Duplicates on filesystems
+/// without reflinks are skipped unless `hard_links` is set.
+pub fn apply(plan: &DedupePlan, hard_links: bool) -> DedupeOutcome {
+    let (linked, skipped): (Vec<_>, Vec<_>) = plan
+        .groups
+        .iter()
+        .partition(|group| group.reflinks || hard_links);
+    let results: Vec<(&PathBuf, u64, Result<LinkKind, String>)> = linked
+        .into_par_iter()
+        .flat_map_iter(|group| {
+            let source = &group.files[0];
+            group.files[1..]
+                .iter()
+                .map(move |target| (target, group.size, replace(source, target, hard_links)))
+        })
+        .collect();
+
+    let mut outcome = DedupeOutcome {
+        skipped: skipped.iter().map(|group| group.files.len() - 1).sum(),
+        ..DedupeOutcome::default()
+    };
+    for (path, size, result) in results {
+        match result {
+            Ok(kind) => {
+                match kind {
+                    LinkKind::Reflink => outcome.reflinked += 1,
+                    LinkKind::HardLink => outcome.hard_linked += 1,
+                }
+                outcome.saved += size;
+            }
+            Err(e) => outcome.failed.push((path.clone(), e)),
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn tree(name: &str) -> TempDir {
+        let root = TempDir::new(&format!("dedupe-{name}"));
+        for project in ["a", "b", "c"] {
+            let modules = root.join(project).join("node_modules/lodash");
+            std::fs::create_dir_all(&modules).unwrap();
+            std::fs::write(modules.join("lodash.js"), "x".repeat(1000)).unwrap();
+            std::fs::write(modules.join("package.json"), project).unwrap();
+            std::fs::write(modules.join("empty.js"), "").unwrap();
+        }
+        root
+    }
+
+    fn roots(root: &Path) -> Vec<PathBuf> {
+        ["a", "b", "c"]
+            .iter()
+            .map(|project| root.join(project).join("node_modules"))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_groups_identical_files() {
+        let root = tree("plan");
+        let plan = plan(&roots(&root));
+        assert_eq!(plan.groups.len(), 1);
+        assert_eq!(plan.groups[0].files.len(), 3);
+        assert!(plan.groups[0].files[0].starts_with(root.join("a")));
+        assert_eq!(plan.savings, 2000);
+        assert!(plan
+            .report(SizeUnit::B, true)
+            .starts_with("Would link 2 duplicate files"));
+    }
+
+    #[test]
+    fn test_hard_links_are_opt_in() {
+        let root = tree("opt-in");
+        let mut plan = plan(&roots(&root));
+        plan.groups[0].reflinks = false;
+        let report = plan.report(SizeUnit::B, false);
+        assert!(
+            report.starts_with("Would link 0 duplicate files"),
+            "{report}"
+        );
+        assert!(report.contains("Would skip 2 duplicate files"), "{report}");
+        assert!(plan
+            .report(SizeUnit::B, true)
+            .contains("2 of them as hard links"));
+
+        let outcome = apply(&plan, false);
+        assert_eq!((outcome.skipped, outcome.hard_linked), (2, 0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_of_other_owners_are_kept_apart() {
+        use std::os::unix::fs::MetadataExt;
+
+        let root = tree("owners");
+        let file = root.join("c/node_modules/lodash/lodash.js");
+        let metadata = std::fs::metadata(&file).unwrap();
+        let (key, _) = file_identity(&metadata, 0);
+        assert_eq!((key.uid, key.gid), (metadata.uid(), metadata.gid()));
+        // Only root may give a file away.
+        if unsafe { libc::geteuid() } == 0 {
+            std::os::unix::fs::chown(&file, Some(metadata.uid() + 1), None).unwrap();
+            let plan = plan(&roots(&root));
+            assert_eq!(plan.groups[0].files.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_clones_keep_the_modification_time() {
+        let root = tree("mtime");
+        let original = root.join("a/node_modules/lodash/lodash.js");
+        let clone = root.join("b/node_modules/lodash/lodash.js");
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(&original)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        copy_attributes(&std::fs::metadata(&original).unwrap(), &clone).unwrap();
+        assert_eq!(std::fs::metadata(&clone).unwrap().modified().unwrap(), old);
+    }
+
+    #[test]
+    fn test_apply_links_duplicates_once() {
+        let root = tree("apply");
+        let outcome = apply(&plan(&roots(&root)), true);
+        assert!(outcome.failed.is_empty(), "{:?}", outcome.failed);
+        assert_eq!(outcome.reflinked + outcome.hard_linked, 2);
+        assert_eq!(outcome.saved, 2000);
+        let contents = std::fs::read_to_string(root.join("c/node_modules/lodash/lodash.js"));
+        assert_eq!(contents.unwrap(), "x".repeat(1000));
+
+        // Hard links are already shared, so only reflinked files would be planned again.
+        let again = plan(&roots(&root));
+        assert_eq!(again.duplicates(), outcome.reflinked);
+    }
+}
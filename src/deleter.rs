@@ -0,0 +1,881 @@
+//! Removal of found directories, either permanently or into the system trash.
+
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{mpsc::UnboundedSender, Semaphore},
+    task::JoinHandle,
+};
+use walkdir::WalkDir;
+
+use crate::{
+    cache::{self, Note},
+    docker,
+    fs::long_path,
+    history::record_deletion,
+    output::{format_rate, SizeUnit},
+    policy::{Policy, ProtectLevel},
+    remote, restore,
+    scanner::FoundDir,
+    throttle::{self, Throttle},
+};
+
+/// How many directories are removed at the same time by default.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Progress of a deletion queued with [`Deleter::delete_reporting`], tagged with the caller's key.
+#[derive(Debug)]
+pub enum DeleteEvent<K> {
+    Started(K),
+    /// The outcome, and how long the removal took once it started.
+    Finished(K, Result<(), DeleteError>, Duration),
+}
+
+/// Why a deletion failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteError {
+    pub message: String,
+    /// The kind of the I/O error, for local deletions.
+    pub kind: Option<io::ErrorKind>,
+    /// Where a [fast](Deleter::fast) deletion had moved the directory to before it failed, so
+    /// what's left of it is there rather than at its own path.
+    pub moved_to: Option<PathBuf>,
+}
+
+impl From<String> for DeleteError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            kind: None,
+            moved_to: None,
+        }
+    }
+}
+
+impl DeleteError {
+    /// Whether a local deletion failed because the user may not remove something in the
+    /// directory, so that [removing it as administrator](remove_elevated) may work.
+    pub fn is_denied(&self) -> bool {
+        self.kind == Some(io::ErrorKind::PermissionDenied)
+    }
+}
+
+impl fmt::Display for DeleteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if let Some(aside) = &self.moved_to {
+            write!(f, " (what's left is in {})", aside.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DeleteError {}
+
+/// What a deletion queued with [`Deleter::delete`] did.
+#[derive(Debug)]
+pub struct Removal {
+    pub result: Result<(), DeleteError>,
+    /// How long the removal took, not counting the wait for a free slot.
+    pub elapsed: Duration,
+}
+
+/// Queues deletions and runs a bounded number of them at once.
+#[derive(Debug, Clone)]
+pub struct Deleter {
+    /// Move directories to the system trash instead of removing them permanently.
+    pub use_trash: bool,
+    /// The `ssh` destination the directories live on, if they aren't local.
+    pub remote: Option<String>,
+    /// The Docker socket to remove [Docker entries](crate::docker) through, if they are.
+    pub docker: Option<PathBuf>,
+    /// Delete local directories at low priority, pausing between batches of files.
+    pub io_nice: bool,
+    /// Rename local directories out of their project before removing them, with
+    /// [parallel workers](remove_dir_all_parallel).
+    pub fast: bool,
+    /// When removing a local directory is denied, [make it writable](make_writable) and retry.
+    pub force: bool,
+    permits: Arc<Semaphore>,
+}
+
+impl Default for Deleter {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl Deleter {
+    pub fn new(use_trash: bool) -> Self {
+        Self::with_concurrency(use_trash, DEFAULT_CONCURRENCY)
+    }
+
+    pub fn with_concurrency(use_trash: bool, concurrency: usize) -> Self {
+        Self {
+            use_trash,
+            remote: None,
+            docker: None,
+            io_nice: false,
+            fast: false,
+            force: false,
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+        }
+    }
+
+    /// Deletes on `destination` over SSH instead of locally.
+    pub fn with_remote(mut self, destination: Option<String>) -> Self {
+        self.remote = destination;
+        self
+    }
+
+    /// Removes Docker objects through `socket` instead of directories.
+    pub fn with_docker(mut self, socket: Option<PathBuf>) -> Self {
+        self.docker = socket;
+        self
+    }
+
+    /// Deletes local directories [gently](crate::throttle) when `io_nice` is set.
+    pub fn with_io_nice(mut self, io_nice: bool) -> Self {
+        self.io_nice = io_nice;
+        self
+    }
+
+    /// Deletes local directories [fast](Deleter::fast) when `fast` is set.
+    pub fn with_fast(mut self, fast: bool) -> Self {
+        self.fast = fast;
+        self
+    }
+
+    /// Retries denied local deletions [after fixing permissions](Deleter::force) when `force`
+    /// is set.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Whether the entries deleted are directories on this machine.
+    pub fn is_local(&self) -> bool {
+        self.remote.is_none() && self.docker.is_none()
+    }
+
+    /// Deletes `path` in the background once a slot is free. Must be called from within a tokio
+    /// runtime.
+    pub fn delete(&self, path: PathBuf) -> JoinHandle<Removal> {
+        self.spawn(path, |_| {})
+    }
+
+    /// Like [`Deleter::delete`], but sends `key` over `events` when the removal starts and
+    /// finishes.
+    pub fn delete_reporting<K>(
+        &self,
+        key: K,
+        path: PathBuf,
+        events: UnboundedSender<DeleteEvent<K>>,
+    ) where
+        K: Clone + Send + 'static,
+    {
+        let started = events.clone();
+        let started_key = key.clone();
+        let handle = self.spawn(path, move |_| {
+            let _ = started.send(DeleteEvent::Started(started_key));
+        });
+
+        tokio::spawn(async move {
+            let removal = handle.await.unwrap_or_else(|e| Removal {
+                result: Err(e.to_string().into()),
+                elapsed: Duration::ZERO,
+            });
+            let _ = events.send(DeleteEvent::Finished(key, removal.result, removal.elapsed));
+        });
+    }
+
+    fn spawn(
+        &self,
+        path: PathBuf,
+        on_start: impl FnOnce(&PathBuf) + Send + 'static,
+    ) -> JoinHandle<Removal> {
+        let permits = Arc::clone(&self.permits);
+        let use_trash = self.use_trash;
+        let remote = self.remote.clone();
+        let docker = self.docker.clone();
+        let io_nice = self.io_nice;
+        let fast = self.fast;
+        let force = self.force;
+
+        tokio::spawn(async move {
+            let _permit = match permits.acquire_owned().await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    return Removal {
+                        result: Err(e.to_string().into()),
+                        elapsed: Duration::ZERO,
+                    }
+                }
+            };
+            on_start(&path);
+
+            let started = Instant::now();
+            let result = if let Some(socket) = docker {
+                docker::remove(&socket, &path).await.map_err(Into::into)
+            } else if let Some(destination) = remote {
+                remote::delete(&destination, &path)
+                    .await
+                    .map_err(Into::into)
+            } else if use_trash {
+                tokio::task::spawn_blocking(move || {
+                    if io_nice {
+                        throttle::lower_priority();
+                    }
+                    trash::delete(path).map_err(|e| e.to_string().into())
+                })
+                .await
+                .unwrap_or_else(|e| Err(e.to_string().into()))
+            } else {
+                tokio::task::spawn_blocking(move || {
+                    remove_local(&long_path(&path), fast, io_nice, force)
+                })
+                .await
+                .unwrap_or_else(|e| Err(e.to_string().into()))
+            };
+            Removal {
+                result,
+                elapsed: started.elapsed(),
+            }
+        })
+    }
+}
+
+/// Removes the local directory `path` [fast](Deleter::fast), [gently](Deleter::io_nice) or
+/// plainly, retrying once [with permissions fixed](Deleter::force) if that's denied. Leftovers
+/// of fast deletions next to it that were cut short are removed first.
+fn remove_local(path: &Path, fast: bool, io_nice: bool, force: bool) -> Result<(), DeleteError> {
+    remove_leftovers(path);
+    // A directory already moved aside by a failed attempt isn't moved again.
+    let moved_to = if fast && aside_owner(path).is_none() {
+        Some(move_aside(path)?)
+    } else {
+        None
+    };
+    let target = moved_to.as_deref().unwrap_or(path);
+    let remove = || {
+        if io_nice {
+            remove_dir_all_gently(target)
+        } else if fast {
+            remove_dir_all_parallel(target)
+        } else {
+            std::fs::remove_dir_all(target)
+        }
+    };
+    match remove() {
+        Err(e) if force && e.kind() == io::ErrorKind::PermissionDenied => {
+            make_writable(target);
+            remove()
+        }
+        result => result,
+    }
+    .map_err(|e| DeleteError {
+        message: e.to_string(),
+        kind: Some(e.kind()),
+        moved_to,
+    })
+}
+
+/// Makes `path` and everything below it removable: on Unix by giving the owner full access to
+/// each directory, as unlinking a file only takes write access to its directory, and on
+/// Windows by clearing the read-only attribute of every entry. Files are left alone on Unix,
+/// as they may be hard links shared with a package store elsewhere. Entries it can't change
+/// are left for the removal to report.
+fn make_writable(path: &Path) {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return;
+    };
+    if metadata.file_type().is_symlink() {
+        return;
+    }
+    let mut permissions = metadata.permissions();
+    #[cfg(unix)]
+    if metadata.is_dir() {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o700);
+        let _ = std::fs::set_permissions(path, permissions);
+    }
+    #[cfg(not(unix))]
+    {
+        permissions.set_readonly(false);
+        let _ = std::fs::set_permissions(path, permissions);
+    }
+
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+            make_writable(&entry.path());
+        }
+    }
+}
+
+/// Marks the hidden names [`move_aside`] gives directories, between their own name and the
+/// process and sequence numbers.
+const ASIDE_MARKER: &str = ".rskill-deleting-";
+
+/// Renames `path` to a hidden name next to it, so that it leaves its project at once. Returns
+/// the new path.
+fn move_aside(path: &Path) -> Result<PathBuf, String> {
+    static RENAMED: AtomicUsize = AtomicUsize::new(0);
+
+    let name = path.file_name().ok_or("not a directory")?.to_string_lossy();
+    let aside = path.with_file_name(format!(
+        ".{name}{ASIDE_MARKER}{}-{}",
+        std::process::id(),
+        RENAMED.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::rename(path, &aside).map_err(|e| e.to_string())?;
+    Ok(aside)
+}
+
+/// The process that [moved](move_aside) `path` aside, if it has such a name.
+fn aside_owner(path: &Path) -> Option<u32> {
+    let name = path.file_name()?.to_str()?.strip_prefix('.')?;
+    let (_, numbers) = name.rsplit_once(ASIDE_MARKER)?;
+    let (pid, sequence) = numbers.split_once('-')?;
+    sequence.parse::<usize>().ok()?;
+    pid.parse().ok()
+}
+
+/// Removes what fast deletions of directories named like `path` left next to it when they
+/// were interrupted, as long as the process that moved them aside is gone. Errors are
+/// ignored: the leftovers are tried again next time.
+fn remove_leftovers(path: &Path) {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let prefix = format!(".{}{ASIDE_MARKER}", name.to_string_lossy());
+    for entry in std::fs::read_dir(parent).into_iter().flatten().flatten() {
+        let leftover = entry.path();
+        if !entry.file_name().to_string_lossy().starts_with(&prefix) {
+            continue;
+        }
+        let Some(pid) = aside_owner(&leftover) else {
+            continue;
+        };
+        if pid != std::process::id() && !is_running(pid) {
+            let _ = std::fs::remove_dir_all(&leftover);
+        }
+    }
+}
+
+/// Whether a process with the id `pid` is running.
+fn is_running(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return false;
+        };
+        // Signal 0 only checks that the process exists; EPERM means it does, as someone else's.
+        let alive = unsafe { libc::kill(pid, 0) } == 0;
+        alive || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(not(unix))]
+    std::process::Command::new("tasklist")
+        .args(["/NH", "/FI", &format!("PID eq {pid}")])
+        .output()
+        .map_or(true, |output| {
+            String::from_utf8_lossy(&output.stdout).contains(&pid.to_string())
+        })
+}
+
+/// Removes `path` and everything below it, unlinking the entries of each directory in
+/// parallel on the rayon pool. A symlink is unlinked, not followed.
+fn remove_dir_all_parallel(path: &Path) -> io::Result<()> {
+    if std::fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return remove_symlink(path);
+    }
+    let entries: Vec<std::fs::DirEntry> = std::fs::read_dir(path)?.collect::<io::Result<_>>()?;
+    entries.into_par_iter().try_for_each(|entry| {
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            remove_dir_all_parallel(&entry.path())
+        } else if file_type.is_symlink() {
+            remove_symlink(&entry.path())
+        } else {
+            std::fs::remove_file(entry.path())
+        }
+    })?;
+    std::fs::remove_dir(path)
+}
+
+/// Unlinks the symlink `path`. Directory symlinks and junctions are directories to Windows.
+fn remove_symlink(path: &Path) -> io::Result<()> {
+    std::fs::remove_file(path).or_else(|e| std::fs::remove_dir(path).map_err(|_| e))
+}
+
+/// Removes `path` and everything below it at low priority, pausing between batches of files.
+/// A symlink is unlinked, not followed.
+fn remove_dir_all_gently(path: &Path) -> io::Result<()> {
+    throttle::lower_priority();
+    let mut throttle = Throttle::new(true);
+    for entry in WalkDir::new(path)
+        .follow_root_links(false)
+        .contents_first(true)
+    {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            std::fs::remove_dir(entry.path())?;
+        } else if entry.path_is_symlink() {
+            remove_symlink(entry.path())?;
+        } else {
+            std::fs::remove_file(entry.path())?;
+        }
+        throttle.tick();
+    }
+    Ok(())
+}
+
+/// Removes the local directory `path` as administrator: through `sudo`, or `pkexec` where
+/// there's no `sudo`, on Unix and after a UAC prompt on Windows. The helper may ask for a
+/// password on the terminal, so callers must hand it over first.
+pub fn remove_elevated(path: &Path) -> Result<(), String> {
+    #[cfg(unix)]
+    let status = ["sudo", "pkexec"]
+        .into_iter()
+        .map(|helper| {
+            std::process::Command::new(helper)
+                .args(["rm", "-rf", "--"])
+                .arg(path)
+                .status()
+        })
+        .find(|status| !matches!(status, Err(e) if e.kind() == io::ErrorKind::NotFound))
+        .unwrap_or_else(|| Err(io::Error::other("neither sudo nor pkexec is installed")));
+    // The path only appears quoted inside an encoded command, which nothing else expands.
+    #[cfg(not(unix))]
+    let status = {
+        let remove = encode_command(&format!(
+            "Remove-Item -LiteralPath '{}' -Recurse -Force -ErrorAction Stop",
+            path.display().to_string().replace('\'', "''")
+        ));
+        std::process::Command::new("powershell")
+            .args(["-NoProfile", "-Command"])
+            .arg(format!(
+                "exit (Start-Process powershell -Verb RunAs -Wait -PassThru -ArgumentList '-NoProfile','-EncodedCommand','{remove}').ExitCode"
+            ))
+            .status()
+    };
+
+    match status {
+        Ok(status) if !status.success() => Err(format!("the elevated removal failed ({status})")),
+        Ok(_) if path.exists() => Err(String::from("still there after the elevated removal")),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Encodes `script` for `powershell -EncodedCommand`: UTF-16LE in base64.
+#[cfg(any(windows, test))]
+fn encode_command(script: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let bytes: Vec<u8> = script.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// What happened to one directory during [`clean`].
+#[derive(Debug)]
+pub enum CleanEvent<'a> {
+    /// Deleted, in this long.
+    Deleted(&'a FoundDir, Duration),
+    Failed(&'a FoundDir, DeleteError),
+    /// Left alone because the policy wants it confirmed first.
+    Skipped(&'a FoundDir, String),
+    /// Deleted although a `warn` rule matches it.
+    Warned(&'a FoundDir),
+}
+
+impl CleanEvent<'_> {
+    /// Whether the event should go to stderr rather than stdout.
+    pub fn is_problem(&self) -> bool {
+        !matches!(self, CleanEvent::Deleted(..))
+    }
+
+    /// How long a deletion took and how fast it went. Kept out of the line of the event, which
+    /// scripts parse.
+    pub fn timing(&self) -> Option<String> {
+        match self {
+            CleanEvent::Deleted(dir, elapsed) => Some(format!(
+                "{elapsed:.1?} ({})",
+                format_rate(dir.size, *elapsed, SizeUnit::Auto)
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CleanEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CleanEvent::Deleted(dir, _) => write!(f, "deleted {}", dir.path.display()),
+            CleanEvent::Failed(dir, e) => {
+                write!(f, "failed to delete {}: {e}", dir.path.display())
+            }
+            CleanEvent::Skipped(dir, reason) => {
+                write!(f, "skipped {}: {reason}", dir.path.display())
+            }
+            CleanEvent::Warned(dir) => write!(
+                f,
+                "warning: {} is in a protected location",
+                dir.path.display()
+            ),
+        }
+    }
+}
+
+/// Deletes every readable directory in `found` that `policy` lets go without a confirmation,
+/// passing each outcome to `report`. Nested directories go with the directory they're in, if
+/// it's deleted too. Each local directory is first added to the `restore` manifest, if given,
+/// and kept if that fails, and loses its tag once deleted. Returns how many were deleted and the
+/// bytes reclaimed.
+pub async fn clean<'a>(
+    found: &'a [FoundDir],
+    deleter: &Deleter,
+    policy: &Policy,
+    restore: Option<&Path>,
+    mut report: impl FnMut(CleanEvent<'a>),
+) -> (usize, u64) {
+    let restore = restore.filter(|_| deleter.is_local());
+    let mut deletable = Vec::new();
+    for dir in found.iter().filter(|dir| dir.error.is_none()) {
+        match policy.level(&dir.path) {
+            Some(level) if level.blocks_bulk_delete() => {
+                report(CleanEvent::Skipped(
+                    dir,
+                    policy.reason(&dir.path).unwrap_or_default(),
+                ));
+                continue;
+            }
+            Some(ProtectLevel::Warn) => report(CleanEvent::Warned(dir)),
+            _ => {}
+        }
+        deletable.push(dir);
+    }
+
+    let mut handles = Vec::new();
+    for &dir in &deletable {
+        let is_inside =
+            |other: &&FoundDir| other.path != dir.path && dir.path.starts_with(&other.path);
+        if deletable.iter().any(is_inside) {
+            continue;
+        }
+        if let Err(e) = restore.map_or(Ok(()), |manifest| restore::append(manifest, dir)) {
+            report(CleanEvent::Skipped(
+                dir,
+                format!("could not write the restore manifest: {e}"),
+            ));
+            continue;
+        }
+        handles.push((dir, deleter.delete(dir.path.clone())));
+    }
+
+    let mut count = 0;
+    let mut reclaimed = 0;
+    let mut untagged = HashMap::new();
+    for (dir, handle) in handles {
+        let Removal { result, elapsed } = handle.await.unwrap_or_else(|e| Removal {
+            result: Err(e.to_string().into()),
+            elapsed: Duration::ZERO,
+        });
+        record_deletion(&dir.path, dir.size, deleter.use_trash, &result);
+        match result {
+            Ok(()) => {
+                report(CleanEvent::Deleted(dir, elapsed));
+                count += 1;
+                reclaimed += dir.size;
+                untagged.insert(dir.path.clone(), Note::default());
+            }
+            Err(e) => report(CleanEvent::Failed(dir, e)),
+        }
+    }
+
+    // A directory installed again at the same path starts untagged. Tags only steer the
+    // interface, so failing to update them doesn't fail the cleanup.
+    let tags = cache::tags_path().filter(|path| path.exists());
+    if let Some(path) = tags.filter(|_| deleter.is_local() && !untagged.is_empty()) {
+        let _ = cache::update_notes(&path, untagged);
+    }
+    (count, reclaimed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[tokio::test]
+    async fn test_delete_reporting_sends_start_and_result() {
+        let dir = TempDir::new("deleter");
+        std::fs::create_dir_all(dir.join("node_modules/pkg")).unwrap();
+
+        let (tx, mut rx) = unbounded_channel();
+        Deleter::with_concurrency(false, 1).delete_reporting(7, dir.to_path_buf(), tx);
+
+        assert!(matches!(rx.recv().await, Some(DeleteEvent::Started(7))));
+        assert!(matches!(
+            rx.recv().await,
+            Some(DeleteEvent::Finished(7, Ok(()), _))
+        ));
+        assert!(!dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_io_nice_deletes_whole_tree() {
+        let root = TempDir::new("deleter-nice");
+        let dir = root.join("project");
+        let package = dir.join("node_modules/pkg/lib");
+        std::fs::create_dir_all(&package).unwrap();
+        for i in 0..300 {
+            std::fs::write(package.join(format!("{i}.js")), "x").unwrap();
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&package, dir.join("node_modules/.bin")).unwrap();
+
+        let deleter = Deleter::default().with_io_nice(true);
+        deleter.delete(dir.clone()).await.unwrap().result.unwrap();
+        assert!(!dir.exists());
+        assert!(deleter.delete(dir).await.unwrap().result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fast_delete_moves_the_directory_aside_first() {
+        let project = TempDir::new("deleter-fast");
+        let package = project.join("node_modules/pkg/lib");
+        std::fs::create_dir_all(&package).unwrap();
+        for i in 0..50 {
+            std::fs::write(package.join(format!("{i}.js")), "x").unwrap();
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&package, project.join("node_modules/.bin")).unwrap();
+
+        let deleter = Deleter::default().with_fast(true);
+        let removal = deleter.delete(project.join("node_modules")).await.unwrap();
+        removal.result.unwrap();
+        assert_eq!(std::fs::read_dir(&project).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fast_delete_removes_leftovers_of_gone_processes() {
+        let project = TempDir::new("deleter-leftovers");
+        let modules = project.join("node_modules");
+        // No process has the id u32::MAX, and ours is still deleting its own.
+        let stale = project.join(format!(".node_modules{ASIDE_MARKER}{}-0", u32::MAX));
+        let ours = project.join(format!(
+            ".node_modules{ASIDE_MARKER}{}-0",
+            std::process::id()
+        ));
+        for dir in [&modules, &stale, &ours] {
+            std::fs::create_dir_all(dir.join("pkg")).unwrap();
+        }
+        assert_eq!(aside_owner(&stale), Some(u32::MAX));
+        assert_eq!(aside_owner(&modules), None);
+
+        let deleter = Deleter::default().with_fast(true);
+        deleter
+            .delete(modules.clone())
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert!(!modules.exists());
+        assert!(!stale.exists());
+        assert!(ours.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_fast_delete_unlinks_a_symlinked_target() {
+        let root = TempDir::new("deleter-fast-link");
+        let shared = root.join("shared");
+        std::fs::create_dir_all(shared.join("pkg")).unwrap();
+        std::fs::write(shared.join("pkg/index.js"), "x").unwrap();
+        let modules = root.join("app/node_modules");
+        std::fs::create_dir_all(root.join("app")).unwrap();
+        std::os::unix::fs::symlink(&shared, &modules).unwrap();
+
+        let deleter = Deleter::default().with_fast(true);
+        deleter
+            .delete(modules.clone())
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert!(!modules.exists());
+        assert_eq!(std::fs::read_dir(root.join("app")).unwrap().count(), 0);
+        assert!(
+            shared.join("pkg/index.js").exists(),
+            "the link's target is kept"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_io_nice_unlinks_a_symlinked_target() {
+        let root = TempDir::new("deleter-nice-link");
+        let shared = root.join("shared");
+        std::fs::create_dir_all(shared.join("pkg")).unwrap();
+        std::fs::write(shared.join("pkg/index.js"), "x").unwrap();
+        let modules = root.join("node_modules");
+        std::os::unix::fs::symlink(&shared, &modules).unwrap();
+
+        let deleter = Deleter::default().with_io_nice(true);
+        deleter
+            .delete(modules.clone())
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert!(std::fs::symlink_metadata(&modules).is_err());
+        assert!(
+            shared.join("pkg/index.js").exists(),
+            "the link's target is kept"
+        );
+    }
+
+    #[test]
+    fn test_failed_fast_delete_reports_where_the_directory_went() {
+        let moved_to = PathBuf::from("/p/.node_modules.rskill-deleting-1-0");
+        let error = DeleteError {
+            message: String::from("busy"),
+            kind: None,
+            moved_to: Some(moved_to.clone()),
+        };
+        assert_eq!(
+            error.to_string(),
+            format!("busy (what's left is in {})", moved_to.display())
+        );
+        assert_eq!(aside_owner(&moved_to), Some(1));
+    }
+
+    #[test]
+    fn test_deleted_line_keeps_its_format() {
+        let dir = FoundDir {
+            size: 2 << 20,
+            ..FoundDir::pending(PathBuf::from("/a/node_modules"))
+        };
+        let event = CleanEvent::Deleted(&dir, Duration::from_secs(2));
+        assert_eq!(event.to_string(), "deleted /a/node_modules");
+        assert_eq!(event.timing().unwrap(), "2.0s (1.00MB/s)");
+        assert_eq!(CleanEvent::Warned(&dir).timing(), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_force_deletes_read_only_trees() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = TempDir::new("deleter-force");
+        let dir = root.join("project");
+        let store = dir.join("node_modules/.pnpm/pkg");
+        std::fs::create_dir_all(&store).unwrap();
+        std::fs::write(store.join("index.js"), "x").unwrap();
+        for path in [store.join("index.js"), store.clone()] {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o444)).unwrap();
+        }
+
+        make_writable(&dir);
+        assert_eq!(
+            std::fs::metadata(&store).unwrap().permissions().mode() & 0o700,
+            0o700
+        );
+        assert_eq!(
+            std::fs::metadata(store.join("index.js"))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777,
+            0o444,
+            "files may be hard links into the pnpm store"
+        );
+
+        std::fs::set_permissions(&store, std::fs::Permissions::from_mode(0o555)).unwrap();
+        let deleter = Deleter::default().with_force(true);
+        deleter.delete(dir.clone()).await.unwrap().result.unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_is_denied_recognizes_permission_errors() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Root may remove anything, so there's nothing to deny.
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = TempDir::new("deleter-denied");
+        let locked = dir.join("node_modules/pkg");
+        std::fs::create_dir_all(&locked).unwrap();
+        std::fs::write(locked.join("index.js"), "x").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o555)).unwrap();
+
+        let error = Deleter::default()
+            .delete(dir.join("node_modules"))
+            .await
+            .unwrap()
+            .result
+            .unwrap_err();
+        assert!(error.is_denied());
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_other_errors_are_not_denied() {
+        let dir = TempDir::new("deleter-missing-denied");
+        let error = Deleter::default()
+            .delete(dir.join("missing"))
+            .await
+            .unwrap()
+            .result
+            .unwrap_err();
+        assert_eq!(error.kind, Some(io::ErrorKind::NotFound));
+        assert!(!error.is_denied());
+        assert!(!DeleteError::from(String::from("Permission denied (os error 13)")).is_denied());
+    }
+
+    #[test]
+    fn test_encode_command_is_utf16_base64() {
+        assert_eq!(encode_command("dir"), "ZABpAHIA");
+        assert_eq!(encode_command("é"), "6QA=");
+        assert_eq!(encode_command("ab"), "YQBiAA==");
+    }
+
+    #[tokio::test]
+    async fn test_delete_reports_missing_directory() {
+        let dir = TempDir::new("deleter-missing");
+        let removal = Deleter::default()
+            .delete(dir.join("missing"))
+            .await
+            .unwrap();
+        assert!(
+            removal.result.is_err(),
+            "Deleting a missing directory should fail"
+        );
+    }
+}
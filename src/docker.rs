@@ -0,0 +1,298 @@
+//! Docker images, containers, volumes and build cache taking up space, for `rskill docker`.
+//!
+//! Objects are read from the Docker Engine API over its Unix socket and listed as entries with
+//! paths such as `docker:image/3f2a9c1b2d4e`, so the interface and the deleter treat them like
+//! directories.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::scanner::FoundDir;
+
+/// The prefix of the paths of Docker entries.
+pub const PATH_PREFIX: &str = "docker:";
+
+/// The socket of `DOCKER_HOST` when it is a `unix://` address, or `/var/run/docker.sock`.
+pub fn socket_path() -> PathBuf {
+    std::env::var("DOCKER_HOST")
+        .ok()
+        .and_then(|host| host.strip_prefix("unix://").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("/var/run/docker.sock"))
+}
+
+/// The answer of `GET /system/df`, as far as rskill cares.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct DiskUsage {
+    images: Option<Vec<Image>>,
+    containers: Option<Vec<Container>>,
+    volumes: Option<Vec<Volume>>,
+    build_cache: Option<Vec<BuildCache>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Image {
+    id: String,
+    repo_tags: Option<Vec<String>>,
+    size: i64,
+    created: i64,
+    /// Containers using the image, or -1 when unknown.
+    containers: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Container {
+    id: String,
+    names: Vec<String>,
+    state: String,
+    size_rw: Option<i64>,
+    created: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Volume {
+    name: String,
+    created_at: Option<String>,
+    usage_data: Option<VolumeUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VolumeUsage {
+    /// Bytes used, or -1 when unknown.
+    size: i64,
+    ref_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BuildCache {
+    size: i64,
+    in_use: bool,
+    last_used_at: Option<String>,
+}
+
+/// An entry of `kind` named `name`, created or last used at `time`.
+fn entry(kind: &str, name: &str, size: i64, time: Option<DateTime<Utc>>) -> FoundDir {
+    let path = match name {
+        "" => format!("{PATH_PREFIX}{kind}"),
+        name => format!("{PATH_PREFIX}{kind}/{name}"),
+    };
+    let time = time.map_or(std::time::SystemTime::now(), Into::into);
+    let mut dir = FoundDir::with_size(PathBuf::from(path), size.max(0) as u64, time);
+    dir.target_kind = kind.to_string();
+    dir
+}
+
+fn timestamp(seconds: i64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(seconds, 0)
+}
+
+fn rfc3339(time: Option<&str>) -> Option<DateTime<Utc>> {
+    time.and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map(|time| time.to_utc())
+}
+
+/// The dangling images, stopped containers and unused volumes in `usage`, then the build cache
+/// not in use as a single entry.
+fn entries(usage: DiskUsage) -> Vec<FoundDir> {
+    let mut found = Vec::new();
+
+    for image in usage.images.unwrap_or_default() {
+        let tags = image.repo_tags.unwrap_or_default();
+        let dangling = tags.iter().all(|tag| tag == "<none>:<none>");
+        if dangling && image.containers <= 0 {
+            let id = image.id.trim_start_matches("sha256:");
+            let id = &id[..id.len().min(12)];
+            found.push(entry("image", id, image.size, timestamp(image.created)));
+        }
+    }
+
+    for container in usage.containers.unwrap_or_default() {
+        if matches!(container.state.as_str(), "exited" | "created" | "dead") {
+            let name = container
+                .names
+                .first()
+                .map(|name| name.trim_start_matches('/').to_string())
+                .unwrap_or(container.id);
+            let size = container.size_rw.unwrap_or(0);
+            found.push(entry(
+                "container",
+                &name,
+                size,
+                timestamp(container.created),
+            ));
+        }
+    }
+
+    for volume in usage.volumes.unwrap_or_default() {
+        let Some(usage) = volume.usage_data else {
+            continue;
+        };
+        if usage.ref_count == 0 {
+            let created = rfc3339(volume.created_at.as_deref());
+            found.push(entry("volume", &volume.name, usage.size, created));
+        }
+    }
+
+    let unused: Vec<_> = usage
+        .build_cache
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|cache| !cache.in_use)
+        .collect();
+    if !unused.is_empty() {
+        let size = unused.iter().map(|cache| cache.size).sum();
+        let last_used = unused
+            .iter()
+            .filter_map(|cache| rfc3339(cache.last_used_at.as_deref()))
+            .max();
+        found.push(entry("build-cache", "", size, last_used));
+    }
+    found
+}
+
+/// Lists what Docker could free: dangling images, stopped containers, unused volumes and build
+/// cache.
+pub async fn list(socket: &Path) -> Result<Vec<FoundDir>, String> {
+    let body = request(socket, "GET", "/system/df").await?;
+    let usage = serde_json::from_str(&body).map_err(|e| format!("unexpected answer: {e}"))?;
+    Ok(entries(usage))
+}
+
+/// The API call removing the entry at `path`.
+fn removal(path: &Path) -> Option<(&'static str, String)> {
+    let path = path.to_str()?.strip_prefix(PATH_PREFIX)?;
+    let (kind, name) = path.split_once('/').unwrap_or((path, ""));
+    match kind {
+        "image" => Some(("DELETE", format!("/images/{name}"))),
+        "container" => Some(("DELETE", format!("/containers/{name}"))),
+        "volume" => Some(("DELETE", format!("/volumes/{name}"))),
+        "build-cache" => Some(("POST", String::from("/build/prune"))),
+        _ => None,
+    }
+}
+
+/// Removes the entry at `path`, as listed by [`list`].
+pub async fn remove(socket: &Path, path: &Path) -> Result<(), String> {
+    let (method, endpoint) =
+        removal(path).ok_or_else(|| format!("{} is not a Docker object", path.display()))?;
+    request(socket, method, &endpoint).await.map(|_| ())
+}
+
+/// Sends a request without a body to the Engine API and returns the body of a successful answer.
+#[cfg(unix)]
+async fn request(socket: &Path, method: &str, endpoint: &str) -> Result<String, String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::UnixStream::connect(socket)
+        .await
+        .map_err(|e| format!("could not connect to Docker at {}: {e}", socket.display()))?;
+    // HTTP/1.0 makes the daemon close the connection after the body instead of chunking it.
+    let head = format!("{method} {endpoint} HTTP/1.0\r\nHost: docker\r\nContent-Length: 0\r\n\r\n");
+    stream
+        .write_all(head.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut answer = Vec::new();
+    stream
+        .read_to_end(&mut answer)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let answer = String::from_utf8_lossy(&answer);
+    let (head, body) = answer.split_once("\r\n\r\n").unwrap_or((&answer, ""));
+    let status: u16 = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or("malformed answer from Docker")?;
+    if (200..300).contains(&status) {
+        return Ok(body.to_string());
+    }
+
+    #[derive(Deserialize)]
+    struct Error {
+        message: String,
+    }
+    Err(serde_json::from_str::<Error>(body).map_or_else(
+        |_| format!("Docker answered {status}"),
+        |error| error.message,
+    ))
+}
+
+#[cfg(not(unix))]
+async fn request(_socket: &Path, _method: &str, _endpoint: &str) -> Result<String, String> {
+    Err(String::from("Docker is only supported over a Unix socket"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_keep_only_reclaimable_objects() {
+        let usage = serde_json::from_str(
+            r#"{
+                "Images": [
+                    {"Id": "sha256:3f2a9c1b2d4e5f60", "RepoTags": ["<none>:<none>"], "Size": 100, "Created": 1700000000, "Containers": 0},
+                    {"Id": "sha256:aaaa", "RepoTags": ["node:22"], "Size": 900, "Created": 1700000000, "Containers": 0}
+                ],
+                "Containers": [
+                    {"Id": "c1", "Names": ["/old_api"], "State": "exited", "SizeRw": 40, "Created": 1700000000},
+                    {"Id": "c2", "Names": ["/db"], "State": "running", "SizeRw": 70, "Created": 1700000000}
+                ],
+                "Volumes": [
+                    {"Name": "cache", "CreatedAt": "2024-01-01T00:00:00Z", "UsageData": {"Size": 30, "RefCount": 0}},
+                    {"Name": "pgdata", "UsageData": {"Size": 500, "RefCount": 1}}
+                ],
+                "BuildCache": [
+                    {"Size": 5, "InUse": false, "LastUsedAt": "2024-02-01T00:00:00Z"},
+                    {"Size": 7, "InUse": false},
+                    {"Size": 11, "InUse": true}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let found = entries(usage);
+        let listed: Vec<_> = found
+            .iter()
+            .map(|dir| (dir.path.display().to_string(), dir.size))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (String::from("docker:image/3f2a9c1b2d4e"), 100),
+                (String::from("docker:container/old_api"), 40),
+                (String::from("docker:volume/cache"), 30),
+                (String::from("docker:build-cache"), 12),
+            ]
+        );
+        assert_eq!(found[2].target_kind, "volume");
+        assert!(found[0].modified > 0);
+        assert!(
+            found.iter().all(|dir| dir.workspace.is_none()),
+            "objects never group into a workspace of the current directory"
+        );
+    }
+
+    #[test]
+    fn test_removal_maps_paths_to_endpoints() {
+        let endpoint = |path: &str| removal(Path::new(path));
+        assert_eq!(
+            endpoint("docker:container/old_api"),
+            Some(("DELETE", String::from("/containers/old_api")))
+        );
+        assert_eq!(
+            endpoint("docker:build-cache"),
+            Some(("POST", String::from("/build/prune")))
+        );
+        assert_eq!(endpoint("/work/app/node_modules"), None);
+    }
+}
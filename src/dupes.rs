@@ -0,0 +1,293 @@
+//! Packages installed at the same version in several projects, for `rskill dupes`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fmt::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{
+    output::{format_size, OutputFormat, SizeUnit},
+    scanner::FoundDir,
+};
+
+/// How many duplicates the text report lists. `--json` lists all of them.
+const SHOWN_DUPLICATES: usize = 25;
+
+/// One package directory inside a `node_modules` tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+    /// Bytes of its files, leaving out its own nested `node_modules`.
+    pub size: u64,
+}
+
+/// A package version installed in more than one project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Duplicate {
+    pub name: String,
+    pub version: String,
+    /// The `node_modules` directories of the projects holding a copy.
+    pub projects: Vec<PathBuf>,
+    /// Copies in all, counting nested ones.
+    pub copies: usize,
+    /// Bytes taken by every copy but one.
+    pub wasted: u64,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    name: Option<String>,
+    version: Option<String>,
+}
+
+/// The directory of the package owning the file at `relative`, a path inside a `node_modules`
+/// tree: the component after the last `node_modules`, or the two after it in an npm scope.
+fn package_root(relative: &Path) -> Option<PathBuf> {
+    let components: Vec<&OsStr> = relative
+        .components()
+        .map(|c| match c {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    let start = components
+        .iter()
+        .rposition(|name| *name == "node_modules")
+        .map_or(0, |i| i + 1);
+    let len = match components.get(start) {
+        Some(name) if name.to_string_lossy().starts_with('@') => 2,
+        Some(_) => 1,
+        None => return None,
+    };
+    // The file must be inside the package, not the package directory itself.
+    if components.len() <= start + len {
+        return None;
+    }
+    Some(components[..start + len].iter().collect())
+}
+
+/// The packages installed in the `node_modules` directory at `modules`, nested ones included.
+/// Directories without a readable `package.json` are left out.
+pub fn installed_packages(modules: &Path) -> Vec<InstalledPackage> {
+    let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+    for entry in WalkDir::new(modules).min_depth(2).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Some(root) = entry
+            .path()
+            .strip_prefix(modules)
+            .ok()
+            .and_then(package_root)
+        else {
+            continue;
+        };
+        let size = entry.metadata().map_or(0, |metadata| metadata.len());
+        *sizes.entry(root).or_default() += size;
+    }
+
+    sizes
+        .into_iter()
+        .filter_map(|(root, size)| {
+            let text = std::fs::read_to_string(modules.join(root).join("package.json")).ok()?;
+            let manifest: Manifest = serde_json::from_str(&text).ok()?;
+            Some(InstalledPackage {
+                name: manifest.name?,
+                version: manifest.version?,
+                size,
+            })
+        })
+        .collect()
+}
+
+/// The package versions installed in more than one of the readable `node_modules` directories in
+/// `found`, most wasteful first.
+pub fn find(found: &[FoundDir]) -> Vec<Duplicate> {
+    let installed: Vec<(&Path, Vec<InstalledPackage>)> = found
+        .par_iter()
+        .filter(|dir| dir.error.is_none() && dir.target_kind == "node_modules")
+        .map(|dir| (dir.path.as_path(), installed_packages(&dir.path)))
+        .collect();
+
+    let mut copies: HashMap<(String, String), Vec<(&Path, u64)>> = HashMap::new();
+    for (modules, packages) in &installed {
+        for package in packages {
+            copies
+                .entry((package.name.clone(), package.version.clone()))
+                .or_default()
+                .push((modules, package.size));
+        }
+    }
+
+    let mut duplicates: Vec<Duplicate> = copies
+        .into_iter()
+        .filter_map(|((name, version), copies)| {
+            let mut projects: Vec<PathBuf> = copies
+                .iter()
+                .map(|(modules, _)| modules.to_path_buf())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+            if projects.len() < 2 {
+                return None;
+            }
+            projects.sort();
+            let total: u64 = copies.iter().map(|(_, size)| size).sum();
+            let largest = copies.iter().map(|(_, size)| *size).max().unwrap_or(0);
+            Some(Duplicate {
+                name,
+                version,
+                projects,
+                copies: copies.len(),
+                wasted: total - largest,
+            })
+        })
+        .collect();
+    duplicates.sort_by(|a, b| {
+        b.wasted
+            .cmp(&a.wasted)
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.version.cmp(&b.version))
+    });
+    duplicates
+}
+
+/// Every duplicate in `format`, its projects separated by `;` in CSV.
+pub fn render(
+    duplicates: &[Duplicate],
+    format: OutputFormat,
+    unit: SizeUnit,
+) -> serde_json::Result<String> {
+    format.render_rows(
+        duplicates,
+        "name,version,copies,wasted_bytes,wasted,projects",
+        |duplicate| {
+            let projects: Vec<_> = duplicate
+                .projects
+                .iter()
+                .map(|project| project.display().to_string())
+                .collect();
+            vec![
+                duplicate.name.clone(),
+                duplicate.version.clone(),
+                duplicate.copies.to_string(),
+                duplicate.wasted.to_string(),
+                format_size(duplicate.wasted, unit),
+                projects.join(";"),
+            ]
+        },
+    )
+}
+
+/// A table of the most wasteful duplicates and the space all of them waste.
+pub fn report(duplicates: &[Duplicate], unit: SizeUnit) -> String {
+    if duplicates.is_empty() {
+        return String::from("No package version is installed in more than one project\n");
+    }
+
+    let mut report = format!(
+        "{:<40} {:<14} {:>8} {:>6} {:>12}\n",
+        "PACKAGE", "VERSION", "PROJECTS", "COPIES", "WASTED"
+    );
+    for duplicate in duplicates.iter().take(SHOWN_DUPLICATES) {
+        let _ = writeln!(
+            report,
+            "{:<40} {:<14} {:>8} {:>6} {:>12}",
+            duplicate.name,
+            duplicate.version,
+            duplicate.projects.len(),
+            duplicate.copies,
+            format_size(duplicate.wasted, unit)
+        );
+    }
+    if duplicates.len() > SHOWN_DUPLICATES {
+        let _ = writeln!(
+            report,
+            "... and {} more (--json lists all)",
+            duplicates.len() - SHOWN_DUPLICATES
+        );
+    }
+
+    let wasted: u64 = duplicates.iter().map(|d| d.wasted).sum();
+    let _ = writeln!(
+        report,
+        "\n{} package versions are duplicated, wasting {}. pnpm keeps a single copy of each.",
+        duplicates.len(),
+        format_size(wasted, unit)
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn package(modules: &Path, dir: &str, name: &str, version: &str, size: usize) {
+        let dir = modules.join(dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("package.json"),
+            format!("{{\"name\":\"{name}\",\"version\":\"{version}\"}}"),
+        )
+        .unwrap();
+        std::fs::write(dir.join("index.js"), "x".repeat(size)).unwrap();
+    }
+
+    #[test]
+    fn test_package_root_handles_scopes_and_nesting() {
+        let root = |path: &str| package_root(Path::new(path));
+        assert_eq!(root("react/index.js"), Some(PathBuf::from("react")));
+        assert_eq!(
+            root("@types/node/fs.d.ts"),
+            Some(PathBuf::from("@types/node"))
+        );
+        assert_eq!(
+            root("a/node_modules/b/lib/b.js"),
+            Some(PathBuf::from("a/node_modules/b"))
+        );
+        assert_eq!(root(".package-lock.json"), None);
+    }
+
+    #[test]
+    fn test_find_reports_versions_shared_by_projects() {
+        let root = TempDir::new("dupes");
+        let (a, b) = (root.join("a/node_modules"), root.join("b/node_modules"));
+        package(&a, "lodash", "lodash", "4.17.21", 100);
+        package(&a, "left-pad", "left-pad", "1.3.0", 10);
+        package(&a, "left-pad/node_modules/lodash", "lodash", "4.17.21", 100);
+        package(&b, "lodash", "lodash", "4.17.21", 100);
+        package(&b, "left-pad", "left-pad", "1.1.0", 10);
+        package(&b, "@types/node", "@types/node", "22.0.0", 50);
+
+        let found = [FoundDir::pending(a.clone()), FoundDir::pending(b.clone())];
+        let duplicates = find(&found);
+        assert_eq!(duplicates.len(), 1);
+        let lodash = &duplicates[0];
+        assert_eq!(lodash.name, "lodash");
+        assert_eq!(lodash.projects, [a.clone(), b.clone()]);
+        assert_eq!(lodash.copies, 3);
+        // Every copy is 100 bytes of index.js plus its package.json.
+        let copy = 100 + "{\"name\":\"lodash\",\"version\":\"4.17.21\"}".len() as u64;
+        assert_eq!(lodash.wasted, 2 * copy);
+        assert!(report(&duplicates, SizeUnit::B).contains("wasting"));
+        let csv = render(&duplicates, OutputFormat::Csv, SizeUnit::B).unwrap();
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            format!(
+                "lodash,4.17.21,3,{},{}B,{};{}",
+                2 * copy,
+                2 * copy,
+                a.display(),
+                b.display()
+            )
+        );
+    }
+}
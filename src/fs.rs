@@ -1,100 +1,1374 @@
-use rayon::prelude::*;
 use std::{
     collections::{HashMap, HashSet},
-    path::{Path, PathBuf},
-    sync::Arc,
-    time::SystemTime,
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock, PoisonError,
+    },
+    time::{Instant, SystemTime},
 };
 
-use fs_extra::dir::{get_details_entry, DirEntryAttr, DirEntryValue};
-use tokio::sync::Mutex;
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{gitignore::Gitignore, Match, WalkBuilder};
+use rayon::prelude::*;
+use regex::{Regex, RegexSet};
+use sysinfo::Disks;
+use tokio::sync::mpsc::UnboundedSender;
 use walkdir::WalkDir;
 
-use crate::cli::{Args, NodeModule};
-
-const READ_BUFFER_SIZE: usize = 64 * 1024; // 64KB buffer
+use crate::{
+    scanner::{
+        CancelToken, FoundDir, ManifestCheck, ProjectInfo, ScanEvent, ScanOptions, SkippedDir,
+    },
+    throttle::Throttle,
+};
 
-thread_local! {
-    static DIR_BUFFER: std::cell::RefCell<Vec<u8>> = std::cell::RefCell::new(Vec::with_capacity(READ_BUFFER_SIZE));
+#[inline]
+pub fn is_nested_module(path: &Path, target: &str) -> bool {
+    path.to_string_lossy().matches(target).count() > 1
 }
 
+/// Like [`is_nested_module`], but counts path components named after any of `targets`, so e.g.
+/// a `target` directory inside a `node_modules` is treated as nested too.
 #[inline]
-pub fn get_dir_details(
-    path: &PathBuf,
-) -> Option<(HashMap<DirEntryAttr, DirEntryValue>, SystemTime)> {
-    let parent_path = path.parent()?;
-
-    let mut config = HashSet::with_capacity(2);
-    config.insert(DirEntryAttr::Size);
-    config.insert(DirEntryAttr::Modified);
-
-    DIR_BUFFER.with(|buffer| {
-        let mut buffer = buffer.borrow_mut();
-        buffer.clear();
-        let node_details = get_details_entry(path, &config).ok()?;
-        let parent_modified = get_details_entry(parent_path, &config)
-            .ok()?
-            .get(&DirEntryAttr::Modified)
-            .and_then(|v| match v {
-                DirEntryValue::SystemTime(time) => Some(*time),
-                _ => None,
-            })?;
-
-        Some((node_details, parent_modified))
-    })
+pub fn is_nested_target(path: &Path, targets: &[String]) -> bool {
+    nesting_depth(path, targets) > 0
+}
+
+/// How many target directories `path` is inside of, 0 for one that isn't
+/// [nested](is_nested_target).
+pub fn nesting_depth(path: &Path, targets: &[String]) -> usize {
+    path.components()
+        .filter(|component| is_target_name(component.as_os_str(), targets))
+        .count()
+        .saturating_sub(1)
 }
 
 #[inline]
-pub fn is_nested_module(path: &Path, target: &str) -> bool {
-    path.to_string_lossy().matches(target).count() > 1
+fn is_target_name(name: &std::ffi::OsStr, targets: &[String]) -> bool {
+    let name = name.to_string_lossy();
+    targets.iter().any(|target| *target == name)
 }
 
-pub async fn scan_directory(root: PathBuf, args: Args, results: Arc<Mutex<Vec<NodeModule>>>) {
-    let canonical_root = match std::fs::canonicalize(&root) {
-        Ok(path) => path,
-        Err(_) => return,
-    };
+/// `path` with a leading `~` replaced by the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => PathBuf::from(format!(
+            "{}{rest}",
+            home_dir().unwrap_or_default().display()
+        )),
+        _ => PathBuf::from(path),
+    }
+}
 
-    let target = args.target.clone();
-    let excluded_paths_option = &args.exclude_paths;
-    let excluded_paths: Vec<&str> = if let Some(excluded_paths) = excluded_paths_option {
-        excluded_paths.split(",").collect()
+/// Turns an `--exclude` pattern into a glob over absolute paths. A leading `~` expands to the
+/// home directory, and patterns that aren't anchored match at any depth, so `vendor` excludes
+/// every `vendor` directory and `work/*` every child of any `work` directory.
+pub fn exclude_glob(pattern: &str) -> Result<Glob, globset::Error> {
+    let pattern = expand_home(pattern.trim_end_matches('/'))
+        .to_string_lossy()
+        .into_owned();
+    let pattern = if pattern.starts_with('/') || pattern.starts_with("**") {
+        pattern
     } else {
-        Vec::new()
+        format!("**/{pattern}")
     };
 
-    let entries: Vec<_> = WalkDir::new(&canonical_root)
-        .follow_links(false)
+    GlobBuilder::new(&pattern).literal_separator(true).build()
+}
+
+/// Compiles `--exclude` patterns into one matcher, leaving out any that aren't valid globs.
+fn exclude_matcher(patterns: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for glob in patterns.iter().filter_map(|p| exclude_glob(p).ok()) {
+        builder.add(glob);
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+/// Compiles regular expressions into one matcher, leaving out any that aren't valid.
+fn regex_matcher(patterns: &[String]) -> RegexSet {
+    RegexSet::new(patterns.iter().filter(|p| Regex::new(p).is_ok()))
+        .unwrap_or_else(|_| RegexSet::empty())
+}
+
+/// The paths a walk with some [`ScanOptions`] leaves out, and the targets it keeps.
+#[derive(Clone)]
+pub(crate) struct PathFilter {
+    globs: GlobSet,
+    exclude_regex: RegexSet,
+    match_regex: RegexSet,
+}
+
+impl PathFilter {
+    pub(crate) fn new(options: &ScanOptions) -> Self {
+        Self {
+            globs: exclude_matcher(&options.exclude_paths),
+            exclude_regex: regex_matcher(&options.exclude_regex),
+            match_regex: regex_matcher(&options.match_regex),
+        }
+    }
+
+    /// Whether `path` matches an `--exclude` glob or an `--exclude-regex`.
+    fn excludes(&self, path: &Path) -> bool {
+        self.globs.is_match(path) || self.exclude_regex.is_match(&path.to_string_lossy())
+    }
+
+    /// Whether the target directory at `path` matches a `--match` regex, if there are any.
+    fn selects(&self, path: &Path) -> bool {
+        self.match_regex.is_empty() || self.match_regex.is_match(&path.to_string_lossy())
+    }
+
+    /// Whether the target directory at `path`, found by a walk that doesn't prune, is kept:
+    /// it's [selected](Self::selects), and neither it nor a directory between it and `root` is
+    /// excluded.
+    pub(crate) fn keeps(&self, path: &Path, root: &Path) -> bool {
+        self.selects(path)
+            && !path
+                .ancestors()
+                .take_while(|dir| *dir != root)
+                .any(|dir| self.excludes(dir))
+    }
+}
+
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+/// Identifies the directory `path` resolves to, however it is reached.
+fn dir_id(path: &Path) -> Option<DirId> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = std::fs::metadata(path).ok()?;
+        Some((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::canonicalize(path).ok()
+    }
+}
+
+/// Directories already seen during one scan: walked ones, so that following symlinks doesn't
+/// loop, and reported targets, so that one reached through several paths is listed once.
+#[derive(Debug, Clone, Default)]
+pub struct VisitedDirs(Arc<Mutex<HashSet<DirId>>>);
+
+impl VisitedDirs {
+    /// Records `path`, returning whether it hadn't been seen yet.
+    pub fn first_visit(&self, path: &Path) -> bool {
+        match dir_id(path) {
+            Some(id) => self
+                .0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(id),
+            None => true,
+        }
+    }
+}
+
+/// The project directories listed one per line by `input`, for `--stdin`. Blank lines are
+/// skipped and a leading `~` expands to the home directory. Paths that can't be resolved are
+/// kept as given, for the scan to report.
+pub fn read_projects(input: impl std::io::BufRead) -> std::io::Result<Vec<PathBuf>> {
+    let mut projects = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let path = expand_home(line.trim());
+        projects.push(std::fs::canonicalize(&path).unwrap_or(path));
+    }
+    Ok(projects)
+}
+
+/// `roots` without duplicates and without those inside another root, comparing the directories
+/// they resolve to. Paths are kept as given. Roots that can't be resolved are kept, for the scan
+/// to report.
+pub fn distinct_roots(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let resolved: Vec<Option<PathBuf>> = roots
+        .iter()
+        .map(|root| std::fs::canonicalize(root).ok())
+        .collect();
+
+    let mut kept: Vec<usize> = Vec::new();
+    for (i, root) in resolved.iter().enumerate() {
+        let Some(root) = root else {
+            kept.push(i);
+            continue;
+        };
+        let covered = resolved.iter().enumerate().any(|(j, other)| {
+            other.as_ref().is_some_and(|other| {
+                // Of two identical roots, the first one is kept.
+                root.starts_with(other) && (root != other || j < i)
+            })
+        });
+        if !covered {
+            kept.push(i);
+        }
+    }
+    kept.into_iter().map(|i| roots[i].clone()).collect()
+}
+
+/// Whether `path` is on another device than `of`, as far as the platform tells. Symlinks are
+/// followed.
+fn on_other_device(path: &Path, of: &Path) -> bool {
+    device_id(path) != device_id(of)
+}
+
+/// The device `path` is on, following symlinks. `None` where the platform doesn't tell.
+pub fn device_id(path: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        std::fs::metadata(path).map(|m| m.dev()).ok()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// The sysfs directory of the disk behind `device`: that of the device itself, of the disk of
+/// a partition, which has no queue of its own, or of the single device below a mapped one such
+/// as an LVM volume.
+#[cfg(target_os = "linux")]
+fn disk_dir(device: u64) -> Option<PathBuf> {
+    let block = format!(
+        "/sys/dev/block/{}:{}",
+        libc::major(device),
+        libc::minor(device)
+    );
+    let mut dir = std::fs::canonicalize(block).ok()?;
+    if !dir.join("queue").is_dir() {
+        dir = dir.parent()?.to_path_buf();
+    }
+    // Mapped devices may nest, but not this deep.
+    for _ in 0..4 {
+        let below: Vec<PathBuf> = std::fs::read_dir(dir.join("slaves"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .collect();
+        let [below] = below.as_slice() else {
+            break;
+        };
+        let below = std::fs::canonicalize(below).ok()?;
+        dir = if below.join("queue").is_dir() {
+            below
+        } else {
+            below.parent()?.to_path_buf()
+        };
+    }
+    dir.join("queue").is_dir().then_some(dir)
+}
+
+/// The device number of the physical disk behind `device`, shared by its partitions and the
+/// volumes mapped onto it, as Linux tells in sysfs. `device` itself elsewhere and for devices
+/// that aren't block devices, such as network filesystems.
+pub fn disk_id(device: u64) -> u64 {
+    #[cfg(target_os = "linux")]
+    {
+        let dev = disk_dir(device).and_then(|dir| std::fs::read_to_string(dir.join("dev")).ok());
+        if let Some((major, minor)) = dev.as_deref().and_then(|dev| dev.trim().split_once(':')) {
+            if let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) {
+                return libc::makedev(major, minor);
+            }
+        }
+    }
+    device
+}
+
+/// Whether the disk behind `device` spins, as Linux tells in sysfs. `None` elsewhere and for
+/// devices that aren't block devices, such as network filesystems.
+pub fn is_rotational(device: u64) -> Option<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        let flag = std::fs::read_to_string(disk_dir(device)?.join("queue/rotational")).ok()?;
+        Some(flag.trim() == "1")
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = device;
+        None
+    }
+}
+
+fn walk_targets(
+    root: &Path,
+    options: &ScanOptions,
+    visited: &VisitedDirs,
+    cancel: &CancelToken,
+    dirs_scanned: &AtomicUsize,
+    errors: &mut Vec<SkippedDir>,
+    mut on_target: impl FnMut(PathBuf),
+) {
+    let targets = &options.targets;
+    let filter_targets = targets.clone();
+    let include_nested = options.include_nested;
+    let exclude_hidden = options.exclude_hidden;
+    let excluded = PathFilter::new(options);
+    let selected = excluded.clone();
+
+    // `root` is an immediate child of the starting directory, so it already sits at depth 1.
+    let follow_links = options.follow_symlinks;
+    let mut walker = WalkDir::new(root)
+        .follow_links(follow_links)
+        .same_file_system(options.same_filesystem);
+    if let Some(max_depth) = options.max_depth {
+        walker = walker.max_depth(max_depth.saturating_sub(1));
+    }
+
+    let mut throttle = Throttle::new(options.io_nice);
+    walker
         .into_iter()
         .filter_entry(move |e| {
-            let is_target = e.file_name().to_string_lossy() == target;
-            let path = e.path().to_string_lossy();
-
-            let is_excluded = excluded_paths
-                .iter()
-                .any(|excluded| path.contains(excluded));
+            if cancel.is_cancelled() {
+                return false;
+            }
+            throttle.tick();
+            if e.file_type().is_dir() {
+                dirs_scanned.fetch_add(1, Ordering::Relaxed);
+            }
+            let is_target = is_target_name(e.file_name(), &filter_targets);
+            let is_excluded = excluded.excludes(e.path());
 
-            if is_target {
-                !is_nested_module(e.path(), &target) && !is_excluded
+            let keep = if is_target {
+                (include_nested || !is_nested_target(e.path(), &filter_targets)) && !is_excluded
             } else {
-                (!args.exclude_hidden || !is_dangerous(e.path())) && !is_excluded
+                (!exclude_hidden || !is_dangerous(e.path())) && !is_excluded
+            };
+            // Checked last, so that directories left out for other reasons aren't recorded.
+            keep && (!follow_links || !e.file_type().is_dir() || visited.first_visit(e.path()))
+        })
+        .filter_map(|e| match e {
+            Ok(entry) => Some(entry),
+            // Already walked through another path.
+            Err(err) if err.loop_ancestor().is_some() => None,
+            Err(err) => {
+                if let Some(path) = err.path() {
+                    errors.push(SkippedDir {
+                        path: path.to_path_buf(),
+                        reason: err
+                            .io_error()
+                            .map(|io_err| io_err.kind().to_string())
+                            .unwrap_or_else(|| err.to_string()),
+                    });
+                }
+                None
+            }
+        })
+        .filter(|e| is_target_name(e.file_name(), targets) && selected.selects(e.path()))
+        .for_each(|e| on_target(e.into_path()));
+}
+
+/// Name of the ignore file read by rskill only, with `.gitignore` syntax.
+pub const RSKILL_IGNORE_FILE: &str = ".rskillignore";
+
+/// Matches paths against every `.rskillignore` above them, caching parsed files per directory.
+#[derive(Default)]
+struct RskillIgnore {
+    cache: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+impl RskillIgnore {
+    fn is_ignored(&mut self, path: &Path) -> bool {
+        for dir in path.ancestors().skip(1) {
+            let matcher = self.cache.entry(dir.to_path_buf()).or_insert_with(|| {
+                let file = dir.join(RSKILL_IGNORE_FILE);
+                file.is_file().then(|| Gitignore::new(file).0)
+            });
+
+            match matcher
+                .as_ref()
+                .map(|m| m.matched_path_or_any_parents(path, true))
+            {
+                Some(Match::Ignore(_)) => return true,
+                Some(Match::Whitelist(_)) => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+}
+
+/// Like [`walk_targets`], but skips anything ignored by `.gitignore`, `.ignore`, `.rskillignore`
+/// and global git excludes. Targets are usually git-ignored themselves, so they are looked up
+/// directly in every directory the walker visits rather than waiting for the walker to yield
+/// them. Only `.rskillignore` can hide a target itself.
+fn walk_targets_respecting_ignore(
+    root: &Path,
+    options: &ScanOptions,
+    visited: &VisitedDirs,
+    cancel: &CancelToken,
+    dirs_scanned: &AtomicUsize,
+    errors: &mut Vec<SkippedDir>,
+    mut on_target: impl FnMut(PathBuf),
+) {
+    let targets = &options.targets;
+    let filter_targets = targets.clone();
+    let exclude_hidden = options.exclude_hidden;
+    let excluded = PathFilter::new(options);
+    let excluded_dirs = excluded.clone();
+
+    let mut rskill_ignore = RskillIgnore::default();
+    let follow_links = options.follow_symlinks;
+    // The walker keeps to the device of the starting directory, the parent of `root`, by
+    // itself, but targets are looked up without it.
+    let start_device = options
+        .same_filesystem
+        .then(|| device_id(root.parent().unwrap_or(root)))
+        .flatten();
+    let mut is_candidate = |path: &Path| {
+        (options.include_nested || !is_nested_target(path, targets))
+            && start_device.is_none_or(|device| device_id(path) == Some(device))
+            && !excluded.excludes(path)
+            && excluded.selects(path)
+            && !rskill_ignore.is_ignored(path)
+            && (!follow_links || visited.first_visit(path))
+    };
+
+    if root
+        .file_name()
+        .is_some_and(|name| is_target_name(name, targets))
+    {
+        if is_candidate(root) {
+            on_target(root.to_path_buf());
+            if options.include_nested {
+                nested_targets(root, 1, options, cancel)
+                    .filter(|nested| is_candidate(nested))
+                    .for_each(&mut on_target);
+            }
+        }
+        return;
+    }
+
+    if options.max_depth.is_some_and(|depth| depth < 2)
+        || (follow_links && !visited.first_visit(root))
+    {
+        return;
+    }
+
+    let walked = visited.clone();
+    let stopped = cancel.clone();
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .follow_links(follow_links)
+        .same_file_system(options.same_filesystem)
+        .hidden(false)
+        .parents(true)
+        .require_git(false)
+        .add_custom_ignore_filename(RSKILL_IGNORE_FILE)
+        // `max_depth` counts from the starting directory, but the lookup below finds
+        // targets one level below each visited directory.
+        .max_depth(options.max_depth.map(|depth| depth.saturating_sub(2)))
+        .filter_entry(move |e| {
+            if stopped.is_cancelled() {
+                return false;
             }
+            let keep = !is_target_name(e.file_name(), &filter_targets)
+                && (!exclude_hidden || !is_dangerous(e.path()))
+                && !excluded_dirs.excludes(e.path());
+            let is_dir = e.file_type().is_some_and(|t| t.is_dir());
+            // Checked last, so that directories left out for other reasons aren't recorded.
+            keep && (!follow_links || !is_dir || walked.first_visit(e.path()))
+        });
+
+    let mut throttle = Throttle::new(options.io_nice);
+    for entry in builder.build() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        throttle.tick();
+        match entry {
+            Ok(entry) => {
+                if !entry.file_type().is_some_and(|t| t.is_dir()) {
+                    continue;
+                }
+                dirs_scanned.fetch_add(1, Ordering::Relaxed);
+                for target in targets {
+                    let candidate = entry.path().join(target);
+                    if !candidate.is_dir() || !is_candidate(&candidate) {
+                        continue;
+                    }
+                    on_target(candidate.clone());
+                    if options.include_nested {
+                        // `root` is at depth 1, and the candidate one level below the entry.
+                        let depth = entry.depth() + 2;
+                        nested_targets(&candidate, depth, options, cancel)
+                            .filter(|nested| is_candidate(nested))
+                            .for_each(&mut on_target);
+                    }
+                }
+            }
+            Err(err) if is_loop_error(&err) => {}
+            Err(err) => errors.push(SkippedDir {
+                path: ignore_error_path(&err).unwrap_or(root).to_path_buf(),
+                reason: err
+                    .io_error()
+                    .map_or_else(|| err.to_string(), |io_err| io_err.kind().to_string()),
+            }),
+        }
+    }
+}
+
+/// The target directories inside the target directory at `path`, `depth` levels below the
+/// starting directory, for [`ScanOptions::include_nested`]. Ignore files aren't consulted, since
+/// everything inside a target usually is ignored.
+fn nested_targets<'a>(
+    path: &Path,
+    depth: usize,
+    options: &'a ScanOptions,
+    cancel: &'a CancelToken,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    let mut walker = WalkDir::new(path)
+        .min_depth(1)
+        .follow_links(options.follow_symlinks)
+        .same_file_system(options.same_filesystem);
+    if let Some(max_depth) = options.max_depth {
+        walker = walker.max_depth(max_depth.saturating_sub(depth));
+    }
+    let outer = path.to_path_buf();
+    walker
+        .into_iter()
+        .filter_entry(move |e| {
+            // Like the walk, only directories inside the outer target that aren't targets count
+            // as hidden.
+            let hidden = || {
+                !is_target_name(e.file_name(), &options.targets)
+                    && is_dangerous(e.path().strip_prefix(&outer).unwrap_or(e.path()))
+            };
+            !cancel.is_cancelled()
+                && e.file_type().is_dir()
+                && !(options.exclude_hidden && hidden())
         })
+        // Includes the loops of followed symlinks.
         .filter_map(Result::ok)
-        .filter(|e| e.file_name().to_string_lossy() == args.target)
-        .collect();
+        .filter(|e| is_target_name(e.file_name(), &options.targets))
+        .map(walkdir::DirEntry::into_path)
+}
+
+/// Whether the walker stopped at a symlink back to a directory it is already inside.
+fn is_loop_error(err: &ignore::Error) -> bool {
+    match err {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => is_loop_error(err),
+        _ => false,
+    }
+}
+
+fn ignore_error_path(err: &ignore::Error) -> Option<&Path> {
+    match err {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            ignore_error_path(err)
+        }
+        _ => None,
+    }
+}
+
+/// Files of which at least one must sit next to a target directory for it to be listed when
+/// manifests are required. Generic names like `target` and `build` are only trusted when the
+/// project manifest is there.
+pub fn required_manifests(target: &str) -> &'static [&'static str] {
+    match target {
+        "node_modules" => &["package.json"],
+        ".venv" | "venv" => &["pyproject.toml", "requirements.txt", "setup.py", "Pipfile"],
+        "target" => &["Cargo.toml"],
+        "build" | ".gradle" => &[
+            "build.gradle",
+            "build.gradle.kts",
+            "settings.gradle",
+            "settings.gradle.kts",
+        ],
+        _ => &[],
+    }
+}
+
+/// Target names shared with unrelated directories, whose manifest is checked even without
+/// `--require-manifest`.
+fn is_generic_target(target: &str) -> bool {
+    matches!(target, "target" | "build" | ".gradle")
+}
+
+/// Whether the project containing `path` has the manifest its target name requires under
+/// `check`.
+pub fn has_required_manifest(path: &Path, check: ManifestCheck) -> bool {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+
+    let manifests = checked_manifests(&name.to_string_lossy(), check);
+    manifests.is_empty() || manifests.iter().any(|m| parent.join(m).is_file())
+}
+
+/// The manifests one of which must sit next to a `target` directory under `check`, empty when
+/// it's kept without any.
+pub fn checked_manifests(target: &str, check: ManifestCheck) -> &'static [&'static str] {
+    match check {
+        ManifestCheck::Off => &[],
+        ManifestCheck::Generic if !is_generic_target(target) => &[],
+        ManifestCheck::Generic | ManifestCheck::All => required_manifests(target),
+    }
+}
+
+/// Reads the project name and version from the `package.json` next to a `node_modules`
+/// directory. Missing or malformed manifests, and manifests without a name, give `None`.
+pub fn read_project_info(path: &Path) -> Option<ProjectInfo> {
+    if path.file_name()? != "node_modules" {
+        return None;
+    }
+
+    let manifest = std::fs::read_to_string(path.parent()?.join("package.json")).ok()?;
+    serde_json::from_str(&manifest).ok()
+}
+
+/// Counts the packages pinned by the lockfile next to a `node_modules` directory: npm (version 2
+/// and later), yarn or pnpm. Lines are counted rather than parsed, which is close enough for an
+/// estimate and fast on lockfiles of several megabytes. `None` without a lockfile to count.
+pub fn count_locked_packages(path: &Path) -> Option<u64> {
+    if path.file_name()? != "node_modules" {
+        return None;
+    }
+    let project = path.parent()?;
+    let read = |lockfile: &str| std::fs::read_to_string(project.join(lockfile)).ok();
+
+    let packages = if let Some(text) = read("package-lock.json") {
+        text.lines()
+            .filter(|line| line.trim_start().starts_with("\"node_modules/") && line.ends_with('{'))
+            .count()
+    } else if let Some(text) = read("yarn.lock") {
+        text.lines()
+            .filter(|line| !line.starts_with([' ', '#']) && line.trim_end().ends_with(':'))
+            .count()
+    } else {
+        // The keys of the `packages:` map, which ends at the next unindented line.
+        read("pnpm-lock.yaml")?
+            .lines()
+            .skip_while(|line| *line != "packages:")
+            .skip(1)
+            .take_while(|line| line.is_empty() || line.starts_with(' '))
+            .filter(|line| {
+                line.starts_with("  ") && !line.starts_with("   ") && line.ends_with(':')
+            })
+            .count()
+    };
+    // Older npm lockfiles only nest dependencies, leaving nothing to count.
+    (packages > 0).then_some(packages as u64)
+}
+
+/// Directories above a project searched for the root of its workspace.
+const WORKSPACE_DEPTH: usize = 4;
+
+/// The root of the pnpm, yarn, npm or Cargo workspace holding the project of the build
+/// directory at `path`, which may be the root itself. `None` outside a workspace.
+pub fn find_workspace_root(path: &Path) -> Option<PathBuf> {
+    let is_root = |dir: &Path| {
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).ok();
+        dir.join("pnpm-workspace.yaml").is_file()
+            || read("package.json")
+                .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+                .is_some_and(|package| package.get("workspaces").is_some())
+            || read("Cargo.toml")
+                .is_some_and(|text| text.lines().any(|line| line.trim() == "[workspace]"))
+    };
+    // A relative path ends in "", which would read the current directory.
+    path.ancestors()
+        .skip(1)
+        .take(WORKSPACE_DEPTH + 1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find(|dir| is_root(dir))
+        .map(Path::to_path_buf)
+}
+
+/// Lockfiles whose modification after a dependency directory was built means the project's
+/// dependencies changed since.
+pub(crate) const LOCKFILES: &[&str] = &[
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "Cargo.lock",
+    "poetry.lock",
+    "uv.lock",
+    "Pipfile.lock",
+    "gradle.lockfile",
+];
+
+/// Whether a lockfile next to `path` was modified after `path` itself.
+pub fn has_newer_lockfile(path: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let (Some(parent), Some(built)) = (path.parent(), modified(path)) else {
+        return false;
+    };
+
+    LOCKFILES
+        .iter()
+        .filter_map(|lockfile| modified(&parent.join(lockfile)))
+        .any(|changed| changed > built)
+}
+
+/// Allocation unit assumed where the real one isn't available, which makes sizes there an
+/// estimate.
+#[cfg(not(unix))]
+const CLUSTER_SIZE: u64 = 4096;
+
+/// Space actually allocated on disk for the files under `path`, as `du` reports it. A file with
+/// several hard links only counts once all of its links are inside `path`, since deleting the
+/// directory frees nothing otherwise.
+///
+/// Outside Unix this is only an estimate: sizes are rounded up to 4 KiB clusters whatever the
+/// volume uses, compressed and sparse files count at their full length, and hard links aren't
+/// detected.
+pub fn allocated_size(path: &Path) -> Result<u64, String> {
+    let options = ScanOptions {
+        disk_usage: true,
+        ..ScanOptions::default()
+    };
+    dir_stats(path, &options).map(|stats| stats.size)
+}
+
+/// What a single walk of a target directory counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirStats {
+    /// Apparent size of the files, or the space allocated as in [`allocated_size`].
+    pub size: u64,
+    pub file_count: u64,
+    /// Most recent modification of any file inside.
+    pub newest_file: Option<SystemTime>,
+    /// Most recent access to any file inside. Directories are left out, since walking them
+    /// updates their own access times.
+    pub last_accessed: Option<SystemTime>,
+    /// The [budget](SizeBudget) ran out before everything was counted.
+    pub partial: bool,
+}
+
+/// The counts of the part of a directory walked by one worker of [`dir_stats`].
+#[derive(Debug, Default)]
+struct PartialStats {
+    stats: DirStats,
+    /// Files with several hard links: allocated size, links found and links in all.
+    links: HashMap<(u64, u64), (u64, u64, u64)>,
+}
+
+impl PartialStats {
+    fn count(&mut self, metadata: &std::fs::Metadata, disk_usage: bool) {
+        if metadata.is_file() {
+            self.stats.file_count += 1;
+            self.stats.newest_file = self.stats.newest_file.max(metadata.modified().ok());
+            self.stats.last_accessed = self.stats.last_accessed.max(metadata.accessed().ok());
+        }
+        if !disk_usage {
+            if metadata.is_file() {
+                self.stats.size += metadata.len();
+            }
+            return;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let allocated = metadata.blocks() * 512;
+            if metadata.is_dir() || metadata.nlink() <= 1 {
+                self.stats.size += allocated;
+                return;
+            }
+            let (_, seen, _) = self
+                .links
+                .entry((metadata.dev(), metadata.ino()))
+                .or_insert((allocated, 0, metadata.nlink()));
+            *seen += 1;
+        }
+
+        #[cfg(not(unix))]
+        if metadata.is_file() {
+            self.stats.size += metadata.len().div_ceil(CLUSTER_SIZE) * CLUSTER_SIZE;
+        }
+    }
+}
+
+fn walk_error(error: walkdir::Error) -> String {
+    error
+        .io_error()
+        .map(|e| e.kind().to_string())
+        .unwrap_or_else(|| error.to_string())
+}
+
+/// Walks `path` once, totalling its size, counting its files and finding the newest one. Its
+/// entries are walked in parallel on the current rayon pool. Once the
+/// [budget](ScanOptions::size_budget) runs out, what was counted so far is returned as partial.
+pub fn dir_stats(path: &Path, options: &ScanOptions) -> Result<DirStats, String> {
+    let (disk_usage, budget) = (options.disk_usage, options.size_budget);
+    let started = Instant::now();
+    let counted = AtomicU64::new(1);
+    let exhausted = || {
+        budget
+            .entries
+            .is_some_and(|max| counted.fetch_add(1, Ordering::Relaxed) >= max)
+            || budget.time.is_some_and(|time| started.elapsed() >= time)
+    };
 
-    let modules: Vec<_> = entries
-        .par_iter()
-        .map(|e| {
-            let path = e.path().to_path_buf();
-            let attrs = get_dir_details(&path);
-            NodeModule::new(path, attrs)
+    let mut total = PartialStats::default();
+    let metadata = std::fs::symlink_metadata(path).map_err(|e| e.kind().to_string())?;
+    total.count(&metadata, disk_usage);
+    if !metadata.is_dir() {
+        return Ok(total.stats);
+    }
+    let children: Vec<PathBuf> = std::fs::read_dir(path)
+        .and_then(|entries| entries.map(|entry| entry.map(|e| e.path())).collect())
+        .map_err(|e| e.kind().to_string())?;
+    let (same_filesystem, io_nice) = (options.same_filesystem, options.io_nice);
+
+    let parts: Vec<PartialStats> = children
+        .into_par_iter()
+        .filter(|child| !same_filesystem || !on_other_device(child, path))
+        .map(|child| {
+            let mut part = PartialStats::default();
+            let mut throttle = Throttle::new(io_nice);
+            let walker = WalkDir::new(child)
+                .follow_root_links(false)
+                .same_file_system(same_filesystem);
+            for entry in walker {
+                if exhausted() {
+                    part.stats.partial = true;
+                    break;
+                }
+                throttle.tick();
+                let metadata = entry.map_err(walk_error)?.metadata().map_err(walk_error)?;
+                part.count(&metadata, disk_usage);
+            }
+            Ok(part)
         })
+        .collect::<Result<_, String>>()?;
+
+    for part in parts {
+        total.stats.size += part.stats.size;
+        total.stats.file_count += part.stats.file_count;
+        total.stats.newest_file = total.stats.newest_file.max(part.stats.newest_file);
+        total.stats.last_accessed = total.stats.last_accessed.max(part.stats.last_accessed);
+        total.stats.partial |= part.stats.partial;
+        for (file, (allocated, seen, links)) in part.links {
+            total.links.entry(file).or_insert((allocated, 0, links)).1 += seen;
+        }
+    }
+    total.stats.size += total
+        .links
+        .values()
+        .filter(|(_, seen, links)| seen == links)
+        .map(|(allocated, _, _)| allocated)
+        .sum::<u64>();
+    Ok(total.stats)
+}
+
+/// What the detail pane shows about a single directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirBreakdown {
+    pub file_count: u64,
+    /// Immediate subdirectories and their apparent size, largest first. npm scopes such as
+    /// `@types` are split into their packages.
+    pub largest: Vec<(String, u64)>,
+    pub modified: Option<SystemTime>,
+    /// Most recent modification of any file inside the directory.
+    pub newest_file: Option<SystemTime>,
+}
+
+/// Walks `path` once, counting files and totalling the size of each immediate subdirectory.
+/// Entries that can't be read are left out.
+pub fn dir_breakdown(path: &Path) -> Result<DirBreakdown, String> {
+    let metadata = std::fs::metadata(path).map_err(|e| e.kind().to_string())?;
+    let mut breakdown = DirBreakdown {
+        modified: metadata.modified().ok(),
+        ..DirBreakdown::default()
+    };
+
+    let mut subdirs: HashMap<String, u64> = HashMap::new();
+    for entry in WalkDir::new(path).min_depth(1).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+
+        breakdown.file_count += 1;
+        breakdown.newest_file = breakdown.newest_file.max(metadata.modified().ok());
+        if entry.depth() > 1 {
+            if let Some(name) = entry
+                .path()
+                .strip_prefix(path)
+                .ok()
+                .and_then(|p| top_level_name(p, entry.depth()))
+            {
+                *subdirs.entry(name).or_default() += metadata.len();
+            }
+        }
+    }
+
+    breakdown.largest = subdirs.into_iter().collect();
+    breakdown
+        .largest
+        .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(breakdown)
+}
+
+/// The subdirectory a file at `relative` (`depth` components deep) is counted under: its first
+/// component, or the first two for files inside an npm scope such as `@types/node`.
+fn top_level_name(relative: &Path, depth: usize) -> Option<String> {
+    let mut names = relative.components().map(|c| match c {
+        Component::Normal(name) => Some(name.to_string_lossy()),
+        _ => None,
+    });
+    let first = names.next()??;
+    if first.starts_with('@') && depth > 2 {
+        let package = names.next()??;
+        return Some(format!("{first}/{package}"));
+    }
+    Some(first.into_owned())
+}
+
+/// Whether `path` is a target directory a walk with `options` would report. Used for
+/// directories found outside a walk, e.g. by `--watch`. Ignore files aren't consulted.
+pub fn is_listable_target(path: &Path, options: &ScanOptions) -> bool {
+    let filter = PathFilter::new(options);
+    path.file_name()
+        .is_some_and(|name| is_target_name(name, &options.targets))
+        && path.is_dir()
+        && (options.include_nested || !is_nested_target(path, &options.targets))
+        && filter.selects(path)
+        && !filter.excludes(path)
+        && (!options.exclude_hidden || path.parent().is_none_or(|parent| !is_dangerous(parent)))
+        && (options.include_system || !is_system(path))
+        && has_required_manifest(path, options.require_manifest)
+}
+
+/// Reads the size and age of a target directory as `options` asks for them, in a single walk.
+/// Under [`ScanOptions::use_atime`] the age is that of the last read of a file inside, unless
+/// the project was modified since.
+pub fn measure(path: PathBuf, options: &ScanOptions) -> FoundDir {
+    let parent_modified = path
+        .parent()
+        .ok_or_else(|| String::from("no parent directory"))
+        .and_then(|parent| {
+            std::fs::metadata(parent)
+                .and_then(|metadata| metadata.modified())
+                .map_err(|e| e.kind().to_string())
+        });
+    let measured = parent_modified.and_then(|modified| {
+        let stats = dir_stats(&path, options)?;
+        let last_used = match stats.last_accessed {
+            Some(accessed) if options.use_atime => accessed.max(modified),
+            _ => modified,
+        };
+        Ok((stats, last_used))
+    });
+    let mut dir = FoundDir::new(path, measured);
+    dir.workspace = find_workspace_root(&dir.path);
+    mark_active(&mut dir, options);
+    dir
+}
+
+/// Sets [`FoundDir::is_active`] from the project's age and lockfiles.
+pub fn mark_active(dir: &mut FoundDir, options: &ScanOptions) {
+    // Caches belong to no project.
+    dir.is_active = dir.error.is_none()
+        && dir.warning.is_none()
+        && (dir.modified < options.active_within || has_newer_lockfile(&dir.path));
+}
+
+/// Walks `root` and sends every target directory found over `tx` as soon as its size is known.
+/// Sizes are computed on the current rayon pool while the walk continues, and the call returns
+/// once all of them are sent. Once `cancel` fires the walk stops, and targets still waiting to
+/// be measured are sent with an error instead.
+pub fn scan_directory(
+    root: PathBuf,
+    options: Arc<ScanOptions>,
+    visited: VisitedDirs,
+    reported: VisitedDirs,
+    cancel: CancelToken,
+    tx: UnboundedSender<ScanEvent>,
+    dirs_scanned: Arc<AtomicUsize>,
+) {
+    let canonical_root = match std::fs::canonicalize(&root) {
+        Ok(path) => path,
+        Err(e) => {
+            let _ = tx.send(ScanEvent::Skipped(SkippedDir {
+                path: root,
+                reason: e.kind().to_string(),
+            }));
+            return;
+        }
+    };
+
+    if options.same_filesystem && root.parent().is_some_and(|p| on_other_device(&root, p)) {
+        let _ = tx.send(ScanEvent::Skipped(SkippedDir {
+            path: root,
+            reason: String::from("on another filesystem"),
+        }));
+        return;
+    }
+
+    let mut walk_errors = Vec::new();
+    rayon::scope(|scope| {
+        let on_target = |path: PathBuf| {
+            if !options.include_system && is_system(&path) {
+                return;
+            }
+            if !has_required_manifest(&path, options.require_manifest) {
+                return;
+            }
+            if !reported.first_visit(&path) {
+                return;
+            }
+            let _ = tx.send(ScanEvent::Matched(path.clone()));
+            let tx = tx.clone();
+            let options = &options;
+            let cancel = &cancel;
+            scope.spawn(move |_| {
+                if cancel.is_cancelled() {
+                    let dir = FoundDir::new(path, Err(String::from("scan cancelled")));
+                    let _ = tx.send(ScanEvent::Found(dir));
+                    return;
+                }
+                let dir = measure(path, options);
+                let _ = tx.send(if dir.matches(options) {
+                    ScanEvent::Found(dir)
+                } else {
+                    ScanEvent::Dropped(dir.path)
+                });
+            });
+        };
+
+        if options.respect_ignore_files {
+            walk_targets_respecting_ignore(
+                &canonical_root,
+                &options,
+                &visited,
+                &cancel,
+                &dirs_scanned,
+                &mut walk_errors,
+                on_target,
+            );
+        } else {
+            walk_targets(
+                &canonical_root,
+                &options,
+                &visited,
+                &cancel,
+                &dirs_scanned,
+                &mut walk_errors,
+                on_target,
+            );
+        }
+    });
+
+    for error in walk_errors {
+        let _ = tx.send(ScanEvent::Skipped(error));
+    }
+}
+
+/// The user's home directory: `USERPROFILE` on Windows, `HOME` elsewhere.
+pub fn home_dir() -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var_os(var)
+        .or_else(|| std::env::var_os("HOME"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// A package manager cache listed by `--caches`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownCache {
+    /// The name listed as its target, e.g. `npm-cache`.
+    pub name: &'static str,
+    pub path: PathBuf,
+    /// What deleting it costs.
+    pub warning: &'static str,
+}
+
+/// The usual locations of the npm, yarn, pnpm, pip, cargo and Gradle caches on this platform,
+/// whether they exist or not. Read from the environment once, as every listed directory is
+/// checked against them.
+pub fn known_caches() -> &'static [KnownCache] {
+    static CACHES: OnceLock<Vec<KnownCache>> = OnceLock::new();
+    CACHES.get_or_init(find_known_caches)
+}
+
+fn find_known_caches() -> Vec<KnownCache> {
+    let Some(home) = home_dir() else {
+        return Vec::new();
+    };
+    let env_dir = |var: &str| std::env::var_os(var).map(PathBuf::from);
+    let local_data = env_dir("LOCALAPPDATA").unwrap_or_else(|| home.join("AppData/Local"));
+    let caches = if cfg!(windows) {
+        local_data.clone()
+    } else if cfg!(target_os = "macos") {
+        home.join("Library/Caches")
+    } else {
+        env_dir("XDG_CACHE_HOME").unwrap_or_else(|| home.join(".cache"))
+    };
+    let cargo = env_dir("CARGO_HOME").unwrap_or_else(|| home.join(".cargo"));
+    let gradle = env_dir("GRADLE_USER_HOME").unwrap_or_else(|| home.join(".gradle"));
+
+    let (npm, yarn, pnpm, pip) = if cfg!(windows) {
+        (
+            local_data.join("npm-cache/_cacache"),
+            local_data.join("Yarn/Cache"),
+            local_data.join("pnpm/store"),
+            local_data.join("pip/Cache"),
+        )
+    } else if cfg!(target_os = "macos") {
+        (
+            home.join(".npm/_cacache"),
+            caches.join("Yarn"),
+            home.join("Library/pnpm/store"),
+            caches.join("pip"),
+        )
+    } else {
+        let data = env_dir("XDG_DATA_HOME").unwrap_or_else(|| home.join(".local/share"));
+        (
+            home.join(".npm/_cacache"),
+            caches.join("yarn"),
+            data.join("pnpm/store"),
+            caches.join("pip"),
+        )
+    };
+
+    [
+        ("npm-cache", npm, "npm downloads packages again on the next install"),
+        ("yarn-cache", yarn, "yarn downloads packages again on the next install"),
+        (
+            "pnpm-store",
+            pnpm,
+            "pnpm downloads packages again, and projects installed from the store need `pnpm install`",
+        ),
+        ("pip-cache", pip, "pip downloads and builds wheels again"),
+        (
+            "cargo-registry",
+            cargo.join("registry"),
+            "cargo downloads crates again, so offline builds fail until then",
+        ),
+        ("cargo-git", cargo.join("git"), "cargo clones git dependencies again"),
+        (
+            "gradle-caches",
+            gradle.join("caches"),
+            "Gradle downloads dependencies and rebuilds its caches again",
+        ),
+    ]
+    .into_iter()
+    .map(|(name, path, warning)| KnownCache {
+        name,
+        path,
+        warning,
+    })
+    .collect()
+}
+
+/// The [known cache](known_caches) at `path`, if it is one.
+pub fn known_cache(path: &Path) -> Option<&'static KnownCache> {
+    known_caches().iter().find(|cache| cache.path == path)
+}
+
+/// Prefixes an absolute Windows path with `\\?\` so it can exceed `MAX_PATH`, which deeply
+/// nested `node_modules` often do. Other paths are returned unchanged.
+pub fn long_path(path: &Path) -> PathBuf {
+    if !cfg!(windows) || !path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    let raw = path.to_string_lossy();
+    if raw.starts_with(r"\\?\") {
+        path.to_path_buf()
+    } else if let Some(unc) = raw.strip_prefix(r"\\") {
+        PathBuf::from(format!(r"\\?\UNC\{unc}"))
+    } else {
+        PathBuf::from(format!(r"\\?\{raw}"))
+    }
+}
+
+/// Mount points of every non-removable disk, leaving out those inside another one since
+/// scanning the outer mount already covers them.
+pub fn fixed_drives() -> Vec<PathBuf> {
+    let disks = Disks::new_with_refreshed_list();
+    let mut mounts: Vec<PathBuf> = disks
+        .list()
+        .iter()
+        .filter(|disk| !disk.is_removable() && disk.total_space() > 0)
+        .map(|disk| disk.mount_point().to_path_buf())
         .collect();
+    mounts.sort();
+    mounts.dedup();
+
+    let mut drives: Vec<PathBuf> = Vec::new();
+    for mount in mounts {
+        if !drives.iter().any(|drive| mount.starts_with(drive)) {
+            drives.push(mount);
+        }
+    }
+    drives
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiskSpace {
+    pub total: u64,
+    pub available: u64,
+}
+
+impl DiskSpace {
+    /// Space available once `freed` more bytes are released, never more than the whole disk.
+    pub fn available_after(&self, freed: u64) -> u64 {
+        self.available.saturating_add(freed).min(self.total)
+    }
+}
+
+/// Returns the space of the filesystem mounted closest to `path`, or `None` if it can't be found.
+pub fn disk_space(path: &Path) -> Option<DiskSpace> {
+    let disks = Disks::new_with_refreshed_list();
+    disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| DiskSpace {
+            total: disk.total_space(),
+            available: disk.available_space(),
+        })
+        .filter(|space| space.total > 0)
+}
+
+/// Restores the most recently trashed item that was originally at `path`.
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+pub fn restore_from_trash(path: &Path) -> Result<(), String> {
+    let item = trash::os_limited::list()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|item| item.original_path() == path)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or_else(|| String::from("not found in the trash"))?;
+
+    trash::os_limited::restore_all([item]).map_err(|e| e.to_string())
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+pub fn restore_from_trash(_path: &Path) -> Result<(), String> {
+    Err(String::from(
+        "restoring from the trash is not supported on this platform",
+    ))
+}
+
+/// Opens `path` in the platform file manager without waiting for it to exit.
+pub fn open_in_file_manager(path: &Path) -> std::io::Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
 
-    let mut results = results.lock().await;
-    results.extend(modules);
+    std::process::Command::new(opener)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+/// The editor command from `$VISUAL` or `$EDITOR`, split on whitespace so it can carry
+/// arguments such as `code --wait`. Falls back to VS Code.
+pub fn editor_command() -> Vec<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(|var| {
+            var.to_string_lossy()
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        })
+        .find(|command| !command.is_empty())
+        .unwrap_or_else(|| vec![String::from("code")])
+}
+
+/// Opens `path` in the [editor](editor_command) and waits for it to exit, sharing the terminal.
+pub fn open_in_editor(path: &Path) -> std::io::Result<()> {
+    let command = editor_command();
+    let status = std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .arg(path)
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "{} exited with {status}",
+            command[0]
+        )))
+    }
+}
+
+/// Locations where version managers and package managers keep globally installed packages.
+/// Matched against the lowercased path with `/` separators.
+pub const SYSTEM_PATTERNS: &[&str] = &[
+    "/.nvm/versions/",
+    "/.fnm/",
+    "/fnm/node-versions/",
+    "/.volta/",
+    "/.asdf/installs/",
+    "/.npm-global/",
+    "/.yarn/global/",
+    "/.config/yarn/global/",
+    "/.pnpm-global/",
+    "/.pnpm-store/",
+    "/pnpm/store/",
+    "/.local/share/pnpm/",
+    "/pnpm/global/",
+    "/cellar/",
+    "/homebrew/lib/node_modules",
+    "/usr/lib/node_modules",
+    "/usr/local/lib/node_modules",
+    "/appdata/roaming/npm/node_modules",
+    "/.cargo/registry/",
+    "/.cargo/git/",
+    "/.rustup/toolchains/",
+];
+
+/// Whether `path` belongs to globally installed tooling, e.g. an nvm-managed node version.
+pub fn is_system(path: &Path) -> bool {
+    matches_system_pattern(&path.to_string_lossy(), SYSTEM_PATTERNS)
+}
+
+#[inline]
+pub fn matches_system_pattern(path: &str, patterns: &[&str]) -> bool {
+    let normalized = path.to_lowercase().replace('\\', "/");
+    patterns.iter().any(|pattern| normalized.contains(pattern))
 }
 
 pub fn is_dangerous(path: &Path) -> bool {
@@ -117,8 +1391,849 @@ pub fn is_dangerous(path: &Path) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "cli")]
+    use crate::cli::Args;
+    use crate::{
+        scanner::{ReinstallCost, Scanner},
+        testing::TempDir,
+    };
+    #[cfg(feature = "cli")]
+    use clap::Parser;
     use std::path::PathBuf;
 
+    fn fixture(name: &str, dirs: &[&str]) -> TempDir {
+        let root = TempDir::new(name);
+        for dir in dirs {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join("index.js"), "").unwrap();
+        }
+        root
+    }
+
+    #[cfg(feature = "cli")]
+    async fn scan(root: &Path, extra_args: &[&str]) -> Vec<FoundDir> {
+        let args = Args::parse_from(["rskill"].iter().chain(extra_args));
+        let scanner = Scanner::new(ScanOptions::from(&args));
+        let (found, _) = scanner.scan(root).await.unwrap();
+        found
+    }
+
+    #[test]
+    fn test_count_locked_packages_of_each_package_manager() {
+        let root = fixture(
+            "lockfiles",
+            &["npm/node_modules", "yarn/node_modules", "pnpm/node_modules"],
+        );
+        std::fs::write(
+            root.join("npm/package-lock.json"),
+            "{\n  \"lockfileVersion\": 3,\n  \"packages\": {\n    \"\": {\n    },\n    \"node_modules/a\": {\n    },\n    \"node_modules/a/node_modules/b\": {\n    }\n  }\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("yarn/yarn.lock"),
+            "# yarn lockfile v1\n\n\"a@^1.0.0\":\n  version \"1.0.0\"\n\nb@^2.0.0, b@^2.1.0:\n  version \"2.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("pnpm/pnpm-lock.yaml"),
+            "importers:\n\n  .:\n    dependencies: {}\n\npackages:\n\n  a@1.0.0:\n    resolution: {}\n\n  b@2.0.0:\n    resolution: {}\n\nsnapshots:\n\n  a@1.0.0: {}\n",
+        )
+        .unwrap();
+
+        for project in ["npm", "yarn", "pnpm"] {
+            let path = root.join(project).join("node_modules");
+            assert_eq!(count_locked_packages(&path), Some(2), "{project}");
+        }
+        let dir = FoundDir::pending(root.join("npm/node_modules"));
+        assert_eq!(
+            dir.locked_packages, None,
+            "only counted for the detail pane"
+        );
+        let dir = FoundDir {
+            locked_packages: count_locked_packages(&dir.path),
+            ..dir
+        };
+        assert_eq!(dir.reinstall_cost(), Some(ReinstallCost::Cheap));
+        assert_eq!(count_locked_packages(&root.join("npm")), None);
+    }
+
+    #[test]
+    fn test_find_workspace_root_of_each_package_manager() {
+        let root = fixture(
+            "workspaces",
+            &[
+                "pnpm/packages/a/node_modules",
+                "yarn/apps/web/node_modules",
+                "cargo/crates/core/target",
+                "alone/node_modules",
+            ],
+        );
+        std::fs::write(root.join("pnpm/pnpm-workspace.yaml"), "packages:\n").unwrap();
+        std::fs::write(
+            root.join("yarn/package.json"),
+            r#"{"workspaces": ["apps/*"]}"#,
+        )
+        .unwrap();
+        std::fs::write(root.join("cargo/Cargo.toml"), "[workspace]\nmembers = []\n").unwrap();
+        std::fs::write(root.join("alone/package.json"), r#"{"name": "alone"}"#).unwrap();
+
+        for (dir, workspace) in [
+            ("pnpm/packages/a/node_modules", "pnpm"),
+            ("yarn/apps/web/node_modules", "yarn"),
+            ("cargo/crates/core/target", "cargo"),
+            ("cargo/target", "cargo"),
+        ] {
+            assert_eq!(
+                find_workspace_root(&root.join(dir)),
+                Some(root.join(workspace)),
+                "{dir}"
+            );
+        }
+        assert_eq!(find_workspace_root(&root.join("alone/node_modules")), None);
+    }
+
+    #[test]
+    fn test_available_after_is_capped_at_total() {
+        let disk = DiskSpace {
+            total: 100,
+            available: 30,
+        };
+        assert_eq!(disk.available_after(20), 50);
+        assert_eq!(disk.available_after(u64::MAX), 100);
+    }
+
+    #[test]
+    fn test_nvm_global_modules() {
+        let path = PathBuf::from("/home/user/.nvm/versions/node/v20.0.0/lib/node_modules");
+        assert!(is_system(&path), "nvm global modules should be system");
+    }
+
+    #[test]
+    fn test_npm_global_prefix() {
+        let path = PathBuf::from("/home/user/.npm-global/lib/node_modules");
+        assert!(is_system(&path), "npm global prefix should be system");
+    }
+
+    #[test]
+    fn test_usr_lib_node_modules() {
+        let path = PathBuf::from("/usr/lib/node_modules");
+        assert!(is_system(&path), "/usr/lib/node_modules should be system");
+    }
+
+    #[test]
+    fn test_homebrew_cellar() {
+        let path = PathBuf::from("/opt/homebrew/Cellar/node/21.0.0/lib/node_modules");
+        assert!(is_system(&path), "Homebrew cellar should be system");
+    }
+
+    #[test]
+    fn test_windows_npm_global() {
+        let path = PathBuf::from("C:\\Users\\user\\AppData\\Roaming\\npm\\node_modules");
+        assert!(is_system(&path), "Windows npm global dir should be system");
+    }
+
+    #[test]
+    fn test_project_modules_not_system() {
+        let path = PathBuf::from("/home/user/projects/app/node_modules");
+        assert!(
+            !is_system(&path),
+            "Project node_modules should not be system"
+        );
+    }
+
+    #[test]
+    fn test_custom_system_pattern() {
+        assert!(matches_system_pattern(
+            "/srv/Tools/node_modules",
+            &["/srv/tools/"]
+        ));
+        assert!(!matches_system_pattern(
+            "/srv/app/node_modules",
+            &["/srv/tools/"]
+        ));
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_max_depth_skips_deep_targets() {
+        let root = fixture(
+            "max-depth",
+            &["shallow/node_modules", "deep/a/b/c/node_modules"],
+        );
+
+        let found = scan(&root, &["--max-depth", "2"]).await;
+        assert_eq!(found.len(), 1, "Only the shallow target should be found");
+        assert!(found[0].path.ends_with("shallow/node_modules"));
+
+        let found = scan(&root, &[]).await;
+        assert_eq!(found.len(), 2, "Unbounded scan should find both targets");
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_multiple_targets() {
+        let root = fixture(
+            "multi-target",
+            &[
+                "web/node_modules",
+                "cli/target",
+                "web/node_modules/pkg/target",
+            ],
+        );
+
+        let found = scan(&root, &["--target", "node_modules, target"]).await;
+        let mut kinds: Vec<_> = found.iter().map(|m| m.target_kind.as_str()).collect();
+        kinds.sort_unstable();
+        assert_eq!(kinds, ["node_modules", "target"]);
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_include_nested_lists_targets_inside_targets() {
+        let root = fixture(
+            "include-nested",
+            &["web/node_modules/pkg/node_modules", "api/node_modules"],
+        );
+        let targets = [String::from("node_modules")];
+
+        for extra_args in [
+            &["--include-nested"][..],
+            &["--include-nested", "--no-ignore"],
+        ] {
+            let mut found = scan(&root, extra_args).await;
+            found.sort_by(|a, b| a.path.cmp(&b.path));
+            let depths: Vec<_> = found
+                .iter()
+                .map(|dir| nesting_depth(&dir.path, &targets))
+                .collect();
+            assert_eq!(depths, [0, 0, 1], "{extra_args:?}");
+        }
+        assert_eq!(scan(&root, &[]).await.len(), 2);
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_nested_targets_honour_the_walk_options() {
+        let root = fixture(
+            "nested-options",
+            &[
+                "web/node_modules/pkg/node_modules",
+                "web/node_modules/.cache/x/node_modules",
+                "web/node_modules/a/b/c/node_modules",
+            ],
+        );
+
+        for no_ignore in [&[][..], &["--no-ignore"]] {
+            let count = |extra: &'static [&'static str]| {
+                let args = [&["--include-nested"][..], no_ignore, extra].concat();
+                let root = root.to_path_buf();
+                async move { scan(&root, &args).await.len() }
+            };
+            assert_eq!(count(&[]).await, 4, "{no_ignore:?}");
+            assert_eq!(
+                count(&["--exclude-hidden-directories"]).await,
+                3,
+                "{no_ignore:?}"
+            );
+            assert_eq!(count(&["--max-depth", "5"]).await, 3, "{no_ignore:?}");
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_stdin_projects_are_checked_without_walking() {
+        let root = fixture(
+            "stdin-projects",
+            &[
+                "app/node_modules",
+                "app/packages/lib/node_modules",
+                "other/node_modules",
+            ],
+        );
+        let input = format!(
+            "{}\n\n{}\n",
+            root.join("app").display(),
+            root.join("gone").display()
+        );
+        let projects = read_projects(input.as_bytes()).unwrap();
+        assert_eq!(projects.len(), 2);
+
+        let args = Args::parse_from(["rskill", "--stdin"]);
+        let scanner = Scanner::new(ScanOptions::from(&args));
+        let (found, skipped) = scanner.scan_all(&projects).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(found[0].path.ends_with("app/node_modules"));
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].path.ends_with("gone"));
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_rust_profile_requires_cargo_manifest() {
+        let root = fixture("rust-profile", &["crate/target", "site/target"]);
+        std::fs::write(root.join("crate/Cargo.toml"), "").unwrap();
+
+        let found = scan(&root, &["--profile", "rust"]).await;
+        assert_eq!(
+            found.len(),
+            1,
+            "Only the target next to Cargo.toml is listed"
+        );
+        assert!(found[0].path.ends_with("crate/target"));
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_require_manifest_checks_every_ecosystem() {
+        let root = fixture(
+            "require-manifest",
+            &[
+                "web/node_modules",
+                "stray/node_modules",
+                "api/.venv",
+                "tool/.venv",
+                "crate/target",
+            ],
+        );
+        std::fs::write(root.join("web/package.json"), "{}").unwrap();
+        std::fs::write(root.join("api/requirements.txt"), "").unwrap();
+
+        let targets = ["-t", "node_modules,.venv,target"];
+        assert_eq!(scan(&root, &targets).await.len(), 5);
+
+        let found = scan(&root, &[&targets[..], &["--require-manifest"]].concat()).await;
+        let mut paths: Vec<_> = found
+            .iter()
+            .map(|dir| dir.path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("api/.venv"),
+                PathBuf::from("web/node_modules")
+            ]
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_ignore_files_keep_git_ignored_targets() {
+        let root = fixture(
+            "gitignore",
+            &["app/node_modules", "app/dist/vendor/node_modules"],
+        );
+        std::fs::write(root.join("app/.gitignore"), "node_modules\ndist\n").unwrap();
+
+        let found = scan(&root, &[]).await;
+        assert_eq!(found.len(), 1, "Ignored build output should not be walked");
+        assert!(found[0].path.ends_with("app/node_modules"));
+
+        let found = scan(&root, &["--no-ignore"]).await;
+        assert_eq!(
+            found.len(),
+            2,
+            "--no-ignore should walk ignored directories"
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_rskillignore_hides_targets() {
+        let root = fixture("rskillignore", &["keep/node_modules", "skip/node_modules"]);
+        std::fs::write(root.join(".rskillignore"), "skip/node_modules\n").unwrap();
+
+        let found = scan(&root, &[]).await;
+        assert_eq!(found.len(), 1, ".rskillignore should hide the target");
+        assert!(found[0].path.ends_with("keep/node_modules"));
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_exclude_globs() {
+        let root = fixture(
+            "exclude-globs",
+            &[
+                "app/node_modules",
+                "app/vendor/lib/node_modules",
+                "work/one/node_modules",
+                "work/one/deep/node_modules",
+            ],
+        );
+        let work = format!("{}/work/*", root.display());
+
+        let found = scan(&root, &["-E", "vendor", "-E", &work]).await;
+        let mut paths: Vec<_> = found
+            .iter()
+            .map(|d| d.path.strip_prefix(&root).unwrap())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, [Path::new("app/node_modules")]);
+
+        let found = scan(&root, &["-E", "**/vendor/**"]).await;
+        assert_eq!(found.len(), 3, "only paths below vendor should be excluded");
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_match_and_exclude_regex() {
+        let root = fixture(
+            "regex-filters",
+            &[
+                "clients/acme/app/node_modules",
+                "clients/acme/tools/node_modules",
+                "clients/globex/app/node_modules",
+                "third_party/x/node_modules",
+            ],
+        );
+
+        for no_ignore in [&[][..], &["--no-ignore"]] {
+            let args = [
+                no_ignore,
+                &[
+                    "--match",
+                    "/clients/[^/]+/app/",
+                    "--exclude-regex",
+                    "globex",
+                ],
+            ]
+            .concat();
+            let found = scan(&root, &args).await;
+            let paths: Vec<_> = found
+                .iter()
+                .map(|d| d.path.strip_prefix(&root).unwrap())
+                .collect();
+            assert_eq!(
+                paths,
+                [Path::new("clients/acme/app/node_modules")],
+                "{args:?}"
+            );
+        }
+        let found = scan(&root, &["--exclude-regex", "/(third_party|clients)/"]).await;
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn test_path_filter_keeps_checks_every_parent_below_the_root() {
+        let options = ScanOptions {
+            exclude_paths: vec![String::from("vendor"), String::from("/srv")],
+            match_regex: vec![String::from("/app/")],
+            ..ScanOptions::default()
+        };
+        let filter = PathFilter::new(&options);
+        let root = Path::new("/srv");
+
+        assert!(filter.keeps(Path::new("/srv/app/node_modules"), root));
+        assert!(!filter.keeps(Path::new("/srv/app/vendor/x/node_modules"), root));
+        assert!(!filter.keeps(Path::new("/srv/lib/node_modules"), root));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_scan_stops_walking() {
+        let root = fixture("cancel", &["app/node_modules", "lib/node_modules"]);
+        let scanner = Scanner::new(ScanOptions::default());
+        scanner.cancel_token().cancel();
+
+        let (found, skipped) = scanner.scan(&root).await.unwrap();
+        assert!(found.is_empty() && skipped.is_empty());
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let options = Arc::new(ScanOptions::default());
+        let dirs_scanned = Arc::new(AtomicUsize::new(0));
+        scan_directory(
+            root.join("app"),
+            options,
+            VisitedDirs::default(),
+            VisitedDirs::default(),
+            scanner.cancel_token(),
+            tx,
+            Arc::clone(&dirs_scanned),
+        );
+        assert!(rx.recv().await.is_none());
+        assert_eq!(dirs_scanned.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_scan_all_reports_unreadable_start_dirs() {
+        let root = fixture("scan-all", &["app/node_modules"]);
+        let missing = root.join("missing");
+
+        let scanner = Scanner::new(ScanOptions::default());
+        let (found, skipped) = scanner
+            .scan_all(&[root.to_path_buf(), missing.clone()])
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].path, missing);
+        assert!(scanner.scan(&missing).await.is_err());
+    }
+
+    #[test]
+    fn test_known_caches_are_listed_with_warnings() {
+        let caches = known_caches();
+        assert!(caches.iter().any(|cache| cache.name == "cargo-registry"));
+        for cache in caches {
+            let dir = FoundDir::pending(cache.path.clone());
+            assert_eq!(dir.target_kind, cache.name);
+            assert_eq!(dir.warning, Some(cache.warning));
+            assert!(!dir.is_system);
+            assert!(!crate::policy::Policy::default().refuses_global(&cache.path));
+        }
+        assert_eq!(known_cache(Path::new("/work/app/node_modules")), None);
+    }
+
+    #[test]
+    fn test_distinct_roots_drops_nested_and_repeated_roots() {
+        let root = fixture("roots", &["app/src", "lib"]);
+        let missing = root.join("missing");
+        let roots = [
+            root.join("app/src"),
+            root.join("lib"),
+            root.join("app"),
+            root.join("lib/../lib"),
+            missing.clone(),
+        ];
+        assert_eq!(
+            distinct_roots(&roots),
+            [root.join("lib"), root.join("app"), missing]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_overlapping_roots_report_targets_once() {
+        let root = fixture(
+            "overlap",
+            &["work/app/node_modules", "work/lib/node_modules"],
+        );
+        let link = root.join("link");
+        std::os::unix::fs::symlink(root.join("work/app"), &link).unwrap();
+        std::fs::create_dir_all(root.join("work/shared")).unwrap();
+        std::os::unix::fs::symlink(
+            root.join("work/lib/node_modules"),
+            root.join("work/shared/node_modules"),
+        )
+        .unwrap();
+
+        let scanner = Scanner::new(ScanOptions::default());
+        let (found, _) = scanner
+            .scan_all(&[root.join("work"), link, root.join("work/app")])
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 2, "{found:?}");
+    }
+
+    #[tokio::test]
+    async fn test_single_thread_scan_finds_every_target_of_a_wide_root() {
+        let projects: Vec<String> = (0..64).map(|i| format!("p{i}/node_modules")).collect();
+        let projects: Vec<&str> = projects.iter().map(String::as_str).collect();
+        let root = fixture("threads", &projects);
+
+        let scanner = Scanner::new(ScanOptions::default())
+            .with_threads(1)
+            .unwrap();
+        let (found, _) = scanner.scan(&root).await.unwrap();
+        assert_eq!(found.len(), 64);
+    }
+
+    #[tokio::test]
+    async fn test_per_disk_threads_walk_every_root() {
+        let first = fixture("disk-a", &["p1/node_modules", "p2/node_modules"]);
+        let second = fixture("disk-b", &["p3/node_modules"]);
+
+        let scanner = Scanner::new(ScanOptions::default()).with_per_disk_threads(1);
+        for _ in 0..2 {
+            let (found, _) = scanner
+                .scan_all(&[first.to_path_buf(), second.to_path_buf()])
+                .await
+                .unwrap();
+            assert_eq!(found.len(), 3, "a second scan reuses the disk's pool");
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_per_disk_pools_are_shared_by_a_disk() {
+        let first = fixture("pools-a", &["p1/node_modules"]);
+        let second = fixture("pools-b", &["p2/node_modules"]);
+        let scanner = Scanner::new(ScanOptions::default()).with_per_disk_threads(1);
+
+        let pools = scanner.root_pools(&[first.to_path_buf(), second.to_path_buf()]);
+        let (a, b) = (pools[0].clone().unwrap(), pools[1].clone().unwrap());
+        assert!(Arc::ptr_eq(&a, &b), "both roots are on one disk");
+        let again = scanner.root_pools(&[first.to_path_buf()]);
+        assert!(
+            Arc::ptr_eq(&a, again[0].as_ref().unwrap()),
+            "pools are reused"
+        );
+
+        // /proc is on no disk at all.
+        let pools = scanner.root_pools(&[first.to_path_buf(), PathBuf::from("/proc")]);
+        assert!(!Arc::ptr_eq(&a, pools[1].as_ref().unwrap()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_is_rotational_reads_the_disk_queue() {
+        assert_eq!(is_rotational(libc::makedev(0, 0)), None);
+        assert_eq!(disk_id(libc::makedev(0, 0)), libc::makedev(0, 0));
+
+        let dev = |dir: &Path| -> Option<u64> {
+            let dev = std::fs::read_to_string(dir.join("dev")).ok()?;
+            let (major, minor) = dev.trim().split_once(':')?;
+            Some(libc::makedev(major.parse().ok()?, minor.parse().ok()?))
+        };
+        for entry in std::fs::read_dir("/sys/class/block")
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            let dir = std::fs::canonicalize(entry.path()).unwrap();
+            let Some(device) = dev(&dir) else { continue };
+            // A partition is measured as its disk.
+            let disk = if dir.join("partition").exists() {
+                dir.parent().unwrap().to_path_buf()
+            } else {
+                dir.clone()
+            };
+            if std::fs::read_dir(disk.join("slaves")).is_ok_and(|mut s| s.next().is_some()) {
+                continue;
+            }
+            let Ok(flag) = std::fs::read_to_string(disk.join("queue/rotational")) else {
+                continue;
+            };
+            assert_eq!(is_rotational(device), Some(flag.trim() == "1"), "{dir:?}");
+            assert_eq!(Some(disk_id(device)), dev(&disk), "{dir:?}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_allocated_size_counts_hard_links_inside_once() {
+        use std::os::unix::fs::MetadataExt;
+
+        let root = fixture("allocated", &["project/node_modules"]);
+        let modules = root.join("project/node_modules");
+        let blocks = |path: &Path| std::fs::metadata(path).unwrap().blocks() * 512;
+        std::fs::write(modules.join("shared.js"), "x".repeat(10_000)).unwrap();
+        std::fs::hard_link(modules.join("shared.js"), modules.join("copy.js")).unwrap();
+        let without_outside_link = allocated_size(&modules).unwrap();
+        assert_eq!(
+            without_outside_link,
+            blocks(&modules)
+                + blocks(&modules.join("index.js"))
+                + blocks(&modules.join("shared.js"))
+        );
+
+        std::fs::hard_link(modules.join("shared.js"), root.join("outside.js")).unwrap();
+        assert_eq!(
+            allocated_size(&modules).unwrap(),
+            without_outside_link - blocks(&modules.join("shared.js")),
+            "files also linked outside free nothing when deleted"
+        );
+    }
+
+    #[test]
+    fn test_dir_stats_counts_files_and_newest_mtime() {
+        let root = fixture("stats", &["project/node_modules"]);
+        let modules = root.join("project/node_modules");
+        let nested = modules.join("lodash/fp");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(modules.join("lodash/lodash.js"), "x".repeat(300)).unwrap();
+        std::fs::write(nested.join("map.js"), "x".repeat(200)).unwrap();
+        let newest = SystemTime::now() + std::time::Duration::from_secs(3600);
+        let file = std::fs::File::options()
+            .write(true)
+            .open(nested.join("map.js"));
+        file.unwrap().set_modified(newest).unwrap();
+
+        let stats = dir_stats(&modules, &ScanOptions::default()).unwrap();
+        assert_eq!(stats.file_count, 3);
+        assert_eq!(
+            stats.size,
+            500 + std::fs::metadata(modules.join("index.js")).unwrap().len()
+        );
+        assert_eq!(stats.newest_file, Some(newest));
+        assert!(!stats.partial);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_stats_does_not_follow_symlinked_packages() {
+        let root = fixture("stats-links", &["project/node_modules", "shared"]);
+        let modules = root.join("project/node_modules");
+        let store = modules.join(".pnpm/lodash");
+        std::fs::create_dir_all(&store).unwrap();
+        std::fs::write(store.join("lodash.js"), "x".repeat(300)).unwrap();
+        std::fs::write(root.join("shared/big.js"), "x".repeat(1000)).unwrap();
+        std::os::unix::fs::symlink(&store, modules.join("lodash")).unwrap();
+        std::os::unix::fs::symlink(root.join("shared"), modules.join("shared")).unwrap();
+
+        let stats = dir_stats(&modules, &ScanOptions::default()).unwrap();
+        assert_eq!(stats.file_count, 2, "index.js and the stored lodash.js");
+        assert_eq!(stats.size, 300);
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_use_atime_dates_directories_by_last_read() {
+        let root = fixture("atime", &["project/node_modules"]);
+        let ago = |days: u64| SystemTime::now() - std::time::Duration::from_secs(days * 86400);
+        let index = std::fs::File::open(root.join("project/node_modules/index.js")).unwrap();
+        let times = std::fs::FileTimes::new()
+            .set_accessed(ago(2))
+            .set_modified(ago(40));
+        index.set_times(times).unwrap();
+        let project = std::fs::File::open(root.join("project")).unwrap();
+        project.set_modified(ago(30)).unwrap();
+
+        let by_project = scan(&root, &[]).await;
+        assert_eq!(by_project[0].modified / 86400, 30);
+        let by_access = scan(&root, &["--use-atime"]).await;
+        assert_eq!(by_access[0].modified / 86400, 2);
+    }
+
+    #[cfg(feature = "cli")]
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_same_filesystem_skips_other_mounts() {
+        let mount = PathBuf::from(format!("/dev/shm/rskill-mount-{}", std::process::id()));
+        let root = fixture("samefs", &["local/node_modules"]);
+        if std::fs::create_dir_all(mount.join("node_modules")).is_err()
+            || !on_other_device(&mount, &root)
+        {
+            let _ = std::fs::remove_dir_all(&mount);
+            return;
+        }
+        std::os::unix::fs::symlink(&mount, root.join("mounted")).unwrap();
+
+        let found = scan(&root, &["--follow-symlinks"]).await;
+        assert_eq!(found.len(), 2);
+        let found = scan(&root, &["--follow-symlinks", "--same-filesystem"]).await;
+        assert_eq!(found.len(), 1);
+        assert!(found[0].path.ends_with("local/node_modules"));
+
+        std::fs::remove_dir_all(&mount).unwrap();
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_size_budget_reports_partial_sizes() {
+        let root = fixture("budget", &["small/node_modules", "large/node_modules"]);
+        for i in 0..10 {
+            let file = root.join(format!("large/node_modules/{i}.js"));
+            std::fs::write(file, "x".repeat(100)).unwrap();
+        }
+
+        let found = scan(&root, &["--size-max-entries", "5", "--min-size", "2KB"]).await;
+        assert_eq!(found.len(), 1, "partial sizes pass the size filter");
+        let large = &found[0];
+        assert!(large.path.ends_with("large/node_modules"));
+        assert!(large.partial);
+        assert!(large.size < 1000);
+
+        let found = scan(&root, &["--size-max-entries", "100"]).await;
+        assert!(found.iter().all(|dir| !dir.partial));
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_active_projects() {
+        let root = fixture("active", &["fresh/node_modules", "stale/node_modules"]);
+        let old = SystemTime::now() - std::time::Duration::from_secs(30 * 86400);
+        for dir in ["stale", "stale/node_modules"] {
+            std::fs::File::open(root.join(dir))
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+        }
+
+        let mut found = scan(&root, &[]).await;
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        let active: Vec<_> = found.iter().map(|d| d.is_active).collect();
+        assert_eq!(active, [true, false]);
+
+        std::fs::write(root.join("stale/package-lock.json"), "{}").unwrap();
+        std::fs::File::open(root.join("stale"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert!(has_newer_lockfile(&root.join("stale/node_modules")));
+        assert!(scan(&root, &["--skip-active"]).await.is_empty());
+    }
+
+    #[cfg(feature = "cli")]
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_follow_symlinks_walks_each_directory_once() {
+        let root = fixture("symlinks", &["real/app/node_modules", "other"]);
+        std::os::unix::fs::symlink(root.join("real"), root.join("other/linked")).unwrap();
+        std::os::unix::fs::symlink(root.join("other"), root.join("other/loop")).unwrap();
+
+        assert_eq!(scan(&root, &["--no-ignore"]).await.len(), 1);
+        for args in [
+            ["--follow-symlinks", "--no-ignore"].as_slice(),
+            &["--follow-symlinks"],
+        ] {
+            let found = scan(&root, args).await;
+            assert_eq!(found.len(), 1, "{args:?} should report the target once");
+        }
+    }
+
+    #[test]
+    fn test_dir_breakdown_totals_subdirectories() {
+        let root = fixture("breakdown", &["small", "big/nested"]);
+        std::fs::write(root.join("big/nested/lib.js"), "x".repeat(100)).unwrap();
+        std::fs::write(root.join("README.md"), "top level").unwrap();
+
+        let breakdown = dir_breakdown(&root).unwrap();
+        assert_eq!(breakdown.file_count, 4);
+        assert_eq!(
+            breakdown.largest,
+            [(String::from("big"), 100), (String::from("small"), 0)]
+        );
+        assert!(breakdown.newest_file.is_some());
+    }
+
+    #[test]
+    fn test_dir_breakdown_splits_npm_scopes() {
+        let root = fixture(
+            "breakdown-scopes",
+            &["@types/node/lib", "@types/react", "lodash"],
+        );
+        std::fs::write(root.join("@types/node/lib/fs.d.ts"), "x".repeat(50)).unwrap();
+        std::fs::write(root.join("@types/README.md"), "x".repeat(7)).unwrap();
+
+        let names: Vec<String> = dir_breakdown(&root)
+            .unwrap()
+            .largest
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["@types/node", "@types", "@types/react", "lodash"]);
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_project_info_from_package_json() {
+        let root = fixture("package-json", &["app/node_modules", "bare/node_modules"]);
+        std::fs::write(
+            root.join("app/package.json"),
+            r#"{ "name": "my-app", "version": "1.2.0", "private": true }"#,
+        )
+        .unwrap();
+        std::fs::write(root.join("bare/package.json"), "{ not json").unwrap();
+
+        let mut found = scan(&root, &[]).await;
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        let labels: Vec<_> = found
+            .iter()
+            .map(|dir| dir.project.as_ref().map(ToString::to_string))
+            .collect();
+        assert_eq!(labels, [Some(String::from("my-app@1.2.0")), None]);
+    }
+
     #[test]
     fn test_hidden_file_unix() {
         let path = PathBuf::from("/home/user/.hidden_file");
@@ -0,0 +1,232 @@
+//! Sizes of the projects found by past scans, kept so `rskill growth` can show which ones grew.
+//!
+//! A project is the directory holding target directories, and its size is the sum of theirs.
+//! Sizes are stored with the [options](options_key) of the scan that measured them, and only
+//! compared with scans using the same ones, as filters change which directories are summed.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::options_key,
+    history::state_dir,
+    output::{format_size, SizeUnit},
+    scanner::{FoundDir, ScanOptions},
+};
+
+/// How many projects the text report lists. `--json` lists all of them.
+const SHOWN_PROJECTS: usize = 20;
+
+/// The size of a project's target directories at the last scan that found it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectSize {
+    pub size: u64,
+    /// When it was measured, as RFC 3339.
+    pub recorded: String,
+    /// The [options](options_key) of the scan that measured it.
+    #[serde(default)]
+    pub options: u64,
+}
+
+/// The last known size of every project, by path.
+pub type SizeHistory = BTreeMap<PathBuf, ProjectSize>;
+
+/// A project whose target directories grew since they were last measured.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Growth {
+    pub project: PathBuf,
+    pub before: u64,
+    pub now: u64,
+    /// When `before` was measured, as RFC 3339.
+    pub since: String,
+}
+
+impl Growth {
+    pub fn grown(&self) -> u64 {
+        self.now - self.before
+    }
+}
+
+/// `sizes.json` in the [state directory](state_dir).
+pub fn default_path() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("sizes.json"))
+}
+
+/// The size of each project in `found`, leaving out directories that couldn't be read and
+/// projects with a directory that was only partly measured.
+pub fn project_sizes(found: &[FoundDir]) -> BTreeMap<PathBuf, u64> {
+    let mut sizes: BTreeMap<PathBuf, Option<u64>> = BTreeMap::new();
+    for dir in found.iter().filter(|dir| dir.error.is_none()) {
+        if let Some(project) = dir.path.parent() {
+            let size = sizes.entry(project.to_path_buf()).or_insert(Some(0));
+            *size = size.filter(|_| !dir.partial).map(|size| size + dir.size);
+        }
+    }
+    sizes
+        .into_iter()
+        .filter_map(|(project, size)| Some((project, size?)))
+        .collect()
+}
+
+/// Reads the sizes stored at `path`. A missing file holds no sizes.
+pub fn load(path: &Path) -> io::Result<SizeHistory> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(SizeHistory::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Stores the sizes of the projects of a finished scan with `options` at `path`, keeping those
+/// of projects it didn't cover that still exist.
+pub fn record(path: &Path, found: &[FoundDir], options: &ScanOptions) -> io::Result<()> {
+    let mut history = load(path).unwrap_or_default();
+    history.retain(|project, _| project.is_dir());
+    let recorded = chrono::Local::now().to_rfc3339();
+    let options = options_key(options);
+    for (project, size) in project_sizes(found) {
+        let recorded = recorded.clone();
+        history.insert(
+            project,
+            ProjectSize {
+                size,
+                recorded,
+                options,
+            },
+        );
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_vec(&history)?)
+}
+
+/// The projects in `found` by a scan with `options` that grew since `previous` measured them
+/// with the same options, fastest-growing first.
+pub fn compare(previous: &SizeHistory, found: &[FoundDir], options: &ScanOptions) -> Vec<Growth> {
+    let options = options_key(options);
+    let mut grown: Vec<Growth> = project_sizes(found)
+        .into_iter()
+        .filter_map(|(project, now)| {
+            let before = previous
+                .get(&project)
+                .filter(|before| before.options == options)?;
+            (now > before.size).then(|| Growth {
+                project,
+                before: before.size,
+                now,
+                since: before.recorded.clone(),
+            })
+        })
+        .collect();
+    grown.sort_by(|a, b| b.grown().cmp(&a.grown()).then(a.project.cmp(&b.project)));
+    grown
+}
+
+/// A table of the projects that grew the most.
+pub fn report(grown: &[Growth], unit: SizeUnit) -> String {
+    if grown.is_empty() {
+        return String::from("No project grew since the last scan\n");
+    }
+
+    let mut text = format!(
+        "{:<50} {:>12} {:>12} {:>12} {:<17}\n",
+        "PROJECT", "BEFORE", "NOW", "GROWTH", "SINCE"
+    );
+    for growth in grown.iter().take(SHOWN_PROJECTS) {
+        let since = chrono::DateTime::parse_from_rfc3339(&growth.since)
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| growth.since.clone());
+        let _ = writeln!(
+            text,
+            "{:<50} {:>12} {:>12} {:>12} {since:<17}",
+            growth.project.display(),
+            format_size(growth.before, unit),
+            format_size(growth.now, unit),
+            format!("+{}", format_size(growth.grown(), unit)),
+        );
+    }
+    if grown.len() > SHOWN_PROJECTS {
+        let _ = writeln!(
+            text,
+            "... and {} more (--json lists all)",
+            grown.len() - SHOWN_PROJECTS
+        );
+    }
+
+    let total: u64 = grown.iter().map(Growth::grown).sum();
+    let _ = writeln!(
+        text,
+        "\n{} projects grew by {} in all",
+        grown.len(),
+        format_size(total, unit)
+    );
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn dir(path: &str, size: u64) -> FoundDir {
+        FoundDir::with_size(PathBuf::from(path), size, SystemTime::now())
+    }
+
+    fn size(size: u64, options: &ScanOptions) -> ProjectSize {
+        ProjectSize {
+            size,
+            recorded: String::from("2026-01-04T03:00:00+00:00"),
+            options: options_key(options),
+        }
+    }
+
+    #[test]
+    fn test_compare_sums_targets_per_project() {
+        let options = ScanOptions::default();
+        let previous = SizeHistory::from([
+            (PathBuf::from("/work/api"), size(100, &options)),
+            (PathBuf::from("/work/web"), size(500, &options)),
+        ]);
+        let found = [
+            dir("/work/api/node_modules", 150),
+            dir("/work/api/target", 250),
+            dir("/work/web/node_modules", 400),
+            dir("/work/new/node_modules", 900),
+        ];
+
+        let grown = compare(&previous, &found, &options);
+        assert_eq!(grown.len(), 1);
+        assert_eq!(grown[0].project, PathBuf::from("/work/api"));
+        assert_eq!(grown[0].grown(), 300);
+        let report = report(&grown, SizeUnit::B);
+        assert!(report.contains("2026-01-04 03:00"));
+        assert!(report.contains("1 projects grew by 300B in all"));
+    }
+
+    #[test]
+    fn test_compare_only_with_scans_filtered_alike() {
+        let filtered = ScanOptions {
+            min_size: Some(1000),
+            ..ScanOptions::default()
+        };
+        let previous = SizeHistory::from([(PathBuf::from("/work/api"), size(100, &filtered))]);
+        let found = [dir("/work/api/node_modules", 150)];
+        assert!(compare(&previous, &found, &ScanOptions::default()).is_empty());
+        assert_eq!(compare(&previous, &found, &filtered).len(), 1);
+
+        let partial = FoundDir {
+            partial: true,
+            ..dir("/work/api/target", 10)
+        };
+        assert!(project_sizes(&[found[0].clone(), partial]).is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+//! The audit trail of deletions, written through `tracing` and read back by `rskill history`.
+//!
+//! Every deletion is one JSON line holding its time, the session it belongs to, the path, its
+//! size and the outcome.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::Level;
+use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+use crate::{
+    fs::home_dir,
+    output::{format_size, SizeUnit},
+};
+
+/// The `tracing` target of audit events. Events of other targets aren't written to the log.
+pub const AUDIT_TARGET: &str = "rskill::audit";
+
+/// When this process started logging, shared by all of its records.
+static SESSION: OnceLock<String> = OnceLock::new();
+
+/// `$XDG_STATE_HOME/rskill`, `%LOCALAPPDATA%\rskill` on Windows, or `~/.local/state/rskill`.
+pub fn state_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_STATE_HOME").map(PathBuf::from)
+    };
+    base.filter(|dir| dir.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(".local").join("state")))
+        .map(|dir| dir.join("rskill"))
+}
+
+/// `history.log` in the [state directory](state_dir).
+pub fn default_log_path() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("history.log"))
+}
+
+/// Starts appending audit events to `path`, creating it if needed.
+pub fn init(path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    SESSION.get_or_init(|| chrono::Local::now().to_rfc3339());
+
+    let layer = tracing_subscriber::fmt::layer()
+        .json()
+        .flatten_event(true)
+        .with_current_span(false)
+        .with_span_list(false)
+        .with_target(false)
+        .with_writer(Mutex::new(file))
+        .with_filter(Targets::new().with_target(AUDIT_TARGET, Level::INFO));
+    tracing_subscriber::registry()
+        .with(layer)
+        .try_init()
+        .map_err(io::Error::other)
+}
+
+/// Records the outcome of deleting `path`. Does nothing unless [`init`] was called.
+pub fn record_deletion<E: fmt::Display>(
+    path: &Path,
+    size: u64,
+    trashed: bool,
+    result: &Result<(), E>,
+) {
+    let session = SESSION.get().map_or("", String::as_str);
+    let path = path.display();
+    match result {
+        Ok(()) => tracing::info!(
+            target: AUDIT_TARGET,
+            session,
+            %path,
+            size,
+            outcome = if trashed { "trashed" } else { "deleted" },
+        ),
+        Err(error) => tracing::warn!(
+            target: AUDIT_TARGET,
+            session,
+            %path,
+            size,
+            outcome = "failed",
+            %error,
+        ),
+    }
+}
+
+/// One line of the log, as far as `history` cares.
+#[derive(Debug, Deserialize)]
+struct Record {
+    session: String,
+    size: u64,
+    outcome: String,
+}
+
+/// The deletions of one run of rskill.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Session {
+    /// When the run started, as RFC 3339.
+    pub started: String,
+    pub deleted: usize,
+    pub failed: usize,
+    /// Bytes freed by the directories deleted or trashed.
+    pub reclaimed: u64,
+}
+
+/// Reads the sessions recorded in the log at `path`, oldest first. Lines that aren't audit
+/// records are left out.
+pub fn load(path: &Path) -> io::Result<Vec<Session>> {
+    let file = std::fs::File::open(path)?;
+    let mut sessions: Vec<Session> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for line in io::BufReader::new(file).lines() {
+        let Ok(record) = serde_json::from_str::<Record>(&line?) else {
+            continue;
+        };
+        let position = *positions.entry(record.session.clone()).or_insert_with(|| {
+            sessions.push(Session {
+                started: record.session.clone(),
+                deleted: 0,
+                failed: 0,
+                reclaimed: 0,
+            });
+            sessions.len() - 1
+        });
+
+        let session = &mut sessions[position];
+        if record.outcome == "failed" {
+            session.failed += 1;
+        } else {
+            session.deleted += 1;
+            session.reclaimed += record.size;
+        }
+    }
+    Ok(sessions)
+}
+
+/// A table of `sessions` with the space reclaimed in each and in total up to it.
+pub fn report(sessions: &[Session], unit: SizeUnit) -> String {
+    if sessions.is_empty() {
+        return String::from("No deletions recorded yet\n");
+    }
+
+    let mut text = format!(
+        "{:<17} {:>8} {:>7} {:>12} {:>12}\n",
+        "SESSION", "DELETED", "FAILED", "RECLAIMED", "TOTAL"
+    );
+    let mut total = 0;
+    for session in sessions {
+        total += session.reclaimed;
+        let started = chrono::DateTime::parse_from_rfc3339(&session.started)
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| session.started.clone());
+        text += &format!(
+            "{started:<17} {:>8} {:>7} {:>12} {:>12}\n",
+            session.deleted,
+            session.failed,
+            format_size(session.reclaimed, unit),
+            format_size(total, unit),
+        );
+    }
+    text += &format!(
+        "\nReclaimed {} over {} sessions\n",
+        format_size(total, unit),
+        sessions.len()
+    );
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_load_groups_records_by_session() {
+        let dir = TempDir::new("history");
+        let path = dir.join("history.log");
+        let first = "2026-01-04T03:00:00+00:00";
+        let second = "2026-01-11T03:00:00+00:00";
+        let record = |session: &str, size: u64, outcome: &str| {
+            format!(
+                "{{\"timestamp\":\"{session}\",\"level\":\"INFO\",\"session\":\"{session}\",\
+                 \"path\":\"/work/app/node_modules\",\"size\":{size},\"outcome\":\"{outcome}\"}}\n"
+            )
+        };
+        std::fs::write(
+            &path,
+            [
+                record(first, 100, "deleted"),
+                record(first, 50, "failed"),
+                String::from("not json\n"),
+                record(second, 30, "trashed"),
+                record(first, 20, "deleted"),
+            ]
+            .concat(),
+        )
+        .unwrap();
+
+        let sessions = load(&path).unwrap();
+        assert_eq!(
+            sessions,
+            [
+                Session {
+                    started: first.to_string(),
+                    deleted: 2,
+                    failed: 1,
+                    reclaimed: 120,
+                },
+                Session {
+                    started: second.to_string(),
+                    deleted: 1,
+                    failed: 0,
+                    reclaimed: 30,
+                },
+            ]
+        );
+
+        let report = report(&sessions, SizeUnit::B);
+        assert!(report.contains("2026-01-11 03:00"));
+        assert!(report.contains("Reclaimed 150B over 2 sessions"));
+    }
+}
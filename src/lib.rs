@@ -1,3 +1,33 @@
+pub mod cache;
+#[cfg(feature = "cli")]
 pub mod cli;
+#[cfg(feature = "cli")]
+pub mod config;
+#[cfg(feature = "cli")]
+pub mod daemon;
+pub mod dedupe;
+pub mod deleter;
+pub mod docker;
+pub mod dupes;
 pub mod fs;
+pub mod growth;
+pub mod history;
+pub mod metrics;
+pub mod notify;
+pub mod output;
+pub mod policy;
+pub mod remote;
+pub mod restore;
+pub mod scanner;
+pub mod script;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "cli")]
+pub mod theme;
+pub mod throttle;
+#[cfg(feature = "cli")]
 pub mod tui;
+pub mod watch;
+
+pub use deleter::Deleter;
+pub use scanner::{CancelToken, FoundDir, ScanOptions, Scanner};
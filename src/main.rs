@@ -1,89 +1,572 @@
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicUsize, Arc},
+    time::Instant,
+};
+
 use clap::Parser;
 use rskill::{
-    cli::{Args, NodeModule, SortBy},
-    fs, tui,
+    cache,
+    cli::{self, Args, Command},
+    config::{self, Config},
+    daemon, dedupe,
+    deleter::{self, CleanEvent},
+    docker, dupes, fs, growth, history,
+    notify::{self, CleanReport},
+    output::{self, CleanSummary},
+    policy::Policy,
+    remote::{self, RemoteRoot},
+    restore,
+    scanner::{CancelToken, FoundDir, ScanEvent, ScanOptions, ScanPlan, Scanner},
+    script,
+    theme::{self, Theme},
+    throttle, tui,
 };
-use std::{path::PathBuf, sync::Arc};
-use tokio::sync::Mutex;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 
-use std::sync::atomic::{AtomicBool, Ordering};
+/// Deletes every readable directory in `found`, printing each outcome and a summary table.
+/// Directories that must be confirmed one at a time are left alone. Fails if any deletion did.
+async fn clean(found: &[FoundDir], args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let policy = Policy::new(&args.protect, args.allow_global);
+    if let Some(path) = &args.emit_script {
+        if !args.deleter().is_local() {
+            return Err("--emit-script only deletes local directories, not those of --ssh".into());
+        }
+        let dirs: Vec<&FoundDir> = found
+            .iter()
+            .filter(|dir| dir.error.is_none())
+            .filter(|dir| {
+                !policy
+                    .level(&dir.path)
+                    .is_some_and(|level| level.blocks_bulk_delete())
+            })
+            .collect();
+        script::write(path, &dirs)?;
+        println!("Wrote {} deletions to {}", dirs.len(), path.display());
+        return Ok(());
+    }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+    let deleter = args.deleter();
+    let manifest = args.restore_manifest.as_deref();
+    let mut summary = CleanSummary::default();
+    let mut report = CleanReport::default();
+    let started = Instant::now();
+    let may_elevate = deleter.is_local() && !deleter.use_trash && std::io::stdin().is_terminal();
+    let mut denied = Vec::new();
+    deleter::clean(found, &deleter, &policy, manifest, |event| {
+        match &event {
+            CleanEvent::Failed(dir, e) if may_elevate && e.is_denied() => {
+                denied.push((*dir, e.clone()))
+            }
+            _ => {
+                summary.add(&event);
+                report.add(&event);
+            }
+        }
+        if event.is_problem() {
+            eprintln!("{event}");
+        } else {
+            println!("{event}");
+        }
+        if let Some(timing) = event.timing() {
+            eprintln!("  took {timing}");
+        }
+    })
+    .await;
+    if !denied.is_empty() {
+        eprint!(
+            "\n{} directories could not be deleted for lack of permission. Delete them as administrator? [y/N] ",
+            denied.len()
+        );
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        let elevate = answer.trim().eq_ignore_ascii_case("y");
+        for (dir, e) in denied {
+            let event = if elevate {
+                let removing = Instant::now();
+                let result = deleter::remove_elevated(e.moved_to.as_deref().unwrap_or(&dir.path));
+                history::record_deletion(&dir.path, dir.size, false, &result);
+                match result {
+                    Ok(()) => CleanEvent::Deleted(dir, removing.elapsed()),
+                    Err(e) => CleanEvent::Failed(dir, e.into()),
+                }
+            } else {
+                CleanEvent::Failed(dir, e)
+            };
+            if elevate && event.is_problem() {
+                eprintln!("{event}");
+            } else if elevate {
+                println!("{event}");
+            }
+            summary.add(&event);
+            report.add(&event);
+        }
+    }
 
-    if args.delete_all {
-        let confirmed = tui::confirm_delete_all(&args.target)?;
-        if !confirmed {
-            return Ok(());
+    let elapsed = started.elapsed();
+    print!("\n{}", summary.report(args.unit()));
+    eprintln!(
+        "Deleting took {elapsed:.1?} ({})",
+        output::format_rate(summary.total().reclaimed, elapsed, args.unit())
+    );
+    if let Some(path) = manifest.filter(|path| path.exists()) {
+        println!("Restore manifest: {}", path.display());
+    }
+    if let Some(url) = &args.notify_webhook {
+        if let Err(e) = report.post(url).await {
+            eprintln!("Could not notify {}: {e}", notify::redact(url));
+        }
+    }
+    match summary.total().failed {
+        0 => Ok(()),
+        failed => Err(format!("{failed} deletions failed").into()),
+    }
+}
+
+/// Lists, measures or cleans the directories of a finished scan, depending on the command.
+async fn run_headless(
+    mut found: Vec<FoundDir>,
+    args: &Args,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(sort) = &args.sort {
+        found.sort_by(|a, b| sort.compare(a, b));
+    }
+
+    match args.command() {
+        Command::Clean { .. } => clean(&found, args).await?,
+        Command::Dedupe { hard_links } => {
+            let roots: Vec<PathBuf> = found
+                .iter()
+                .filter(|dir| dir.error.is_none())
+                .map(|dir| dir.path.clone())
+                .collect();
+            let plan = dedupe::plan(&roots);
+            if args.dry_run {
+                print!("{}", plan.report(args.unit(), hard_links));
+                return Ok(());
+            }
+            let outcome = dedupe::apply(&plan, hard_links);
+            for (path, error) in &outcome.failed {
+                eprintln!("Could not link {}: {error}", path.display());
+            }
+            if outcome.skipped > 0 {
+                eprintln!(
+                    "Skipped {} duplicate files on filesystems without reflinks (--hard-links links them)",
+                    outcome.skipped
+                );
+            }
+            println!(
+                "Linked {} duplicate files ({} as reflinks), saving {}",
+                outcome.reflinked + outcome.hard_linked,
+                outcome.reflinked,
+                output::format_size(outcome.saved, args.unit())
+            );
+            if !outcome.failed.is_empty() {
+                return Err(format!("{} files could not be linked", outcome.failed.len()).into());
+            }
+        }
+        Command::Dupes => {
+            let duplicates = dupes::find(&found);
+            match args.output_format() {
+                Some(format) => {
+                    write_output(&dupes::render(&duplicates, format, args.unit())?, args)?
+                }
+                None => print!("{}", dupes::report(&duplicates, args.unit())),
+            }
+        }
+        Command::Stats => {
+            let stats = output::Stats::new(&found);
+            match args.output_format() {
+                Some(format) => write_output(&stats.render(format, args.unit())?, args)?,
+                None => print!("{}", stats.report(args.unit())),
+            }
+        }
+        _ => match args.output_format() {
+            Some(format) => write_output(&format.render(&found, args.unit())?, args)?,
+            None if args.dry_run => print!("{}", output::dry_run_report(&found, args.unit())),
+            None => print!(
+                "{}",
+                output::list_report(&found, &args.targets(), args.unit())
+            ),
+        },
+    }
+    Ok(())
+}
+
+/// Writes `rendered` to `--output-file`, or prints it.
+fn write_output(rendered: &str, args: &Args) -> std::io::Result<()> {
+    match &args.output_file {
+        Some(path) => std::fs::write(path, rendered),
+        None => {
+            print!("{rendered}");
+            Ok(())
+        }
+    }
+}
+
+/// Shows the directories arriving on `rx` in the interface, then exits with the code of the
+/// session.
+async fn run_interactive(
+    rx: UnboundedReceiver<ScanEvent>,
+    dirs_scanned: Arc<AtomicUsize>,
+    cancel: CancelToken,
+    plan: Option<ScanPlan>,
+    disk: Option<fs::DiskSpace>,
+    args: Args,
+    start: Instant,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // The interface blocks on terminal events, so keep it off the workers running the scan.
+    let manifest = args.restore_manifest.clone();
+    let print_summary = args.summary;
+    let (chosen, summary) = tokio::task::spawn_blocking(move || {
+        tui::run_tui(rx, dirs_scanned, cancel, plan, disk, args, start).map_err(|e| e.to_string())
+    })
+    .await??;
+
+    if let Some(path) = chosen {
+        println!("{}", path.display());
+    }
+    // Stdout only carries the chosen project, for shells to `cd` into.
+    if let Some(path) = manifest.filter(|path| path.exists()) {
+        eprintln!("Restore manifest: {}", path.display());
+    }
+    if print_summary {
+        eprintln!("{}", serde_json::to_string(&summary)?);
+    }
+    match summary.exit_code() {
+        0 => Ok(()),
+        code => std::process::exit(code),
+    }
+}
+
+/// Scans `root` over SSH, then shows or processes what was found like a local scan.
+async fn scan_remote(root: &RemoteRoot, args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let cancel = CancelToken::default();
+    let mut rx = remote::spawn_scan(root, &ScanOptions::from(&args), cancel.clone())?;
+    if !args.is_headless() {
+        return run_interactive(rx, Arc::default(), cancel, None, None, args, start).await;
+    }
+
+    let mut found = Vec::new();
+    while let Some(event) = rx.recv().await {
+        match event {
+            ScanEvent::Found(dir) => found.push(dir),
+            ScanEvent::Skipped(skipped) => {
+                return Err(format!("{}: {}", skipped.path.display(), skipped.reason).into())
+            }
+            ScanEvent::Matched(_) | ScanEvent::Dropped(_) => {}
+        }
+    }
+    run_headless(found, &args).await
+}
+
+/// Lists what Docker could free, in the interface or printed like the results of a scan.
+async fn scan_docker(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let options = ScanOptions::from(&args);
+    let found: Vec<FoundDir> = docker::list(&docker::socket_path())
+        .await?
+        .into_iter()
+        .filter(|entry| entry.matches(&options))
+        .collect();
+    if args.is_headless() {
+        return run_headless(found, &args).await;
+    }
+
+    let (tx, rx) = unbounded_channel();
+    for entry in found {
+        let _ = tx.send(ScanEvent::Found(entry));
+    }
+    drop(tx);
+    run_interactive(
+        rx,
+        Arc::default(),
+        CancelToken::default(),
+        None,
+        None,
+        args,
+        start,
+    )
+    .await
+}
+
+/// Runs the install commands of every project in the restore manifest at `path`, or only
+/// prints them with `--dry-run`.
+fn reinstall(path: &Path, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = restore::load(path)?;
+    let mut reinstalled = 0;
+    for entry in &entries {
+        let Some(project) = entry.path.parent().filter(|project| project.is_dir()) else {
+            eprintln!("skipped {}: the project is gone", entry.path.display());
+            continue;
+        };
+        if entry.path.exists() {
+            println!("skipped {}: already there", entry.path.display());
+            continue;
+        }
+        let commands = entry.install_commands();
+        if commands.is_empty() {
+            println!("skipped {}: nothing to run", entry.path.display());
+            continue;
+        }
+
+        let mut result = Ok(());
+        for command in &commands {
+            println!("$ {} (in {})", command.join(" "), project.display());
+            if !dry_run {
+                result = restore::run_command(command, project);
+                if result.is_err() {
+                    break;
+                }
+            }
+        }
+        match result {
+            Ok(()) => reinstalled += 1,
+            Err(e) => eprintln!("failed to reinstall {}: {e}", project.display()),
         }
     }
-    let results = Arc::new(Mutex::new(Vec::<NodeModule>::with_capacity(1000)));
-    let mut handles = Vec::with_capacity(10);
 
-    let start_dir = if args.full {
-        PathBuf::from(std::env::var("HOME")?)
+    let verb = if dry_run {
+        "Would reinstall"
     } else {
-        std::fs::canonicalize(&args.directory)?
+        "Reinstalled"
     };
+    println!("{verb} {reinstalled} of {} projects", entries.len());
+    Ok(())
+}
 
-    let scanning = Arc::new(AtomicBool::new(true));
-    let start = std::time::Instant::now();
-    let spinner_handle = {
-        let scanning = Arc::clone(&scanning);
-        tokio::spawn(tui::display_spinner(scanning))
-    };
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = Args::parse();
+    if args.io_nice {
+        throttle::lower_priority();
+    }
 
-    let mut entries = tokio::fs::read_dir(&start_dir).await?;
-    while let Some(entry) = entries.next_entry().await? {
-        let results = Arc::clone(&results);
-        let args = args.clone();
-        let path = entry.path();
+    if let Command::Completions { shell } = args.command() {
+        cli::write_completions(shell, &mut std::io::stdout());
+        return Ok(());
+    }
 
-        handles.push(tokio::spawn(async move {
-            fs::scan_directory(path, args, results).await;
-        }));
+    if let Command::Daemon {
+        service: Some(kind),
+        ..
+    } = args.command()
+    {
+        let exe = std::env::current_exe()?;
+        let daemon_args = daemon::service_args(std::env::args().skip(1));
+        let working_dir = std::env::current_dir()?;
+        print!(
+            "{}",
+            daemon::service_file(kind, &exe, &daemon_args, &working_dir)
+        );
+        return Ok(());
     }
 
-    for handle in handles {
-        handle.await?;
+    if let Some(path) = config::config_path() {
+        let config = Config::load(&path)?;
+        args.protect.extend(config.protect);
+        args.exclude_paths.extend(config.exclude);
+        args.theme = args.theme.or(config.theme);
+        args.colors = config.colors;
+        args.size_bands = args.size_bands.or_else(|| {
+            config
+                .size_bands
+                .and_then(|b| cli::parse_size_bands(&b).ok())
+        });
+        args.bookmarks = config.roots;
     }
 
-    scanning.store(false, Ordering::Relaxed);
-    let _ = spinner_handle.await?;
+    if let Command::Reinstall { manifest } = args.command() {
+        return reinstall(&manifest, args.dry_run);
+    }
 
-    let modules = results.lock().await;
+    if args.command() == Command::History {
+        let path = args
+            .log_file
+            .clone()
+            .or_else(history::default_log_path)
+            .ok_or("could not find the history log")?;
+        let sessions = match history::load(&path) {
+            Ok(sessions) => sessions,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        match args.output_format() {
+            Some(_) => println!("{}", serde_json::to_string_pretty(&sessions)?),
+            None => print!("{}", history::report(&sessions, args.unit())),
+        }
+        return Ok(());
+    }
 
-    let modules_vec = match &args.sort {
-        Some(o) => match o {
-            SortBy::Path => {
-                let mut modules_vec = modules.to_vec();
-                modules_vec.sort_unstable_by(|a, b| {
-                    a.path
-                        .to_string_lossy()
-                        .partial_cmp(&b.path.to_string_lossy())
-                        .unwrap_or(std::cmp::Ordering::Equal)
-                });
-                modules_vec
+    match &args.log_file {
+        Some(path) => history::init(path)?,
+        // The history is a convenience, not worth failing the run for.
+        None => {
+            if let Some(path) = history::default_log_path() {
+                let _ = history::init(&path);
             }
-            SortBy::Size => {
-                let mut modules_vec = modules.to_vec();
-                modules_vec.sort_unstable_by(|a, b| {
-                    b.size
-                        .partial_cmp(&a.size)
-                        .unwrap_or(std::cmp::Ordering::Equal)
-                });
-                modules_vec
+        }
+    }
+
+    if let (Command::Clean { yes: false }, None) = (args.command(), &args.emit_script) {
+        return Err("refusing to delete without --yes".into());
+    }
+    args.check_delete_all()?;
+    if args.notify_webhook.is_some()
+        && !matches!(
+            args.command(),
+            Command::Clean { .. } | Command::Daemon { .. }
+        )
+    {
+        return Err("--notify-webhook only applies to clean and daemon".into());
+    }
+
+    let bulk_delete = args.delete_all || matches!(args.command(), Command::Clean { .. });
+    // Docker objects and remote projects can't be reinstalled from a manifest.
+    if bulk_delete
+        && args.restore_manifest.is_none()
+        && args.emit_script.is_none()
+        && args.command() != Command::Docker
+        && args.ssh.is_none()
+    {
+        args.restore_manifest = restore::default_manifest_path();
+    }
+
+    let theme = Theme::resolve(args.theme, &args.colors, theme::no_color_requested());
+    if args.pick_root && args.directory.len() > 1 {
+        return Err("--pick-root browses from a single --directory".into());
+    }
+    if args.pick_root && !args.is_headless() {
+        let start = std::fs::canonicalize(&args.directory[0])?;
+        match tui::pick_root(start, &theme)? {
+            Some(dir) => args.directory = vec![dir.display().to_string()],
+            None => return Ok(()),
+        }
+    }
+
+    if args.delete_all && !args.is_headless() {
+        let confirmed = tui::confirm_delete_all(&args.targets().join(", "), &theme)?;
+        if !confirmed {
+            return Ok(());
+        }
+    }
+
+    if args.command() == Command::Docker {
+        return scan_docker(args).await;
+    }
+
+    if let Some(root) = args.ssh.clone() {
+        match args.command() {
+            Command::Daemon { .. } => return Err("--ssh can't be used with daemon".into()),
+            Command::Dedupe { .. } => return Err("--ssh can't be used with dedupe".into()),
+            Command::Growth => return Err("--ssh can't be used with growth".into()),
+            _ if args.emit_script.is_some() => {
+                return Err("--ssh can't be used with --emit-script".into())
             }
-            SortBy::LastMod => {
-                let mut modules_vec = modules.to_vec();
-                modules_vec.sort_unstable_by(|a, b| b.modified.cmp(&a.modified));
-                modules_vec
+            _ => {}
+        }
+        return scan_remote(&root, args).await;
+    }
+
+    let roots = args.roots()?;
+    let start_dirs = if args.stdin {
+        fs::read_projects(std::io::stdin().lock())?
+    } else if !roots.is_empty() {
+        roots.into_iter().map(|(_, path)| path).collect()
+    } else if args.all_drives {
+        fs::fixed_drives()
+    } else if args.full {
+        vec![fs::home_dir().ok_or("could not find the home directory")?]
+    } else {
+        args.directory
+            .iter()
+            .map(std::fs::canonicalize)
+            .collect::<Result<_, _>>()?
+    };
+
+    let mut scanner = Scanner::new(ScanOptions::from(&args));
+    if args.threads.is_some() || args.io_nice {
+        scanner = scanner.with_threads(args.threads.map_or(0, |threads| threads.get()))?;
+    }
+    // An explicit --threads caps the whole scan.
+    if args.threads.is_none() {
+        scanner = scanner.with_per_disk_threads(args.per_disk_threads.map_or(0, |t| t.get()));
+    }
+    let start = Instant::now();
+    let cache_file = cache::cache_path(&start_dirs, scanner.options());
+    let cached = match &cache_file {
+        Some(path) if args.cached => cache::load(path, scanner.options()).ok(),
+        _ => None,
+    };
+
+    if let Command::Daemon {
+        schedule, metrics, ..
+    } = args.command()
+    {
+        daemon::run(&schedule, metrics, &scanner, &start_dirs, &args).await?;
+        return Ok(());
+    }
+
+    if args.is_headless() {
+        let sizes_file = growth::default_path();
+        // Read before this scan replaces them.
+        let previous = match (&sizes_file, args.command()) {
+            (Some(path), Command::Growth) => growth::load(path)?,
+            _ => growth::SizeHistory::new(),
+        };
+        let found = match cached {
+            Some(found) => found,
+            None => {
+                let (found, _) = scanner.scan_all(&start_dirs).await?;
+                if let Some(path) = &cache_file {
+                    let _ = cache::save(path, &found);
+                }
+                // Sizes measured right before a cleanup would only show regrowth from nothing.
+                if let Some(path) = sizes_file.as_ref().filter(|_| !bulk_delete) {
+                    let _ = growth::record(path, &found, scanner.options());
+                }
+                found
             }
-        },
-        None => modules.to_vec(),
+        };
+        if args.command() == Command::Growth {
+            let grown = growth::compare(&previous, &found, scanner.options());
+            match args.output_format() {
+                Some(_) => println!("{}", serde_json::to_string_pretty(&grown)?),
+                None => print!("{}", growth::report(&grown, args.unit())),
+            }
+            return Ok(());
+        }
+        return run_headless(found, &args).await;
+    }
+
+    let (rx, dirs_scanned) = match (cached, cache_file) {
+        (Some(found), _) => cache::replay(found),
+        (None, cache_file) => {
+            let (rx, dirs_scanned) = scanner.spawn_all(&start_dirs).await?;
+            let options = scanner.options().clone();
+            let rx = cache::record(rx, scanner.cancel_token(), move |found| {
+                if let Some(path) = cache_file {
+                    let _ = cache::save(&path, &found);
+                }
+                if let Some(path) = growth::default_path().filter(|_| !bulk_delete) {
+                    let _ = growth::record(&path, &found, &options);
+                }
+            });
+            (rx, dirs_scanned)
+        }
+    };
+    let disk = match start_dirs.as_slice() {
+        [start_dir] => fs::disk_space(start_dir),
+        _ => None,
     };
 
-    let _ = tui::run_tui(modules_vec, args, start);
-    Ok(())
+    let plan = ScanPlan {
+        scanner: scanner.clone(),
+        start_dirs,
+    };
+
+    let cancel = scanner.cancel_token();
+    run_interactive(rx, dirs_scanned, cancel, Some(plan), disk, args, start).await
 }
@@ -0,0 +1,243 @@
+//! Prometheus metrics of the scheduled cleanups, served by `rskill daemon --metrics`.
+//!
+//! The endpoint speaks just enough HTTP to answer `GET /metrics`, which is all a scraper asks.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Semaphore,
+};
+
+use crate::scanner::FoundDir;
+
+/// The most of a request that is read, which is plenty for its request line.
+const MAX_REQUEST: usize = 8 * 1024;
+/// How long a client has to send its request and read the answer before it's dropped.
+const EXCHANGE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many clients are answered at once. Others are disconnected right away.
+const MAX_CONNECTIONS: usize = 16;
+
+/// The directories found by the last scan of one target.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct TargetTotals {
+    count: usize,
+    bytes: u64,
+}
+
+/// What the daemon has found and deleted since it started.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// The readable directories of the last scan, per target.
+    found: BTreeMap<String, TargetTotals>,
+    scan_duration: Duration,
+    deleted: u64,
+    deleted_bytes: u64,
+    failed: u64,
+    /// When the last cleanup finished, in seconds since the Unix epoch.
+    last_cleanup: Option<i64>,
+}
+
+impl Metrics {
+    pub fn record_scan(&mut self, found: &[FoundDir], duration: Duration) {
+        self.found.clear();
+        for dir in found.iter().filter(|dir| dir.error.is_none()) {
+            let totals = self.found.entry(dir.target_kind.clone()).or_default();
+            totals.count += 1;
+            totals.bytes += dir.size;
+        }
+        self.scan_duration = duration;
+    }
+
+    pub fn record_cleanup(&mut self, deleted: usize, reclaimed: u64, failed: usize) {
+        self.deleted += deleted as u64;
+        self.deleted_bytes += reclaimed;
+        self.failed += failed as u64;
+        self.last_cleanup = Some(chrono::Local::now().timestamp());
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{name}{labels} {value}");
+            }
+        };
+        let per_target = |value: fn(&TargetTotals) -> String| -> Vec<(String, String)> {
+            self.found
+                .iter()
+                .map(|(target, totals)| {
+                    (format!("{{target=\"{}\"}}", escape(target)), value(totals))
+                })
+                .collect()
+        };
+        let single = |value: String| [(String::new(), value)];
+
+        metric(
+            "rskill_reclaimable_bytes",
+            "gauge",
+            "Size of the directories found by the last scan.",
+            &per_target(|t| t.bytes.to_string()),
+        );
+        metric(
+            "rskill_directories",
+            "gauge",
+            "Number of directories found by the last scan.",
+            &per_target(|t| t.count.to_string()),
+        );
+        metric(
+            "rskill_scan_duration_seconds",
+            "gauge",
+            "How long the last scan took.",
+            &single(self.scan_duration.as_secs_f64().to_string()),
+        );
+        metric(
+            "rskill_deleted_directories_total",
+            "counter",
+            "Directories deleted since the daemon started.",
+            &single(self.deleted.to_string()),
+        );
+        metric(
+            "rskill_deleted_bytes_total",
+            "counter",
+            "Bytes reclaimed since the daemon started.",
+            &single(self.deleted_bytes.to_string()),
+        );
+        metric(
+            "rskill_failed_deletions_total",
+            "counter",
+            "Deletions that failed since the daemon started.",
+            &single(self.failed.to_string()),
+        );
+        if let Some(time) = self.last_cleanup {
+            metric(
+                "rskill_last_cleanup_timestamp_seconds",
+                "gauge",
+                "When the last cleanup finished, in seconds since the Unix epoch.",
+                &single(time.to_string()),
+            );
+        }
+        out
+    }
+}
+
+/// Escapes a label value of the text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Answers `GET /metrics` on `listener` with `metrics` from a background task.
+pub fn serve(listener: TcpListener, metrics: Arc<Mutex<Metrics>>) {
+    let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let Ok(permit) = Arc::clone(&connections).try_acquire_owned() else {
+                continue;
+            };
+            let metrics = Arc::clone(&metrics);
+            tokio::spawn(async move {
+                let _ = tokio::time::timeout(EXCHANGE_TIMEOUT, respond(stream, &metrics)).await;
+                drop(permit);
+            });
+        }
+    });
+}
+
+async fn respond(mut stream: TcpStream, metrics: &Mutex<Metrics>) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut line = request.lines().next().unwrap_or_default().split(' ');
+
+    let (status, body) = match (line.next(), line.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = metrics.lock().map(|m| m.render()).unwrap_or_default();
+            ("200 OK", body)
+        }
+        (Some("GET"), _) => ("404 Not Found", String::from("Not found\n")),
+        _ => (
+            "405 Method Not Allowed",
+            String::from("Method not allowed\n"),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_render_reports_each_target() {
+        let dir = |path: &str, size| FoundDir {
+            size,
+            ..FoundDir::pending(PathBuf::from(path))
+        };
+        let mut metrics = Metrics::default();
+        metrics.record_scan(
+            &[
+                dir("/a/node_modules", 10),
+                dir("/b/node_modules", 5),
+                dir("/c/target", 7),
+            ],
+            Duration::from_millis(1500),
+        );
+        metrics.record_cleanup(3, 22, 0);
+
+        let text = metrics.render();
+        assert!(text.contains("rskill_reclaimable_bytes{target=\"node_modules\"} 15\n"));
+        assert!(text.contains("rskill_directories{target=\"target\"} 1\n"));
+        assert!(text.contains("rskill_scan_duration_seconds 1.5\n"));
+        assert!(text.contains("rskill_deleted_bytes_total 22\n"));
+        assert!(text.contains("# TYPE rskill_deleted_directories_total counter\n"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_answers_metrics_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let metrics = Arc::new(Mutex::new(Metrics::default()));
+        metrics.lock().unwrap().record_cleanup(1, 4096, 0);
+        serve(listener, metrics);
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("rskill_deleted_bytes_total 4096\n"));
+    }
+}
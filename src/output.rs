@@ -0,0 +1,710 @@
+use chrono::{DateTime, Local, SecondsFormat};
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Component, Path, MAIN_SEPARATOR, MAIN_SEPARATOR_STR},
+    time::Duration,
+};
+
+use crate::{
+    deleter::CleanEvent,
+    fs::nesting_depth,
+    scanner::{FoundDir, ProjectInfo},
+};
+
+/// Unit sizes are shown in. `Auto` picks, for each size, the largest unit keeping it at least 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SizeUnit {
+    #[default]
+    Auto,
+    B,
+    Kb,
+    Mb,
+    Gb,
+    Tb,
+}
+
+impl SizeUnit {
+    const FIXED: [SizeUnit; 5] = [
+        SizeUnit::B,
+        SizeUnit::Kb,
+        SizeUnit::Mb,
+        SizeUnit::Gb,
+        SizeUnit::Tb,
+    ];
+
+    fn shift(self) -> u32 {
+        match self {
+            SizeUnit::Auto | SizeUnit::B => 0,
+            SizeUnit::Kb => 10,
+            SizeUnit::Mb => 20,
+            SizeUnit::Gb => 30,
+            SizeUnit::Tb => 40,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SizeUnit::Auto => "auto",
+            SizeUnit::B => "B",
+            SizeUnit::Kb => "KB",
+            SizeUnit::Mb => "MB",
+            SizeUnit::Gb => "GB",
+            SizeUnit::Tb => "TB",
+        }
+    }
+
+    /// The fixed unit `bytes` is shown in.
+    fn resolve(self, bytes: u64) -> SizeUnit {
+        match self {
+            SizeUnit::Auto => Self::FIXED
+                .into_iter()
+                .rev()
+                .find(|unit| bytes >> unit.shift() > 0)
+                .unwrap_or(SizeUnit::B),
+            unit => unit,
+        }
+    }
+}
+
+/// Formats `bytes` in `unit`, e.g. `1.50GB`. Whole bytes are shown without decimals.
+pub fn format_size(bytes: u64, unit: SizeUnit) -> String {
+    match unit.resolve(bytes) {
+        SizeUnit::B | SizeUnit::Auto => format!("{bytes}B"),
+        unit => format!(
+            "{:.2}{}",
+            bytes as f64 / (1u64 << unit.shift()) as f64,
+            unit.label()
+        ),
+    }
+}
+
+/// `bytes` handled over `elapsed`, e.g. `45.30MB/s`.
+pub fn format_rate(bytes: u64, elapsed: Duration, unit: SizeUnit) -> String {
+    let per_second = bytes as f64 / elapsed.as_secs_f64().max(0.001);
+    format!("{}/s", format_size(per_second as u64, unit))
+}
+
+/// `path` shortened to at most `width` characters, e.g. `~/w/…/myapp/node_modules`. The home
+/// directory becomes `~`, then the leading directories are cut to their initial and finally
+/// replaced by `…`, keeping the project and target directories whole as long as possible.
+pub fn shorten_path(path: &Path, home: Option<&Path>, width: usize) -> String {
+    let (mut prefix, rest) = match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(rest) => (format!("~{MAIN_SEPARATOR}"), rest),
+        None => (String::new(), path),
+    };
+    let mut parts = Vec::new();
+    for component in rest.components() {
+        match component {
+            Component::Normal(name) => parts.push(name.to_string_lossy().into_owned()),
+            other => prefix.push_str(&other.as_os_str().to_string_lossy()),
+        }
+    }
+    let join = |parts: &[String]| {
+        format!("{prefix}{}", parts.join(MAIN_SEPARATOR_STR))
+            .trim_end_matches(MAIN_SEPARATOR)
+            .to_string()
+    };
+    let fits = |text: &String| text.chars().count() <= width;
+
+    let mut shortened = join(&parts);
+    // Every directory but the project and the target may shrink.
+    let leading = parts.len().saturating_sub(2);
+    for i in 0..leading {
+        if fits(&shortened) {
+            return shortened;
+        }
+        let hidden = usize::from(parts[i].starts_with('.'));
+        parts[i] = parts[i].chars().take(1 + hidden).collect();
+        shortened = join(&parts);
+    }
+    if !fits(&shortened) && leading >= 2 {
+        parts.splice(1..leading, [String::from("…")]);
+        shortened = join(&parts);
+    }
+    if fits(&shortened) {
+        return shortened;
+    }
+
+    let chars: Vec<char> = shortened.chars().collect();
+    let kept = width.saturating_sub(1).min(chars.len());
+    let mut truncated: String = chars[chars.len() - kept..].iter().collect();
+    if width > 0 {
+        truncated.insert(0, '…');
+    }
+    truncated
+}
+
+/// When something last modified `seconds_ago` seconds ago was modified.
+pub fn modified_at(seconds_ago: i64) -> DateTime<Local> {
+    Local::now() - chrono::Duration::seconds(seconds_ago)
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModuleRecord<'a> {
+    pub path: &'a Path,
+    pub size: u64,
+    /// Seconds since the parent project was last modified.
+    pub modified: i64,
+    /// When the parent project was last modified, as an RFC 3339 timestamp.
+    pub modified_at: String,
+    pub is_dangerous: bool,
+    pub is_active: bool,
+    pub target_kind: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<&'a ProjectInfo>,
+    /// The size is a lower bound: measuring ran out of `--size-timeout` or `--size-max-entries`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_count: Option<u64>,
+    /// Seconds since the newest file inside was last modified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newest_file: Option<i64>,
+}
+
+impl<'a> From<&'a FoundDir> for ModuleRecord<'a> {
+    fn from(module: &'a FoundDir) -> Self {
+        Self {
+            path: &module.path,
+            size: module.size,
+            modified: module.modified,
+            modified_at: modified_at(module.modified).to_rfc3339_opts(SecondsFormat::Secs, false),
+            is_dangerous: module.is_dangerous,
+            is_active: module.is_active,
+            target_kind: &module.target_kind,
+            project: module.project.as_ref(),
+            partial: module.partial,
+            file_count: module.file_count,
+            newest_file: module.newest_file,
+        }
+    }
+}
+
+pub fn to_json(modules: &[FoundDir]) -> serde_json::Result<String> {
+    let records: Vec<ModuleRecord> = modules.iter().map(ModuleRecord::from).collect();
+    serde_json::to_string_pretty(&records)
+}
+
+/// One JSON object per line.
+pub fn to_ndjson(modules: &[FoundDir]) -> serde_json::Result<String> {
+    let mut lines = String::new();
+    for module in modules {
+        lines += &serde_json::to_string(&ModuleRecord::from(module))?;
+        lines.push('\n');
+    }
+    Ok(lines)
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// A header row followed by one row per directory.
+pub fn to_csv(modules: &[FoundDir], unit: SizeUnit) -> String {
+    let mut csv = String::from("path,bytes,size,age_days,dangerous,target_kind\n");
+    for module in modules {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{}",
+            csv_field(&module.path.to_string_lossy()),
+            module.size,
+            format_size(module.size, unit),
+            module.modified / 86400,
+            module.is_dangerous,
+            csv_field(&module.target_kind),
+        );
+    }
+    csv
+}
+
+/// Formats `--output` can write scan results in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// Guesses the format from a file extension, falling back to JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => OutputFormat::Csv,
+            Some("ndjson" | "jsonl") => OutputFormat::Ndjson,
+            _ => OutputFormat::Json,
+        }
+    }
+
+    pub fn render(&self, modules: &[FoundDir], unit: SizeUnit) -> serde_json::Result<String> {
+        match self {
+            OutputFormat::Json => to_json(modules).map(|json| json + "\n"),
+            OutputFormat::Csv => Ok(to_csv(modules, unit)),
+            OutputFormat::Ndjson => to_ndjson(modules),
+        }
+    }
+
+    /// Renders `rows` as a JSON array, one JSON object per line, or a CSV table of `header`
+    /// with the `fields` of each row.
+    pub fn render_rows<T: Serialize>(
+        &self,
+        rows: &[T],
+        header: &str,
+        fields: impl Fn(&T) -> Vec<String>,
+    ) -> serde_json::Result<String> {
+        match self {
+            OutputFormat::Json => serde_json::to_string_pretty(rows).map(|json| json + "\n"),
+            OutputFormat::Ndjson => {
+                let mut lines = String::new();
+                for row in rows {
+                    lines += &serde_json::to_string(row)?;
+                    lines.push('\n');
+                }
+                Ok(lines)
+            }
+            OutputFormat::Csv => {
+                let mut csv = format!("{header}\n");
+                for row in rows {
+                    let fields: Vec<String> = fields(row).iter().map(|f| csv_field(f)).collect();
+                    let _ = writeln!(csv, "{}", fields.join(","));
+                }
+                Ok(csv)
+            }
+        }
+    }
+}
+
+/// One line per directory: size, age and path, indented by how deep it is nested in other
+/// `targets`.
+pub fn list_report(dirs: &[FoundDir], targets: &[String], unit: SizeUnit) -> String {
+    let mut report = String::new();
+    for dir in dirs {
+        let size = match &dir.error {
+            Some(error) => format!("({error})"),
+            None if dir.partial => format!("≥{}", format_size(dir.size, unit)),
+            None => format_size(dir.size, unit),
+        };
+        let _ = write!(
+            report,
+            "{size:>12}  {:>6}  {}{}",
+            format!("{}d", dir.modified / 86400),
+            "  ".repeat(nesting_depth(&dir.path, targets)),
+            dir.path.display()
+        );
+        match &dir.project {
+            Some(project) => {
+                let _ = writeln!(report, "  ({project})");
+            }
+            None => report.push('\n'),
+        }
+    }
+    report
+}
+
+/// How many of the largest directories [`Stats`] lists.
+const LARGEST_COUNT: usize = 10;
+
+/// Upper bounds, in days, of the age ranges in [`Stats::ages`], with their labels.
+const AGE_BUCKETS: &[(i64, &str)] = &[
+    (7, "< 1 week"),
+    (30, "1 week - 1 month"),
+    (90, "1 - 3 months"),
+    (180, "3 - 6 months"),
+    (365, "6 - 12 months"),
+    (i64::MAX, "> 1 year"),
+];
+
+#[derive(Debug, Default, Serialize)]
+pub struct Totals {
+    pub count: usize,
+    pub size: u64,
+}
+
+impl Totals {
+    fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.size += size;
+    }
+}
+
+/// Directories whose project was last modified within an age range.
+#[derive(Debug, Serialize)]
+pub struct AgeRange {
+    pub label: &'static str,
+    #[serde(flatten)]
+    pub totals: Totals,
+}
+
+/// One entry of [`Stats`], as a row of CSV or NDJSON.
+#[derive(Debug, Serialize)]
+pub struct StatsRow<'a> {
+    /// `total`, `target`, `largest` or `age`.
+    pub section: &'static str,
+    /// The target, path or age range.
+    pub label: Cow<'a, str>,
+    pub count: usize,
+    pub bytes: u64,
+}
+
+/// Aggregate numbers over the readable directories of a scan.
+#[derive(Debug, Serialize)]
+pub struct Stats<'a> {
+    #[serde(flatten)]
+    pub total: Totals,
+    pub per_kind: BTreeMap<&'a str, Totals>,
+    /// Largest directories first.
+    pub largest: Vec<ModuleRecord<'a>>,
+    /// Directories grouped by the age of their project, youngest first.
+    pub ages: Vec<AgeRange>,
+}
+
+impl<'a> Stats<'a> {
+    pub fn new(dirs: &'a [FoundDir]) -> Self {
+        let mut readable: Vec<&FoundDir> = dirs.iter().filter(|d| d.error.is_none()).collect();
+        let mut total = Totals::default();
+        let mut per_kind: BTreeMap<&str, Totals> = BTreeMap::new();
+        let mut ages: Vec<_> = AGE_BUCKETS
+            .iter()
+            .map(|(_, label)| AgeRange {
+                label,
+                totals: Totals::default(),
+            })
+            .collect();
+
+        for dir in &readable {
+            total.add(dir.size);
+            per_kind.entry(&dir.target_kind).or_default().add(dir.size);
+            let bucket = AGE_BUCKETS
+                .iter()
+                .position(|(days, _)| dir.modified / 86400 < *days)
+                .unwrap_or(AGE_BUCKETS.len() - 1);
+            ages[bucket].totals.add(dir.size);
+        }
+
+        readable.sort_by_key(|dir| std::cmp::Reverse(dir.size));
+        let largest = readable
+            .into_iter()
+            .take(LARGEST_COUNT)
+            .map(ModuleRecord::from)
+            .collect();
+
+        Self {
+            total,
+            per_kind,
+            largest,
+            ages,
+        }
+    }
+
+    /// Every entry as a flat row, the total first.
+    pub fn rows(&self) -> Vec<StatsRow<'_>> {
+        let row = |section, label, totals: &Totals| StatsRow {
+            section,
+            label,
+            count: totals.count,
+            bytes: totals.size,
+        };
+        let mut rows = vec![row("total", Cow::Borrowed(""), &self.total)];
+        for (kind, totals) in &self.per_kind {
+            rows.push(row("target", Cow::Borrowed(*kind), totals));
+        }
+        for record in &self.largest {
+            rows.push(StatsRow {
+                section: "largest",
+                label: record.path.to_string_lossy(),
+                count: 1,
+                bytes: record.size,
+            });
+        }
+        for AgeRange { label, totals } in &self.ages {
+            rows.push(row("age", Cow::Borrowed(*label), totals));
+        }
+        rows
+    }
+
+    /// The stats in `format`: the whole object as JSON, or [rows](Self::rows) otherwise.
+    pub fn render(&self, format: OutputFormat, unit: SizeUnit) -> serde_json::Result<String> {
+        match format {
+            OutputFormat::Json => serde_json::to_string_pretty(self).map(|json| json + "\n"),
+            _ => format.render_rows(&self.rows(), "section,label,count,bytes,size", |row| {
+                vec![
+                    row.section.to_string(),
+                    row.label.to_string(),
+                    row.count.to_string(),
+                    row.bytes.to_string(),
+                    format_size(row.bytes, unit),
+                ]
+            }),
+        }
+    }
+
+    pub fn report(&self, unit: SizeUnit) -> String {
+        let size = |bytes| format_size(bytes, unit);
+
+        let mut report = String::new();
+        let _ = writeln!(report, "Directories: {}", self.total.count);
+        let _ = writeln!(report, "Total size: {}", size(self.total.size));
+        for (kind, totals) in &self.per_kind {
+            let _ = writeln!(report, "  {kind}: {} ({})", totals.count, size(totals.size));
+        }
+
+        if !self.largest.is_empty() {
+            let _ = writeln!(report, "\nLargest:");
+            for record in &self.largest {
+                let _ = writeln!(
+                    report,
+                    "  {:>12}  {}",
+                    size(record.size),
+                    record.path.display()
+                );
+            }
+        }
+
+        let _ = writeln!(report, "\nBy age:");
+        for AgeRange { label, totals } in &self.ages {
+            let _ = writeln!(
+                report,
+                "  {label:<17} {:>5}  {:>12}",
+                totals.count,
+                size(totals.size)
+            );
+        }
+        report
+    }
+}
+
+/// What `clean` did to the directories of one target.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CleanTotals {
+    pub deleted: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub reclaimed: u64,
+}
+
+/// The outcomes of a `clean` run, per target.
+#[derive(Debug, Default)]
+pub struct CleanSummary {
+    pub per_kind: BTreeMap<String, CleanTotals>,
+}
+
+impl CleanSummary {
+    pub fn add(&mut self, event: &CleanEvent) {
+        let (dir, update): (_, fn(&mut CleanTotals, u64)) = match event {
+            CleanEvent::Deleted(dir, _) => (dir, |t, size| {
+                t.deleted += 1;
+                t.reclaimed += size;
+            }),
+            CleanEvent::Failed(dir, _) => (dir, |t, _| t.failed += 1),
+            CleanEvent::Skipped(dir, _) => (dir, |t, _| t.skipped += 1),
+            CleanEvent::Warned(_) => return,
+        };
+        let totals = self.per_kind.entry(dir.target_kind.clone()).or_default();
+        update(totals, dir.size);
+    }
+
+    pub fn total(&self) -> CleanTotals {
+        self.per_kind
+            .values()
+            .fold(CleanTotals::default(), |total, t| CleanTotals {
+                deleted: total.deleted + t.deleted,
+                failed: total.failed + t.failed,
+                skipped: total.skipped + t.skipped,
+                reclaimed: total.reclaimed + t.reclaimed,
+            })
+    }
+
+    /// A table with a row per target and one for the whole run.
+    pub fn report(&self, unit: SizeUnit) -> String {
+        let mut report = format!(
+            "{:<16} {:>8} {:>7} {:>8} {:>12}\n",
+            "TARGET", "DELETED", "FAILED", "SKIPPED", "RECLAIMED"
+        );
+        let total = self.total();
+        let rows = self.per_kind.iter().map(|(kind, t)| (kind.as_str(), t));
+        for (kind, t) in rows.chain([("TOTAL", &total)]) {
+            let _ = writeln!(
+                report,
+                "{kind:<16} {:>8} {:>7} {:>8} {:>12}",
+                t.deleted,
+                t.failed,
+                t.skipped,
+                format_size(t.reclaimed, unit)
+            );
+        }
+        report
+    }
+}
+
+/// Lists the directories `--delete-all` would remove, followed by a summary line.
+pub fn dry_run_report(modules: &[FoundDir], unit: SizeUnit) -> String {
+    let mut report = String::new();
+    let mut total = 0;
+    let mut count = 0;
+
+    for module in modules.iter().filter(|m| m.error.is_none()) {
+        let _ = writeln!(
+            report,
+            "{:>12}  {}",
+            format_size(module.size, unit),
+            module.path.display()
+        );
+        total += module.size;
+        count += 1;
+    }
+
+    let _ = writeln!(
+        report,
+        "Would delete {count} directories, reclaiming {}",
+        format_size(total, unit)
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shorten_path_keeps_project_and_target() {
+        let home = Path::new("/home/me");
+        let path = Path::new("/home/me/work/clients/acme/myapp/node_modules");
+        assert_eq!(
+            shorten_path(path, Some(home), 80),
+            "~/work/clients/acme/myapp/node_modules"
+        );
+        assert_eq!(
+            shorten_path(path, Some(home), 27),
+            "~/w/c/a/myapp/node_modules"
+        );
+        assert_eq!(
+            shorten_path(path, Some(home), 24),
+            "~/w/…/myapp/node_modules"
+        );
+        assert_eq!(shorten_path(path, None, 10), "…e_modules");
+        assert_eq!(
+            shorten_path(Path::new("/srv/.cache/x/target"), None, 16),
+            "/s/.c/x/target"
+        );
+    }
+
+    #[test]
+    fn test_stats_groups_by_age_and_size() {
+        let dir = |path: &str, size, days: i64| FoundDir {
+            size,
+            modified: days * 86400,
+            ..FoundDir::pending(PathBuf::from(path))
+        };
+        let dirs = [
+            dir("/a/node_modules", 10, 1),
+            dir("/b/target", 30, 40),
+            dir("/c/node_modules", 20, 400),
+        ];
+
+        let stats = Stats::new(&dirs);
+        assert_eq!((stats.total.count, stats.total.size), (3, 60));
+        assert_eq!(stats.per_kind["node_modules"].size, 30);
+        let largest: Vec<_> = stats.largest.iter().map(|r| r.size).collect();
+        assert_eq!(largest, [30, 20, 10]);
+        let ages: Vec<_> = stats.ages.iter().map(|a| a.totals.count).collect();
+        assert_eq!(ages, [1, 0, 1, 0, 0, 1]);
+
+        let csv = stats.render(OutputFormat::Csv, SizeUnit::B).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("section,label,count,bytes,size"));
+        assert_eq!(lines.next(), Some("total,,3,60,60B"));
+        assert!(csv.contains("largest,/b/target,1,30,30B\n"));
+        assert!(csv.contains("age,< 1 week,1,10,10B\n"));
+        let ndjson = stats.render(OutputFormat::Ndjson, SizeUnit::B).unwrap();
+        assert_eq!(ndjson.lines().count(), stats.rows().len());
+    }
+
+    #[test]
+    fn test_clean_summary_counts_outcomes_per_target() {
+        let dir = |path: &str, size| FoundDir {
+            size,
+            ..FoundDir::pending(PathBuf::from(path))
+        };
+        let (a, b, c) = (
+            dir("/a/node_modules", 10),
+            dir("/b/node_modules", 20),
+            dir("/c/target", 30),
+        );
+
+        let mut summary = CleanSummary::default();
+        summary.add(&CleanEvent::Warned(&a));
+        summary.add(&CleanEvent::Deleted(&a, Duration::from_secs(1)));
+        summary.add(&CleanEvent::Failed(&b, String::from("denied").into()));
+        summary.add(&CleanEvent::Skipped(&c, String::from("protected")));
+
+        let total = summary.total();
+        assert_eq!(
+            (total.deleted, total.failed, total.skipped, total.reclaimed),
+            (1, 1, 1, 10)
+        );
+        assert_eq!(summary.per_kind["node_modules"].failed, 1);
+        let report = summary.report(SizeUnit::B);
+        assert_eq!(report.lines().count(), 4);
+        assert!(report.lines().last().unwrap().starts_with("TOTAL"));
+    }
+
+    #[test]
+    fn test_format_size_scales_per_value() {
+        assert_eq!(format_size(512, SizeUnit::Auto), "512B");
+        assert_eq!(format_size(1536, SizeUnit::Auto), "1.50KB");
+        assert_eq!(format_size(3 << 30, SizeUnit::Auto), "3.00GB");
+        assert_eq!(format_size(5 << 40, SizeUnit::Auto), "5.00TB");
+        assert_eq!(format_size(3 << 30, SizeUnit::Mb), "3072.00MB");
+        assert_eq!(format_size(1 << 20, SizeUnit::Gb), "0.00GB");
+    }
+
+    #[test]
+    fn test_format_rate_per_second() {
+        let rate =
+            |bytes, millis| format_rate(bytes, Duration::from_millis(millis), SizeUnit::Auto);
+        assert_eq!(rate(90 << 20, 2000), "45.00MB/s");
+        assert_eq!(
+            rate(512, 0),
+            "500.00KB/s",
+            "instant removals count as a millisecond"
+        );
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        let module = FoundDir {
+            size: 3 << 20,
+            modified: 2 * 86400,
+            ..FoundDir::pending(PathBuf::from("/work/a, \"b\"/node_modules"))
+        };
+        assert_eq!(
+            to_csv(&[module], SizeUnit::Auto),
+            "path,bytes,size,age_days,dangerous,target_kind\n\
+             \"/work/a, \"\"b\"\"/node_modules\",3145728,3.00MB,2,false,node_modules\n"
+        );
+    }
+
+    #[test]
+    fn test_record_dates_modification() {
+        let module = FoundDir {
+            modified: 2 * 86400,
+            ..FoundDir::pending(PathBuf::from("/work/app/node_modules"))
+        };
+        let record = ModuleRecord::from(&module);
+        let expected = (Local::now() - chrono::Duration::days(2)).date_naive();
+        let dated = DateTime::parse_from_rfc3339(&record.modified_at).unwrap();
+        assert_eq!(dated.with_timezone(&Local).date_naive(), expected);
+        assert_eq!(record.modified, 2 * 86400);
+    }
+}
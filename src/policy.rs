@@ -0,0 +1,189 @@
+//! Rules protecting paths from deletion, given with `--protect` or in the config file.
+
+use std::{fmt, path::Path};
+
+use globset::GlobMatcher;
+use serde::{Deserialize, Serialize};
+
+use crate::fs::{exclude_glob, is_system, known_cache};
+
+/// How strongly a path is protected, from least to most strict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "cli",
+    derive(clap::ValueEnum),
+    value(rename_all = "kebab-case")
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProtectLevel {
+    /// Delete, but show a warning.
+    Warn,
+    /// Only delete one at a time, after confirming twice. Bulk deletions skip it.
+    DoubleConfirm,
+    /// Never delete.
+    Refuse,
+}
+
+impl ProtectLevel {
+    const ALL: [ProtectLevel; 3] = [
+        ProtectLevel::Warn,
+        ProtectLevel::DoubleConfirm,
+        ProtectLevel::Refuse,
+    ];
+
+    /// Whether bulk deletions such as `--delete-all` and `clean` must leave the path alone.
+    pub fn blocks_bulk_delete(self) -> bool {
+        self >= ProtectLevel::DoubleConfirm
+    }
+}
+
+impl fmt::Display for ProtectLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProtectLevel::Warn => "warn",
+            ProtectLevel::DoubleConfirm => "double-confirm",
+            ProtectLevel::Refuse => "refuse",
+        })
+    }
+}
+
+/// A glob, as understood by [`exclude_glob`], and the protection of the paths below it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectRule {
+    pub pattern: String,
+    #[serde(default = "default_level")]
+    pub level: ProtectLevel,
+}
+
+fn default_level() -> ProtectLevel {
+    ProtectLevel::Refuse
+}
+
+/// Parses a `--protect` value: a glob, optionally prefixed with its level as in
+/// `warn:~/work/*`. The level defaults to `refuse`.
+pub fn parse_protect(input: &str) -> Result<ProtectRule, String> {
+    let (level, pattern) = input
+        .split_once(':')
+        .and_then(|(level, pattern)| {
+            ProtectLevel::ALL
+                .into_iter()
+                .find(|known| known.to_string().eq_ignore_ascii_case(level))
+                .map(|level| (level, pattern))
+        })
+        .unwrap_or((default_level(), input));
+
+    exclude_glob(pattern).map_err(|e| e.to_string())?;
+    Ok(ProtectRule {
+        pattern: pattern.to_string(),
+        level,
+    })
+}
+
+/// The compiled protection rules of a session.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    rules: Vec<(GlobMatcher, ProtectLevel)>,
+    /// Let [system](crate::fs::is_system) directories be deleted. They are refused otherwise.
+    pub allow_global: bool,
+}
+
+impl Policy {
+    /// Compiles `rules`, leaving out any whose pattern isn't a valid glob.
+    pub fn new(rules: &[ProtectRule], allow_global: bool) -> Self {
+        Self {
+            allow_global,
+            rules: rules
+                .iter()
+                .filter_map(|rule| {
+                    let glob = exclude_glob(&rule.pattern).ok()?;
+                    Some((glob.compile_matcher(), rule.level))
+                })
+                .collect(),
+        }
+    }
+
+    /// The strictest level among the rules matching `path` or one of its parents, and the
+    /// refusal of global directories.
+    pub fn level(&self, path: &Path) -> Option<ProtectLevel> {
+        if self.refuses_global(path) {
+            return Some(ProtectLevel::Refuse);
+        }
+
+        self.rules
+            .iter()
+            .filter(|(matcher, _)| path.ancestors().any(|p| matcher.is_match(p)))
+            .map(|(_, level)| *level)
+            .max()
+    }
+
+    /// Whether `path` is refused because it belongs to globally installed tooling. Known caches
+    /// listed by `--caches` aren't.
+    pub fn refuses_global(&self, path: &Path) -> bool {
+        !self.allow_global && is_system(path) && known_cache(path).is_none()
+    }
+
+    /// Why deleting `path` isn't allowed as is, for messages.
+    pub fn reason(&self, path: &Path) -> Option<String> {
+        if self.refuses_global(path) {
+            return Some(String::from(
+                "global package directory, pass --allow-global to delete it",
+            ));
+        }
+        self.level(path).map(|level| format!("protected ({level})"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_protect_levels() {
+        assert_eq!(
+            parse_protect("/srv/production"),
+            Ok(ProtectRule {
+                pattern: String::from("/srv/production"),
+                level: ProtectLevel::Refuse,
+            })
+        );
+        assert_eq!(
+            parse_protect("double-confirm:**/shared/**").map(|rule| rule.level),
+            Ok(ProtectLevel::DoubleConfirm)
+        );
+        assert_eq!(
+            parse_protect("C:/work").map(|rule| rule.pattern),
+            Ok(String::from("C:/work"))
+        );
+        assert!(parse_protect("warn:[unclosed").is_err());
+    }
+
+    #[test]
+    fn test_policy_uses_strictest_matching_rule() {
+        let policy = Policy::new(
+            &[
+                parse_protect("warn:/work").unwrap(),
+                parse_protect("refuse:/work/production").unwrap(),
+            ],
+            false,
+        );
+        assert_eq!(
+            policy.level(Path::new("/work/app/node_modules")),
+            Some(ProtectLevel::Warn)
+        );
+        assert_eq!(
+            policy.level(Path::new("/work/production/api/node_modules")),
+            Some(ProtectLevel::Refuse)
+        );
+        assert_eq!(policy.level(Path::new("/home/app/node_modules")), None);
+    }
+
+    #[test]
+    fn test_global_directories_need_allow_global() {
+        let path = Path::new("/home/user/.cargo/registry/src/index/serde-1.0.0/target");
+        assert_eq!(
+            Policy::new(&[], false).level(path),
+            Some(ProtectLevel::Refuse)
+        );
+        assert_eq!(Policy::new(&[], true).level(path), None);
+    }
+}
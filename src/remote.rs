@@ -0,0 +1,265 @@
+//! Scanning and deleting on another machine over SSH, for `--ssh user@host:/path`.
+//!
+//! Nothing needs to be installed there: the walk is `find` piped into `du`, run by the remote
+//! shell, and deletions are `rm -rf`. `ssh` runs in batch mode, so the host must accept a key or
+//! an agent rather than prompt for a password. Manifests are checked there too, and the exclude
+//! and match filters are applied to the paths printed, but ignore files aren't read.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, SystemTime},
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    process::Command,
+    sync::mpsc::{self, UnboundedReceiver},
+};
+
+use crate::{
+    fs::{checked_manifests, PathFilter},
+    scanner::{CancelToken, FoundDir, ScanEvent, ScanOptions, SkippedDir},
+};
+
+/// A directory on another machine: the `ssh` destination and the path to search there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteRoot {
+    /// `host` or `user@host`, as given to `ssh`.
+    pub destination: String,
+    pub path: String,
+}
+
+/// Parses `user@host:/path`. The path defaults to the remote home directory.
+pub fn parse_remote(input: &str) -> Result<RemoteRoot, String> {
+    let (destination, path) = input.split_once(':').unwrap_or((input, ""));
+    if destination.is_empty() || destination.starts_with('-') {
+        return Err(format!(
+            "invalid remote '{input}', expected user@host:/path"
+        ));
+    }
+    Ok(RemoteRoot {
+        destination: destination.to_string(),
+        path: if path.is_empty() { "." } else { path }.to_string(),
+    })
+}
+
+impl std::fmt::Display for RemoteRoot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.destination, self.path)
+    }
+}
+
+impl RemoteRoot {
+    /// The shell script printing `<KiB>\t<parent mtime>\t<path>` for every target directory
+    /// below the root that has the manifest [`ScanOptions::require_manifest`] asks for. Either
+    /// number is empty when it couldn't be read.
+    pub fn scan_script(&self, options: &ScanOptions) -> String {
+        let names = options
+            .targets
+            .iter()
+            .map(|target| format!("-name {}", shell_quote(target)))
+            .collect::<Vec<_>>()
+            .join(" -o ");
+        let depth = options
+            .max_depth
+            .map_or(String::new(), |depth| format!(" -maxdepth {depth}"));
+        let prune = if options.include_nested {
+            ""
+        } else {
+            " -prune"
+        };
+        let hidden = if options.exclude_hidden {
+            " -o -name '.*' -prune"
+        } else {
+            ""
+        };
+
+        let manifests: Vec<String> = options
+            .targets
+            .iter()
+            .filter_map(|target| {
+                let manifests = checked_manifests(target, options.require_manifest);
+                let tests = manifests
+                    .iter()
+                    .map(|manifest| format!("[ -f \"$p\"/{} ]", shell_quote(manifest)))
+                    .collect::<Vec<_>>();
+                (!tests.is_empty())
+                    .then(|| format!("{}) {} ;;", shell_quote(target), tests.join(" || ")))
+            })
+            .collect();
+        let manifest = if manifests.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "case \"${{d##*/}}\" in {} esac || continue; ",
+                manifests.join(" ")
+            )
+        };
+
+        format!(
+            "find {}{depth} -mindepth 1 -type d \\( \\( {names} \\){prune} -print{hidden} \\) 2>/dev/null \
+             | while IFS= read -r d; do \
+             p=$(dirname -- \"$d\"); \
+             {manifest}\
+             s=$(du -sk -- \"$d\" 2>/dev/null | cut -f1); \
+             m=$(stat -c %Y -- \"$p\" 2>/dev/null || stat -f %m -- \"$p\" 2>/dev/null); \
+             printf '%s\\t%s\\t%s\\n' \"$s\" \"$m\" \"$d\"; \
+             done",
+            shell_quote(&self.path),
+        )
+    }
+}
+
+/// Quotes `arg` for a POSIX shell.
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Reads one line printed by [`RemoteRoot::scan_script`].
+pub fn parse_line(line: &str) -> Option<FoundDir> {
+    let mut fields = line.splitn(3, '\t');
+    let (size, modified, path) = (fields.next()?, fields.next()?, fields.next()?);
+    if path.is_empty() {
+        return None;
+    }
+
+    let modified = modified.parse().map_or(SystemTime::now(), |secs| {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    });
+    let mut dir = FoundDir::with_size(PathBuf::from(path), 0, modified);
+    match size.parse::<u64>() {
+        Ok(kib) => dir.size = kib * 1024,
+        Err(_) => dir.error = Some(String::from("could not read the size")),
+    }
+    Some(dir)
+}
+
+/// Starts scanning `root` in the background. Directories arrive on the returned receiver as the
+/// remote walk prints them, and the receiver closes once `ssh` exits or the scan is cancelled.
+/// If `ssh` fails, the root is reported as skipped with its error.
+pub fn spawn_scan(
+    root: &RemoteRoot,
+    options: &ScanOptions,
+    cancel: CancelToken,
+) -> std::io::Result<UnboundedReceiver<ScanEvent>> {
+    let mut child = ssh(&root.destination, &root.scan_script(options))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let (tx, rx) = mpsc::unbounded_channel();
+    let (root, options) = (root.clone(), options.clone());
+    let filter = PathFilter::new(&options);
+
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if cancel.is_cancelled() {
+                return;
+            }
+            let Some(dir) = parse_line(&line) else {
+                continue;
+            };
+            if filter.keeps(&dir.path, Path::new(&root.path))
+                && dir.matches(&options)
+                && (options.include_system || !dir.is_system)
+            {
+                let _ = tx.send(ScanEvent::Found(dir));
+            }
+        }
+
+        let mut errors = String::new();
+        let _ = stderr.read_to_string(&mut errors).await;
+        match child.wait().await {
+            Ok(status) if status.success() => {}
+            result => {
+                let reason = match errors.trim() {
+                    "" => result.map_or_else(|e| e.to_string(), |s| format!("ssh exited with {s}")),
+                    errors => errors.to_string(),
+                };
+                let _ = tx.send(ScanEvent::Skipped(SkippedDir {
+                    path: PathBuf::from(root.to_string()),
+                    reason,
+                }));
+            }
+        }
+    });
+    Ok(rx)
+}
+
+/// Removes `path` on `destination` for good.
+pub async fn delete(destination: &str, path: &Path) -> Result<(), String> {
+    let script = format!("rm -rf -- {}", shell_quote(&path.to_string_lossy()));
+    let output = ssh(destination, &script)
+        .output()
+        .await
+        .map_err(|e| format!("could not run ssh: {e}"))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+fn ssh(destination: &str, script: &str) -> Command {
+    let mut command = Command::new("ssh");
+    command
+        .args(["-o", "BatchMode=yes", "--", destination, script])
+        .stdin(Stdio::null());
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::ManifestCheck;
+
+    #[test]
+    fn test_parse_remote_splits_destination() {
+        assert_eq!(
+            parse_remote("me@build:/srv/projects"),
+            Ok(RemoteRoot {
+                destination: String::from("me@build"),
+                path: String::from("/srv/projects"),
+            })
+        );
+        assert_eq!(parse_remote("build").unwrap().path, ".");
+        assert!(parse_remote(":/srv").is_err());
+        assert!(parse_remote("-oProxyCommand=x:/srv").is_err());
+    }
+
+    #[test]
+    fn test_scan_script_output_is_parsed() {
+        let root = parse_remote("build:/srv/it's here").unwrap();
+        let options = ScanOptions {
+            targets: vec![String::from("node_modules"), String::from("target")],
+            exclude_hidden: true,
+            max_depth: Some(4),
+            ..ScanOptions::default()
+        };
+        let script = root.scan_script(&options);
+        assert!(script.starts_with("find '/srv/it'\\''s here' -maxdepth 4 -mindepth 1"));
+        assert!(script.contains(
+            "\\( -name 'node_modules' -o -name 'target' \\) -prune -print -o -name '.*' -prune"
+        ));
+        assert!(!script.contains("case"));
+
+        let script = root.scan_script(&ScanOptions {
+            require_manifest: ManifestCheck::Generic,
+            ..options
+        });
+        assert!(script.contains(
+            "case \"${d##*/}\" in 'target') [ -f \"$p\"/'Cargo.toml' ] ;; esac || continue;"
+        ));
+
+        let dir = parse_line("2048\t0\t/srv/app/node_modules").unwrap();
+        assert_eq!(dir.size, 2 << 20);
+        assert_eq!(dir.target_kind, "node_modules");
+        assert!(dir.modified > 0);
+        assert!(parse_line("\t\t/srv/app/target").unwrap().error.is_some());
+        assert!(parse_line("garbage").is_none());
+    }
+}
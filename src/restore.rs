@@ -0,0 +1,233 @@
+//! Restore manifests: the projects whose dependencies or build output a bulk deletion removed,
+//! and the commands that bring them back, for `rskill reinstall`.
+//!
+//! A manifest holds one JSON entry per line, appended before each directory is deleted, so an
+//! interrupted run still leaves a usable manifest.
+
+use std::{
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fs::{required_manifests, LOCKFILES},
+    history::state_dir,
+    scanner::FoundDir,
+};
+
+/// One deleted directory and the project files found next to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoreEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    /// The target name the directory matched, e.g. `node_modules`.
+    pub target_kind: String,
+    /// Project manifests and lockfiles next to the directory, e.g. `package.json`.
+    pub manifests: Vec<String>,
+}
+
+impl RestoreEntry {
+    pub fn new(dir: &FoundDir) -> Self {
+        let project = dir.path.parent();
+        let manifests = required_manifests(&dir.target_kind)
+            .iter()
+            .chain(LOCKFILES)
+            .chain(&["gradlew"])
+            .filter(|name| project.is_some_and(|project| project.join(name).is_file()))
+            .map(|name| name.to_string())
+            .collect();
+
+        Self {
+            path: dir.path.clone(),
+            size: dir.size,
+            target_kind: dir.target_kind.clone(),
+            manifests,
+        }
+    }
+
+    fn has(&self, manifest: &str) -> bool {
+        self.manifests.iter().any(|m| m == manifest)
+    }
+
+    /// The commands that recreate the directory, run in its project. Empty when the manifests
+    /// don't say how, e.g. for `__pycache__`, which comes back on its own.
+    pub fn install_commands(&self) -> Vec<Vec<String>> {
+        let command = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect();
+        match self.target_kind.as_str() {
+            "node_modules" if self.has("package.json") => {
+                vec![command(if self.has("pnpm-lock.yaml") {
+                    &["pnpm", "install"]
+                } else if self.has("yarn.lock") {
+                    &["yarn", "install"]
+                } else if self.has("bun.lockb") {
+                    &["bun", "install"]
+                } else if self.has("package-lock.json") {
+                    &["npm", "ci"]
+                } else {
+                    &["npm", "install"]
+                })]
+            }
+            "target" if self.has("Cargo.toml") => vec![command(&["cargo", "build"])],
+            ".venv" | "venv" => {
+                if self.has("uv.lock") {
+                    return vec![command(&["uv", "sync"])];
+                }
+                if self.has("poetry.lock") {
+                    return vec![command(&["poetry", "install"])];
+                }
+                let install = if self.has("requirements.txt") {
+                    ["install", "-r", "requirements.txt"]
+                } else if self.has("pyproject.toml") || self.has("setup.py") {
+                    ["install", "-e", "."]
+                } else {
+                    return Vec::new();
+                };
+                let pip = Path::new(&self.target_kind)
+                    .join(if cfg!(windows) { "Scripts" } else { "bin" })
+                    .join("pip");
+                vec![
+                    command(&["python3", "-m", "venv", &self.target_kind]),
+                    std::iter::once(pip.display().to_string())
+                        .chain(install.map(String::from))
+                        .collect(),
+                ]
+            }
+            "build" | ".gradle" if self.has("gradlew") => {
+                vec![command(&[
+                    if cfg!(windows) {
+                        "gradlew.bat"
+                    } else {
+                        "./gradlew"
+                    },
+                    "build",
+                ])]
+            }
+            "build" | ".gradle" if self.manifests.iter().any(|m| m.contains(".gradle")) => {
+                vec![command(&["gradle", "build"])]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// `restore/<time>.jsonl` in the [state directory](state_dir), named after when the run started.
+pub fn default_manifest_path() -> Option<PathBuf> {
+    let name = chrono::Local::now().format("%Y-%m-%dT%H-%M-%S");
+    state_dir().map(|dir| dir.join("restore").join(format!("{name}.jsonl")))
+}
+
+/// Appends `dir` to the manifest at `path`, creating it if needed.
+pub fn append(path: &Path, dir: &FoundDir) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(&RestoreEntry::new(dir))?)
+}
+
+/// Reads the entries of the manifest at `path`. Lines that can't be read are left out.
+pub fn load(path: &Path) -> io::Result<Vec<RestoreEntry>> {
+    let file = std::fs::File::open(path)?;
+    let mut entries = Vec::new();
+    for line in io::BufReader::new(file).lines() {
+        if let Ok(entry) = serde_json::from_str(&line?) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Runs `args` in `dir`, sharing the terminal, and returns why it failed if it did.
+pub fn run_command(args: &[String], dir: &Path) -> Result<(), String> {
+    let (program, rest) = args.split_first().ok_or("empty command")?;
+    let status = Command::new(program)
+        .args(rest)
+        .current_dir(dir)
+        .status()
+        .map_err(|e| format!("could not run {program}: {e}"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{program} exited with {status}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn entry(target_kind: &str, manifests: &[&str]) -> RestoreEntry {
+        RestoreEntry {
+            path: PathBuf::from("/work/app").join(target_kind),
+            size: 0,
+            target_kind: target_kind.to_string(),
+            manifests: manifests.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_install_commands_follow_lockfiles() {
+        let first = |entry: RestoreEntry| entry.install_commands().first().map(|c| c.join(" "));
+        assert_eq!(
+            first(entry("node_modules", &["package.json", "pnpm-lock.yaml"])).as_deref(),
+            Some("pnpm install")
+        );
+        assert_eq!(
+            first(entry(
+                "node_modules",
+                &["package.json", "package-lock.json"]
+            ))
+            .as_deref(),
+            Some("npm ci")
+        );
+        assert_eq!(first(entry("node_modules", &[])), None);
+        assert_eq!(
+            first(entry("target", &["Cargo.toml", "Cargo.lock"])).as_deref(),
+            Some("cargo build")
+        );
+        assert!(entry("__pycache__", &[]).install_commands().is_empty());
+
+        let venv = entry(".venv", &["requirements.txt"]).install_commands();
+        assert_eq!(venv.len(), 2);
+        assert_eq!(venv[0].join(" "), "python3 -m venv .venv");
+        assert!(venv[1]
+            .join(" ")
+            .ends_with("pip install -r requirements.txt"));
+    }
+
+    #[test]
+    fn test_append_records_manifests_found() {
+        let root = TempDir::new("restore");
+        std::fs::create_dir_all(root.join("app/node_modules")).unwrap();
+        std::fs::write(root.join("app/package.json"), "{}").unwrap();
+        std::fs::write(root.join("app/yarn.lock"), "").unwrap();
+
+        let manifest = root.join("restore.jsonl");
+        let dir = FoundDir {
+            size: 42,
+            ..FoundDir::pending(root.join("app/node_modules"))
+        };
+        append(&manifest, &dir).unwrap();
+        std::fs::write(
+            &manifest,
+            std::fs::read_to_string(&manifest).unwrap() + "garbage\n",
+        )
+        .unwrap();
+
+        let entries = load(&manifest).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].size, 42);
+        assert_eq!(entries[0].manifests, ["package.json", "yarn.lock"]);
+        assert_eq!(
+            entries[0].install_commands(),
+            [vec![String::from("yarn"), String::from("install")]]
+        );
+    }
+}
@@ -0,0 +1,655 @@
+//! The scanning engine behind the command line interface, usable on its own.
+//!
+//! ```no_run
+//! use rskill::scanner::{ScanOptions, Scanner};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let scanner = Scanner::new(ScanOptions::default());
+//! let (found, _skipped) = scanner.scan(std::path::Path::new(".")).await?;
+//! for dir in found {
+//!     println!("{} {}", dir.size, dir.path.display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Local};
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    fs::{
+        device_id, disk_id, distinct_roots, is_dangerous, is_listable_target, is_rotational,
+        is_system, known_cache, known_caches, measure, read_project_info, scan_directory, DirStats,
+        VisitedDirs,
+    },
+    throttle,
+};
+
+/// Threads walking a spinning disk when scanning several disks, since more only make its head
+/// seek back and forth.
+pub const SPINNING_DISK_THREADS: usize = 2;
+
+/// How recently a project must have been modified to count as active by default: a week.
+pub const DEFAULT_ACTIVE_WITHIN: i64 = 7 * 86400;
+
+/// What to look for and where not to look.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Directory names to find, e.g. `node_modules`.
+    pub targets: Vec<String>,
+    /// Skip hidden and other [dangerous](crate::fs::is_dangerous) directories while walking.
+    pub exclude_hidden: bool,
+    /// Skip paths matching one of these glob patterns, as understood by
+    /// [`exclude_glob`](crate::fs::exclude_glob). Invalid patterns are ignored.
+    pub exclude_paths: Vec<String>,
+    /// Skip paths matching one of these regular expressions. Invalid ones are ignored.
+    pub exclude_regex: Vec<String>,
+    /// Only list target directories whose path matches one of these regular expressions, when
+    /// there are any. Invalid ones are ignored.
+    pub match_regex: Vec<String>,
+    /// Maximum depth below the starting directory, where 1 means its immediate children.
+    pub max_depth: Option<usize>,
+    /// Skip directories ignored by `.gitignore`, `.ignore`, `.rskillignore` and global git
+    /// excludes.
+    pub respect_ignore_files: bool,
+    /// Keep directories that belong to globally installed tooling.
+    pub include_system: bool,
+    /// Which target directories are only kept next to their project manifest.
+    pub require_manifest: ManifestCheck,
+    /// Drop directories smaller than this many bytes. Directories whose size couldn't be read are
+    /// kept.
+    pub min_size: Option<u64>,
+    /// Drop directories whose project was modified less than this many seconds ago.
+    pub older_than: Option<i64>,
+    /// Projects modified less than this many seconds ago count as active.
+    pub active_within: i64,
+    /// Drop directories of [active](FoundDir::is_active) projects.
+    pub skip_active: bool,
+    /// Walk into symlinked directories. Each directory is still walked only once.
+    pub follow_symlinks: bool,
+    /// Never cross into another filesystem than that of the starting directory, like `du -x`.
+    pub same_filesystem: bool,
+    /// Pause between batches of directories walked, see [`throttle`](crate::throttle). The
+    /// threads of a [pool of its own](Scanner::with_threads) also run at low priority.
+    pub io_nice: bool,
+    /// Report the space allocated on disk rather than the apparent size. See
+    /// [`allocated_size`](crate::fs::allocated_size).
+    pub disk_usage: bool,
+    /// Also list the [package manager caches](crate::fs::known_caches) that exist.
+    pub caches: bool,
+    /// How much work measuring a single directory may take before its size is reported as
+    /// [partial](FoundDir::partial).
+    pub size_budget: SizeBudget,
+    /// Date directories by the last access to a file inside rather than by the last
+    /// modification of their project. Needs a filesystem mounted with access times.
+    pub use_atime: bool,
+    /// Also list target directories inside other ones, e.g. the `node_modules` of a package
+    /// vendored in a `node_modules`.
+    pub include_nested: bool,
+    /// The starting directories are projects, only checked for targets directly inside them
+    /// rather than walked.
+    pub project_roots: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            targets: vec![String::from("node_modules")],
+            exclude_hidden: false,
+            exclude_paths: Vec::new(),
+            exclude_regex: Vec::new(),
+            match_regex: Vec::new(),
+            max_depth: None,
+            respect_ignore_files: true,
+            include_system: false,
+            require_manifest: ManifestCheck::Off,
+            min_size: None,
+            older_than: None,
+            disk_usage: false,
+            active_within: DEFAULT_ACTIVE_WITHIN,
+            skip_active: false,
+            follow_symlinks: false,
+            same_filesystem: false,
+            io_nice: false,
+            caches: false,
+            size_budget: SizeBudget::default(),
+            use_atime: false,
+            include_nested: false,
+            project_roots: false,
+        }
+    }
+}
+
+/// Limits on measuring one directory, so that directories holding millions of files don't stall
+/// the scan. Unlimited by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SizeBudget {
+    pub time: Option<Duration>,
+    /// Files and directories counted.
+    pub entries: Option<u64>,
+}
+
+impl SizeBudget {
+    pub fn is_unlimited(&self) -> bool {
+        self.time.is_none() && self.entries.is_none()
+    }
+}
+
+/// Which target directories must sit next to the manifest of their project, e.g. `Cargo.toml`
+/// for `target`, to be kept. See [`required_manifests`](crate::fs::required_manifests).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ManifestCheck {
+    Off,
+    /// Only generic names such as `target` and `build`, as `--profile` does.
+    Generic,
+    /// Every target name with a known manifest, for `--require-manifest`.
+    All,
+}
+
+/// Name and version of the project a directory belongs to, read from its `package.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectInfo {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl fmt::Display for ProjectInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{}@{version}", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+fn seconds_since(time: SystemTime) -> i64 {
+    Local::now()
+        .signed_duration_since(DateTime::<Local>::from(time))
+        .num_seconds()
+}
+
+/// A target directory found by the scanner.
+#[derive(Debug, Clone)]
+pub struct FoundDir {
+    pub path: PathBuf,
+    /// Size in bytes: apparent, or allocated on disk under [`ScanOptions::disk_usage`].
+    pub size: u64,
+    /// Seconds since the parent project was last modified, or since a file inside was last read
+    /// under [`ScanOptions::use_atime`].
+    pub modified: i64,
+    pub is_dangerous: bool,
+    pub is_system: bool,
+    /// The project was modified recently, or its lockfile changed after the directory was
+    /// built, so it is probably still being worked on.
+    pub is_active: bool,
+    /// The target name this directory matched.
+    pub target_kind: String,
+    /// Why the size couldn't be read, if it couldn't.
+    pub error: Option<String>,
+    /// The project owning a `node_modules` directory, if its `package.json` names one.
+    pub project: Option<ProjectInfo>,
+    /// What deleting a [known cache](crate::fs::known_caches) costs, if it is one.
+    pub warning: Option<&'static str>,
+    /// Measuring ran out of [budget](ScanOptions::size_budget), so `size` is a lower bound.
+    pub partial: bool,
+    /// Files inside, when the directory was walked to measure it.
+    pub file_count: Option<u64>,
+    /// Seconds since the newest file inside was last modified, when it was walked.
+    pub newest_file: Option<i64>,
+    /// Packages pinned by the lockfile of a `node_modules` directory's project, if it has one.
+    /// Only counted for the detail pane of the interface, as lockfiles can be large.
+    pub locked_packages: Option<u64>,
+    /// The root of the monorepo workspace the directory's project belongs to, if any.
+    pub workspace: Option<PathBuf>,
+}
+
+/// How much work bringing a `node_modules` directory back would take, judged from the packages
+/// its lockfile pins and the size it grew to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReinstallCost {
+    /// Fewer than [`CHEAP_PACKAGES`] packages and [`CHEAP_SIZE`] bytes.
+    Cheap,
+    /// More than [`EXPENSIVE_PACKAGES`] packages or [`EXPENSIVE_SIZE`] bytes.
+    Expensive,
+}
+
+pub const CHEAP_PACKAGES: u64 = 100;
+pub const CHEAP_SIZE: u64 = 50 << 20;
+pub const EXPENSIVE_PACKAGES: u64 = 1000;
+pub const EXPENSIVE_SIZE: u64 = 500 << 20;
+
+impl fmt::Display for ReinstallCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ReinstallCost::Cheap => "cheap to reinstall",
+            ReinstallCost::Expensive => "expensive to reinstall",
+        })
+    }
+}
+
+impl FoundDir {
+    /// Whether the directory passes the size, age and activity filters of `options`. Directories
+    /// whose details couldn't be read always pass, so they can still be reported.
+    pub fn matches(&self, options: &ScanOptions) -> bool {
+        if options.skip_active && self.is_active {
+            return false;
+        }
+
+        self.error.is_some()
+            || (options
+                .min_size
+                .is_none_or(|min| self.partial || self.size >= min)
+                && options.older_than.is_none_or(|age| self.modified >= age))
+    }
+
+    #[inline]
+    pub fn new(path: PathBuf, details: Result<(DirStats, SystemTime), String>) -> Self {
+        match details {
+            Ok((stats, parent_modified)) => Self {
+                partial: stats.partial,
+                file_count: Some(stats.file_count),
+                newest_file: stats.newest_file.map(seconds_since),
+                ..Self::with_size(path, stats.size, parent_modified)
+            },
+            Err(e) => Self {
+                error: Some(e),
+                ..Self::with_size(path, 0, SystemTime::now())
+            },
+        }
+    }
+
+    /// How costly reinstalling the directory would be, for `node_modules` directories at
+    /// either end of the scale. `None` in between, and for other targets.
+    pub fn reinstall_cost(&self) -> Option<ReinstallCost> {
+        if self.target_kind != "node_modules" || self.error.is_some() {
+            return None;
+        }
+        let packages = self.locked_packages;
+        if packages.is_some_and(|n| n > EXPENSIVE_PACKAGES) || self.size > EXPENSIVE_SIZE {
+            Some(ReinstallCost::Expensive)
+        } else if packages.is_some_and(|n| n < CHEAP_PACKAGES) && self.size < CHEAP_SIZE {
+            Some(ReinstallCost::Cheap)
+        } else {
+            None
+        }
+    }
+
+    /// A directory whose size and parent modification time are already known.
+    pub fn with_size(path: PathBuf, size: u64, parent_modified: SystemTime) -> Self {
+        let modified = seconds_since(parent_modified);
+        let cache = known_cache(&path);
+        FoundDir {
+            path: path.clone(),
+            size,
+            modified,
+            is_dangerous: is_dangerous(&path),
+            is_system: cache.is_none() && is_system(&path),
+            is_active: false,
+            target_kind: match &cache {
+                Some(cache) => cache.name.to_string(),
+                None => path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            },
+            error: None,
+            project: read_project_info(&path),
+            locked_packages: None,
+            workspace: None,
+            warning: cache.map(|cache| cache.warning),
+            partial: false,
+            file_count: None,
+            newest_file: None,
+        }
+    }
+
+    /// A directory whose size and age haven't been read yet, both left at zero.
+    pub fn pending(path: PathBuf) -> Self {
+        Self::with_size(path, 0, SystemTime::now())
+    }
+}
+
+/// A directory the scanner couldn't descend into.
+#[derive(Debug, Clone)]
+pub struct SkippedDir {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug)]
+pub enum ScanEvent {
+    /// A target directory was found. Its details follow in a `Found` or `Dropped` event once
+    /// its size is known.
+    Matched(PathBuf),
+    Found(FoundDir),
+    /// A matched directory turned out not to pass the size or age filters.
+    Dropped(PathBuf),
+    Skipped(SkippedDir),
+}
+
+/// Stops a running scan. Walkers stop descending, and directories matched but not measured yet
+/// are reported with an error instead of their size.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A scanner and the directories it starts from, kept to scan them again or watch them.
+#[derive(Debug, Clone)]
+pub struct ScanPlan {
+    pub scanner: Scanner,
+    pub start_dirs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    options: Arc<ScanOptions>,
+    cancel: CancelToken,
+    /// Threads walking and measuring directories. Rayon's global pool when `None`.
+    pool: Option<Arc<ThreadPool>>,
+    /// Threads walking each disk, see [`with_per_disk_threads`](Self::with_per_disk_threads).
+    per_disk: Option<usize>,
+    /// The pool of each disk walked so far by device, shared by every scan of this scanner.
+    disk_pools: Arc<Mutex<HashMap<u64, Arc<ThreadPool>>>>,
+}
+
+impl Scanner {
+    pub fn new(options: ScanOptions) -> Self {
+        Self {
+            options: Arc::new(options),
+            cancel: CancelToken::default(),
+            pool: None,
+            per_disk: None,
+            disk_pools: Arc::default(),
+        }
+    }
+
+    /// Walks and measures directories on a pool of `threads` threads of its own, rather than
+    /// on the global one sized to the number of CPUs. Zero threads means one per CPU.
+    pub fn with_threads(mut self, threads: usize) -> Result<Self, ThreadPoolBuildError> {
+        self.pool = Some(Arc::new(self.build_pool(threads)?));
+        Ok(self)
+    }
+
+    /// Walks the starting directories of each disk on a pool of `threads` threads of its own,
+    /// so that a slow disk doesn't hold up the walkers of a fast one. With zero threads, each
+    /// disk gets as many as suit it: [`SPINNING_DISK_THREADS`] if it spins and one per CPU
+    /// otherwise, and only when the starting directories are on several disks.
+    pub fn with_per_disk_threads(mut self, threads: usize) -> Self {
+        self.per_disk = Some(threads);
+        self
+    }
+
+    fn build_pool(&self, threads: usize) -> Result<ThreadPool, ThreadPoolBuildError> {
+        let io_nice = self.options.io_nice;
+        ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("rskill-scan-{i}"))
+            .start_handler(move |_| {
+                if io_nice {
+                    throttle::lower_priority();
+                }
+            })
+            .build()
+    }
+
+    /// The pool walking each of `roots`, by their disk when [per-disk
+    /// threads](Self::with_per_disk_threads) apply, or the scanner's own pool otherwise.
+    pub(crate) fn root_pools(&self, roots: &[PathBuf]) -> Vec<Option<Arc<ThreadPool>>> {
+        let devices: Vec<Option<u64>> = roots
+            .iter()
+            .map(|root| device_id(root).map(disk_id))
+            .collect();
+        let several_disks = devices
+            .iter()
+            .flatten()
+            .any(|device| Some(device) != devices[0].as_ref());
+        let threads = match self.per_disk {
+            Some(threads) if threads > 0 || several_disks => threads,
+            _ => return vec![self.pool.clone(); roots.len()],
+        };
+
+        let mut pools = self.disk_pools.lock().unwrap_or_else(|e| e.into_inner());
+        devices
+            .into_iter()
+            .map(|device| {
+                let Some(device) = device else {
+                    return self.pool.clone();
+                };
+                if let Some(pool) = pools.get(&device) {
+                    return Some(Arc::clone(pool));
+                }
+                let threads = match threads {
+                    0 if is_rotational(device) == Some(true) => SPINNING_DISK_THREADS,
+                    threads => threads,
+                };
+                // Falls back to the shared pool rather than failing the scan.
+                let Ok(pool) = self.build_pool(threads) else {
+                    return self.pool.clone();
+                };
+                let pool = Arc::new(pool);
+                pools.insert(device, Arc::clone(&pool));
+                Some(pool)
+            })
+            .collect()
+    }
+
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// A token that stops every scan started by this scanner, including those still running.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// The same scanner with a cancel token of its own, so it can scan again after a scan was
+    /// cancelled.
+    pub fn restarted(&self) -> Self {
+        Self {
+            cancel: CancelToken::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Like [`restarted`](Self::restarted), looking for `targets` instead.
+    pub fn retargeted(&self, targets: Vec<String>) -> Self {
+        let options = ScanOptions {
+            targets,
+            ..(*self.options).clone()
+        };
+        Self {
+            options: Arc::new(options),
+            ..self.restarted()
+        }
+    }
+
+    /// Starts scanning `start_dir` in the background, one job per entry in it. The jobs share a
+    /// bounded, work-stealing thread pool, so wide directories don't start more walks than
+    /// there are threads.
+    ///
+    /// Results arrive on the returned receiver as they are found, and the receiver closes once
+    /// the scan is done or [cancelled](Self::cancel_token). The counter tracks how many
+    /// directories have been walked so far.
+    pub async fn spawn(
+        &self,
+        start_dir: &Path,
+    ) -> std::io::Result<(UnboundedReceiver<ScanEvent>, Arc<AtomicUsize>)> {
+        self.spawn_all(&[start_dir.to_path_buf()]).await
+    }
+
+    /// Like [`spawn`](Self::spawn), but for several starting directories sharing one receiver.
+    /// A starting directory that can't be read fails the call when it is the only one, and is
+    /// reported as skipped otherwise.
+    pub async fn spawn_all(
+        &self,
+        start_dirs: &[PathBuf],
+    ) -> std::io::Result<(UnboundedReceiver<ScanEvent>, Arc<AtomicUsize>)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let dirs_scanned = Arc::new(AtomicUsize::new(0));
+        let visited = VisitedDirs::default();
+        let reported = VisitedDirs::default();
+
+        if self.options.project_roots {
+            self.check_projects(start_dirs, &tx, &reported, &dirs_scanned);
+        } else {
+            let roots = distinct_roots(start_dirs);
+            let pools = self.root_pools(&roots);
+            for (start_dir, pool) in roots.iter().zip(pools) {
+                let mut entries = match tokio::fs::read_dir(start_dir).await {
+                    Ok(entries) => entries,
+                    Err(e) if start_dirs.len() > 1 => {
+                        let _ = tx.send(ScanEvent::Skipped(SkippedDir {
+                            path: start_dir.clone(),
+                            reason: e.kind().to_string(),
+                        }));
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+
+                let tx = tx.clone();
+                let options = Arc::clone(&self.options);
+                let counter = Arc::clone(&dirs_scanned);
+                let visited = visited.clone();
+                let reported = reported.clone();
+                let cancel = self.cancel.clone();
+                tokio::spawn(async move {
+                    while let Ok(Some(entry)) = entries.next_entry().await {
+                        if cancel.is_cancelled() {
+                            break;
+                        }
+                        let tx = tx.clone();
+                        let dirs_scanned = Arc::clone(&counter);
+                        let options = Arc::clone(&options);
+                        let visited = visited.clone();
+                        let reported = reported.clone();
+                        let cancel = cancel.clone();
+                        let path = entry.path();
+
+                        let walk = move || {
+                            scan_directory(
+                                path,
+                                options,
+                                visited,
+                                reported,
+                                cancel,
+                                tx,
+                                dirs_scanned,
+                            );
+                        };
+                        match &pool {
+                            Some(pool) => pool.spawn(walk),
+                            None => rayon::spawn(walk),
+                        }
+                    }
+                });
+            }
+        }
+
+        if self.options.caches {
+            for cache in known_caches() {
+                if cache.path.is_dir() && reported.first_visit(&cache.path) {
+                    self.measure_in_background(cache.path.clone(), tx.clone());
+                }
+            }
+        }
+
+        Ok((rx, dirs_scanned))
+    }
+
+    /// Looks for targets directly inside each of `projects`, for
+    /// [`ScanOptions::project_roots`].
+    fn check_projects(
+        &self,
+        projects: &[PathBuf],
+        tx: &UnboundedSender<ScanEvent>,
+        reported: &VisitedDirs,
+        dirs_scanned: &AtomicUsize,
+    ) {
+        for project in projects {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            if !project.is_dir() {
+                let _ = tx.send(ScanEvent::Skipped(SkippedDir {
+                    path: project.clone(),
+                    reason: String::from("not a directory"),
+                }));
+                continue;
+            }
+            dirs_scanned.fetch_add(1, Ordering::Relaxed);
+            for target in &self.options.targets {
+                let path = project.join(target);
+                if is_listable_target(&path, &self.options) && reported.first_visit(&path) {
+                    self.measure_in_background(path, tx.clone());
+                }
+            }
+        }
+    }
+
+    /// Reports the target directory at `path` as matched, and measures it on the scanner's
+    /// pool, found directly rather than by a walk.
+    fn measure_in_background(&self, path: PathBuf, tx: UnboundedSender<ScanEvent>) {
+        let _ = tx.send(ScanEvent::Matched(path.clone()));
+        let options = Arc::clone(&self.options);
+        let measure_dir = move || {
+            let dir = measure(path, &options);
+            let _ = tx.send(if dir.matches(&options) {
+                ScanEvent::Found(dir)
+            } else {
+                ScanEvent::Dropped(dir.path)
+            });
+        };
+        match &self.pool {
+            Some(pool) => pool.spawn(measure_dir),
+            None => rayon::spawn(measure_dir),
+        }
+    }
+
+    /// Scans `start_dir` to completion.
+    pub async fn scan(
+        &self,
+        start_dir: &Path,
+    ) -> std::io::Result<(Vec<FoundDir>, Vec<SkippedDir>)> {
+        self.scan_all(&[start_dir.to_path_buf()]).await
+    }
+
+    /// Scans every directory in `start_dirs` to completion.
+    pub async fn scan_all(
+        &self,
+        start_dirs: &[PathBuf],
+    ) -> std::io::Result<(Vec<FoundDir>, Vec<SkippedDir>)> {
+        let (mut rx, _) = self.spawn_all(start_dirs).await?;
+        let mut found = Vec::new();
+        let mut skipped = Vec::new();
+        while let Some(event) = rx.recv().await {
+            match event {
+                ScanEvent::Found(dir) => found.push(dir),
+                ScanEvent::Skipped(dir) => skipped.push(dir),
+                ScanEvent::Matched(_) | ScanEvent::Dropped(_) => {}
+            }
+        }
+        Ok((found, skipped))
+    }
+}
@@ -0,0 +1,73 @@
+//! Shell scripts removing found directories, written by `--emit-script` instead of deleting
+//! them, so that the deletions can be reviewed first or run on another machine.
+
+use std::{fmt::Write, io, path::Path};
+
+use crate::{
+    output::{format_size, SizeUnit},
+    scanner::FoundDir,
+};
+
+/// The name scripts are written to when `--emit-script` isn't given.
+pub const DEFAULT_PATH: &str = "rskill-delete.sh";
+
+/// `path` quoted for a POSIX shell.
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', r"'\''"))
+}
+
+/// A `sh` script running `rm -rf` on each of `dirs`, stopping at the first that fails.
+pub fn render(dirs: &[&FoundDir]) -> String {
+    let total: u64 = dirs.iter().map(|dir| dir.size).sum();
+    let mut script = format!(
+        "#!/bin/sh\n# Written by rskill on {}: {} directories, {} in all.\nset -e\n\n",
+        chrono::Local::now().format("%Y-%m-%d %H:%M"),
+        dirs.len(),
+        format_size(total, SizeUnit::Auto)
+    );
+    for dir in dirs {
+        let _ = writeln!(
+            script,
+            "rm -rf -- {}  # {}",
+            quote(&dir.path),
+            format_size(dir.size, SizeUnit::Auto)
+        );
+    }
+    script
+}
+
+/// Writes the [script](render) removing `dirs` to `path`, executable on Unix.
+pub fn write(path: &Path, dirs: &[&FoundDir]) -> io::Result<()> {
+    std::fs::write(path, render(dirs))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::SystemTime};
+
+    use super::*;
+
+    #[test]
+    fn test_render_quotes_paths() {
+        let dirs = [
+            FoundDir::with_size(
+                PathBuf::from("/work/app/node_modules"),
+                2048,
+                SystemTime::now(),
+            ),
+            FoundDir::with_size(PathBuf::from("/work/it's/target"), 1024, SystemTime::now()),
+        ];
+        let script = render(&dirs.iter().collect::<Vec<_>>());
+
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("2 directories, 3.00KB in all"));
+        assert!(script.contains("rm -rf -- '/work/app/node_modules'  # 2.00KB\n"));
+        assert!(script.contains(r"rm -rf -- '/work/it'\''s/target'  # 1.00KB"));
+    }
+}
@@ -0,0 +1,288 @@
+//! Colors of the interface, chosen with `--theme`, `NO_COLOR` and the `[colors]` table of the
+//! config file.
+
+use std::str::FromStr;
+
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+/// The built-in palettes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    Dark,
+    Light,
+    Solarized,
+    /// The terminal's own colors, changed only by the `[colors]` table of the config file.
+    Custom,
+}
+
+/// The color of each kind of text the interface shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub deleted: Color,
+    pub failed: Color,
+    /// Directories of globally installed tooling.
+    pub global: Color,
+    /// Global directories refused without `--allow-global`.
+    pub protected_global: Color,
+    /// Directories of projects worked on recently.
+    pub active: Color,
+    /// Directories in hidden or system locations.
+    pub dangerous: Color,
+    /// Rows still being measured or that couldn't be read, and hints.
+    pub muted: Color,
+    pub status: Color,
+    /// Confirmation prompts.
+    pub warning: Color,
+    /// The progress bar of bulk deletions.
+    pub gauge: Color,
+    /// Sizes below the medium [band](SizeBands).
+    pub small: Color,
+    pub medium: Color,
+    /// Sizes from the large band up.
+    pub large: Color,
+}
+
+/// The sizes from which the list shows a directory's size in the medium and large colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeBands {
+    pub medium: u64,
+    pub large: u64,
+}
+
+impl Default for SizeBands {
+    fn default() -> Self {
+        Self {
+            medium: 100 << 20,
+            large: 1 << 30,
+        }
+    }
+}
+
+impl SizeBands {
+    /// The color of `size` in `theme`.
+    pub fn color(&self, size: u64, theme: &Theme) -> Color {
+        if size >= self.large {
+            theme.large
+        } else if size >= self.medium {
+            theme.medium
+        } else {
+            theme.small
+        }
+    }
+}
+
+/// Colors set in the `[colors]` table of the config file, as names such as `red`, indexes such
+/// as `130` or hex codes such as `#b58900`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorOverrides {
+    pub deleted: Option<String>,
+    pub failed: Option<String>,
+    pub global: Option<String>,
+    pub protected_global: Option<String>,
+    pub active: Option<String>,
+    pub dangerous: Option<String>,
+    pub muted: Option<String>,
+    pub status: Option<String>,
+    pub warning: Option<String>,
+    pub gauge: Option<String>,
+    pub small: Option<String>,
+    pub medium: Option<String>,
+    pub large: Option<String>,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            deleted: Color::Red,
+            failed: Color::Red,
+            global: Color::Magenta,
+            protected_global: Color::Blue,
+            active: Color::Green,
+            dangerous: Color::Yellow,
+            muted: Color::DarkGray,
+            status: Color::Cyan,
+            warning: Color::Yellow,
+            gauge: Color::Red,
+            small: Color::Green,
+            medium: Color::Yellow,
+            large: Color::Red,
+        }
+    }
+
+    /// Darker shades that stay readable on a white background.
+    pub fn light() -> Self {
+        Self {
+            deleted: Color::Red,
+            failed: Color::Red,
+            global: Color::Indexed(90),
+            protected_global: Color::Indexed(19),
+            active: Color::Indexed(28),
+            dangerous: Color::Indexed(130),
+            muted: Color::Indexed(244),
+            status: Color::Indexed(25),
+            warning: Color::Indexed(130),
+            gauge: Color::Indexed(124),
+            small: Color::Indexed(28),
+            medium: Color::Indexed(130),
+            large: Color::Indexed(124),
+        }
+    }
+
+    /// The accents of the Solarized palette, readable on both of its backgrounds.
+    pub fn solarized() -> Self {
+        Self {
+            deleted: Color::Rgb(0xdc, 0x32, 0x2f),
+            failed: Color::Rgb(0xdc, 0x32, 0x2f),
+            global: Color::Rgb(0xd3, 0x36, 0x82),
+            protected_global: Color::Rgb(0x6c, 0x71, 0xc4),
+            active: Color::Rgb(0x85, 0x99, 0x00),
+            dangerous: Color::Rgb(0xb5, 0x89, 0x00),
+            muted: Color::Rgb(0x93, 0xa1, 0xa1),
+            status: Color::Rgb(0x2a, 0xa1, 0x98),
+            warning: Color::Rgb(0xcb, 0x4b, 0x16),
+            gauge: Color::Rgb(0xdc, 0x32, 0x2f),
+            small: Color::Rgb(0x85, 0x99, 0x00),
+            medium: Color::Rgb(0xb5, 0x89, 0x00),
+            large: Color::Rgb(0xdc, 0x32, 0x2f),
+        }
+    }
+
+    /// The terminal's default color everywhere.
+    pub fn plain() -> Self {
+        Self {
+            deleted: Color::Reset,
+            failed: Color::Reset,
+            global: Color::Reset,
+            protected_global: Color::Reset,
+            active: Color::Reset,
+            dangerous: Color::Reset,
+            muted: Color::Reset,
+            status: Color::Reset,
+            warning: Color::Reset,
+            gauge: Color::Reset,
+            small: Color::Reset,
+            medium: Color::Reset,
+            large: Color::Reset,
+        }
+    }
+
+    /// The theme of a session: `name` if given, or no colors at all when `NO_COLOR` is set, or
+    /// the dark theme. The overrides apply on top, except under `NO_COLOR`.
+    pub fn resolve(name: Option<ThemeName>, overrides: &ColorOverrides, no_color: bool) -> Self {
+        let mut theme = match name {
+            Some(ThemeName::Dark) => Self::dark(),
+            Some(ThemeName::Light) => Self::light(),
+            Some(ThemeName::Solarized) => Self::solarized(),
+            Some(ThemeName::Custom) => Self::plain(),
+            None if no_color => return Self::plain(),
+            None => Self::dark(),
+        };
+        theme.apply(overrides);
+        theme
+    }
+
+    fn apply(&mut self, overrides: &ColorOverrides) {
+        let slots = [
+            (&mut self.deleted, &overrides.deleted),
+            (&mut self.failed, &overrides.failed),
+            (&mut self.global, &overrides.global),
+            (&mut self.protected_global, &overrides.protected_global),
+            (&mut self.active, &overrides.active),
+            (&mut self.dangerous, &overrides.dangerous),
+            (&mut self.muted, &overrides.muted),
+            (&mut self.status, &overrides.status),
+            (&mut self.warning, &overrides.warning),
+            (&mut self.gauge, &overrides.gauge),
+            (&mut self.small, &overrides.small),
+            (&mut self.medium, &overrides.medium),
+            (&mut self.large, &overrides.large),
+        ];
+        for (color, name) in slots {
+            if let Some(parsed) = name.as_deref().and_then(|n| Color::from_str(n).ok()) {
+                *color = parsed;
+            }
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl ColorOverrides {
+    /// Checks that every color set can be parsed, naming the first one that can't.
+    pub fn validate(&self) -> Result<(), String> {
+        let names = [
+            ("deleted", &self.deleted),
+            ("failed", &self.failed),
+            ("global", &self.global),
+            ("protected_global", &self.protected_global),
+            ("active", &self.active),
+            ("dangerous", &self.dangerous),
+            ("muted", &self.muted),
+            ("status", &self.status),
+            ("warning", &self.warning),
+            ("gauge", &self.gauge),
+            ("small", &self.small),
+            ("medium", &self.medium),
+            ("large", &self.large),
+        ];
+        for (key, value) in names {
+            if let Some(value) = value {
+                Color::from_str(value)
+                    .map_err(|_| format!("colors.{key}: unknown color '{value}'"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the `NO_COLOR` convention asks for no colors: the variable is set and not empty.
+pub fn no_color_requested() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_honors_no_color_and_overrides() {
+        let overrides = ColorOverrides {
+            dangerous: Some(String::from("#b58900")),
+            status: Some(String::from("not-a-color")),
+            ..ColorOverrides::default()
+        };
+
+        let theme = Theme::resolve(None, &overrides, false);
+        assert_eq!(theme.dangerous, Color::Rgb(0xb5, 0x89, 0x00));
+        assert_eq!(theme.status, Theme::dark().status);
+        assert_eq!(Theme::resolve(None, &overrides, true), Theme::plain());
+
+        let theme = Theme::resolve(Some(ThemeName::Light), &overrides, true);
+        assert_eq!(theme.active, Theme::light().active);
+        assert_eq!(theme.dangerous, Color::Rgb(0xb5, 0x89, 0x00));
+
+        let theme = Theme::resolve(Some(ThemeName::Custom), &ColorOverrides::default(), false);
+        assert_eq!(theme, Theme::plain());
+
+        assert_eq!(
+            overrides.validate(),
+            Err(String::from("colors.status: unknown color 'not-a-color'"))
+        );
+    }
+
+    #[test]
+    fn test_size_bands_pick_the_color_of_each_band() {
+        let theme = Theme::dark();
+        let bands = SizeBands::default();
+        assert_eq!(bands.color(99 << 20, &theme), theme.small);
+        assert_eq!(bands.color(100 << 20, &theme), theme.medium);
+        assert_eq!(bands.color(1 << 30, &theme), theme.large);
+    }
+}
@@ -0,0 +1,85 @@
+//! Running at low priority for `--io-nice`, so that scanning or cleaning doesn't slow down the
+//! other programs on a laptop or a busy server.
+//!
+//! Threads doing the work lower their own CPU and disk priority (`nice 19` and `ionice -c2 -n7`
+//! on Linux), and pause briefly after every batch of filesystem operations.
+
+use std::time::Duration;
+
+/// How many filesystem operations a [`Throttle`] lets through between pauses.
+pub const BATCH: u64 = 256;
+
+/// How long a [`Throttle`] pauses after each batch.
+pub const PAUSE: Duration = Duration::from_millis(10);
+
+/// Counts filesystem operations and pauses the thread after every [batch](BATCH) of them.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    enabled: bool,
+    done: u64,
+}
+
+impl Throttle {
+    /// A throttle that never pauses unless `enabled`.
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, done: 0 }
+    }
+
+    /// Records one operation, pausing if it completes a batch.
+    pub fn tick(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        self.done += 1;
+        if self.done.is_multiple_of(BATCH) {
+            std::thread::sleep(PAUSE);
+        }
+    }
+}
+
+/// Lowers the CPU and, on Linux, the disk priority of the calling thread and of the threads it
+/// starts afterwards. Does nothing where the platform offers neither, and failures are ignored.
+pub fn lower_priority() {
+    // SAFETY: both calls only change scheduling attributes of the calling thread.
+    #[cfg(unix)]
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, 19);
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_BE: libc::c_int = 2;
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        const LOWEST_LEVEL: libc::c_int = 7;
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT | LOWEST_LEVEL,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn test_throttle_pauses_after_each_batch() {
+        let started = Instant::now();
+        let mut throttle = Throttle::new(true);
+        for _ in 0..BATCH * 2 {
+            throttle.tick();
+        }
+        assert!(started.elapsed() >= PAUSE * 2);
+
+        let started = Instant::now();
+        let mut throttle = Throttle::new(false);
+        for _ in 0..BATCH * 2 {
+            throttle.tick();
+        }
+        assert!(started.elapsed() < PAUSE);
+    }
+}
@@ -1,29 +1,140 @@
 use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicUsize, Ordering},
         Arc,
     },
     time::Duration,
 };
 
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+        MouseEventKind,
+    },
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Position, Rect},
     prelude::CrosstermBackend,
-    style::{Color, Style},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Row, Table},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{
+        Bar, BarChart, BarGroup, Block, Borders, Clear, Gauge, List, ListItem, ListState,
+        Paragraph, Row, Table, Tabs, Wrap,
+    },
     Terminal,
 };
 
-use crate::cli::{App, Args, NodeModule};
+use tokio::sync::mpsc::{error::TryRecvError, UnboundedReceiver};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    cache::{self, Tag},
+    cli::{
+        App, Args, DeleteBatch, DeleteState, ListRow, NodeModule, PickerAction, RootPicker,
+        SessionSummary,
+    },
+    deleter,
+    fs::{nesting_depth, open_in_editor, DirBreakdown, DiskSpace},
+    output::{format_size, modified_at, SizeUnit},
+    policy::ProtectLevel,
+    scanner::{CancelToken, FoundDir, ScanEvent, ScanPlan},
+    theme::Theme,
+    watch::TargetWatcher,
+};
+
+const STATUS_TIMEOUT: Duration = Duration::from_secs(3);
+/// Columns below which the detail pane is left out, so that the list stays readable.
+const MIN_DETAILS_WIDTH: u16 = 100;
+/// Drawn at the end of the selected row, deleting it when clicked.
+const DELETE_BUTTON: &str = "[delete]";
+/// Rows moved by one notch of the mouse wheel.
+const MOUSE_SCROLL_LINES: isize = 3;
+/// Keys listed in the help overlay, in the order shown.
+const KEYBINDINGS: &[(&str, &str)] = &[
+    ("↑/↓ PgUp/PgDn", "Move the selection"),
+    ("Home/g End/G", "Jump to the first or last directory"),
+    ("Space", "Delete the selected directory"),
+    ("r", "Retry a failed deletion"),
+    (
+        "S",
+        "Delete a directory denied for lack of permission as administrator",
+    ),
+    ("m", "Mark or unmark the selected directory"),
+    ("a / A", "Mark all / clear all marks"),
+    ("d / D", "Delete all marked directories"),
+    ("T", "Toggle trash and permanent deletion"),
+    ("u", "Restore the last trashed directory"),
+    ("Tab", "Show or hide the detail pane"),
+    ("b", "Show the largest directories as a bar chart"),
+    ("o", "Open the project in the file manager"),
+    ("y / Y", "Copy the selected / marked paths to the clipboard"),
+    ("W", "Write the marked directories as a deletion script"),
+    ("x", "Hide the directory, optionally for good"),
+    ("e", "Open the project in the editor"),
+    ("Enter", "List the subdirectories by size"),
+    ("p", "Print the project folder and exit"),
+    ("s", "Cycle the sort order"),
+    ("t", "Search for other directory names, scanning again"),
+    ("f", "Show full paths or shorten them to fit"),
+    (
+        "l",
+        "Show the dates directories were last modified instead of their age",
+    ),
+    (
+        "1 / 2 / 3",
+        "Tag the directory keep, later or delete, or untag it",
+    ),
+    ("n", "Write a note about the directory, kept with its tag"),
+    (
+        "w",
+        "Collapse the directories of each workspace or list them one by one",
+    ),
+    ("F", "Count every file of a directory with a partial size"),
+    ("←/→", "Switch between the tabs of each ecosystem"),
+    ("/", "Filter by fuzzy-matching paths"),
+    ("E", "Show directories skipped by the scan"),
+    (
+        "R",
+        "Scan again, keeping marks, the filter and the selection",
+    ),
+    ("Esc / Ctrl+C", "Stop the scan"),
+    ("?", "Show this help"),
+    ("q", "Quit"),
+];
+/// Number of subdirectories listed in the detail pane. Enter lists them all.
+const DETAIL_SUBDIRS: usize = 5;
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+fn apply_scan_event(app: &mut App, event: ScanEvent) {
+    match event {
+        ScanEvent::Matched(path) => app.push_pending(path),
+        ScanEvent::Found(dir) => app.update_module(dir),
+        ScanEvent::Dropped(path) => app.drop_module(&path),
+        ScanEvent::Skipped(skipped) => app.skipped.push(skipped),
+    }
+}
+
+/// Moves everything the scanner has sent so far into `app`, without blocking.
+fn drain_scan_events(app: &mut App, rx: &mut UnboundedReceiver<ScanEvent>) {
+    if !app.scanning {
+        return;
+    }
 
-fn from_bytes(bytes: u64, in_gb: bool) -> f32 {
-    let shift = if in_gb { 30 } else { 20 };
-    bytes as f32 / (1 << shift) as f32
+    loop {
+        match rx.try_recv() {
+            Ok(event) => apply_scan_event(app, event),
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => {
+                app.finish_scan();
+                break;
+            }
+        }
+    }
 }
 
 #[inline]
@@ -49,125 +160,1125 @@ fn get_age(seconds: i64) -> i64 {
     }
 }
 
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+/// Formats the disk usage as `used/total (percent)`, counting everything deleted so far as freed.
+fn format_disk_usage(disk: DiskSpace, freed: u64) -> String {
+    let used = disk
+        .total
+        .saturating_sub(disk.available)
+        .saturating_sub(freed);
+    let percent = used as f64 / disk.total as f64 * 100.0;
+    format!(
+        "Disk: {}/{} ({percent:.0}%)",
+        format_size(used, SizeUnit::Auto),
+        format_size(disk.total, SizeUnit::Auto)
+    )
+}
+
+/// Formats the free space, counting everything deleted so far as freed, followed by what it
+/// would be once the marked directories are deleted too.
+fn format_free_space(disk: DiskSpace, freed: u64, marked: u64) -> String {
+    let free = disk.available_after(freed);
+    let mut text = format!("Free: {}", format_size(free, SizeUnit::Auto));
+    if marked > 0 {
+        text += &format!(
+            " → {} (d)",
+            format_size(disk.available_after(freed + marked), SizeUnit::Auto)
+        );
+    }
+    text
+}
+
+fn format_timestamp(time: Option<std::time::SystemTime>) -> String {
+    time.map_or_else(
+        || String::from("unknown"),
+        |time| {
+            DateTime::<Local>::from(time)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        },
+    )
+}
+
+/// The size of `module`, as a lower bound when measuring it ran out of budget.
+fn size_label(module: &FoundDir, unit: SizeUnit) -> String {
+    if module.partial {
+        format!("≥ {} (partial)", format_size(module.size, unit))
+    } else {
+        format_size(module.size, unit)
+    }
+}
+
+/// Progress of a deletion batch: items and bytes done, throughput, time spent and left, and the
+/// directory being removed.
+fn batch_label(batch: &DeleteBatch) -> String {
+    let mut label = format!(
+        "{}/{} | {} reclaimed",
+        batch.completed,
+        batch.total,
+        format_size(batch.reclaimed, SizeUnit::Auto)
+    );
+    if let Some(rate) = batch.rate() {
+        label += &format!(" | {rate}");
+    }
+    label += &format!(" | {:.0?} elapsed", batch.started.elapsed());
+    if let Some(eta) = batch.eta() {
+        label += &format!(" | ETA {}", format_duration(eta.as_secs() as i64));
+    }
+    if let Some(current) = &batch.current {
+        label += &format!(" | {}", current.display());
+    }
+    label
+}
+
+/// Text of the detail pane for the highlighted module.
+fn details_text(app: &App, unit: SizeUnit) -> String {
+    let Some(index) = app.selected() else {
+        return String::from("Nothing selected");
+    };
+    if let Some((root, members)) = app.workspace_members(index) {
+        let mut text = format!(
+            "Workspace: {}\nPress w to list each directory\n\n",
+            root.display()
+        );
+        for member in members.iter().map(|&i| &app.modules[i]) {
+            let path = member.path.strip_prefix(root).unwrap_or(&member.path);
+            text += &format!("{} {}\n", size_label(member, unit), path.display());
+        }
+        return text;
+    }
+    let module = &app.modules[index];
+
+    let mut text = format!("{}\n", module.path.display());
+    if let Some(project) = &module.project {
+        text += &format!("Project: {project}\n");
+    }
+    if let Some(warning) = module.warning {
+        text += &format!("Warning: {warning}\n");
+    }
+    if let Some(note) = &module.note {
+        text += &format!("Note: {note}\n");
+    }
+    if let Some(packages) = module.locked_packages {
+        text += &format!("Lockfile: {packages} packages\n");
+    }
+    if let Some(cost) = module.reinstall_cost() {
+        text += &format!("Reinstall: {cost}\n");
+    }
+    text += &format!(
+        "Size: {} bytes ({})\n",
+        module.size,
+        size_label(module, unit)
+    );
+    if module.partial {
+        text += "Press F to count every file\n";
+    }
+
+    match app.details.get(&index) {
+        Some(Some(Ok(DirBreakdown {
+            file_count,
+            largest,
+            modified,
+            newest_file,
+        }))) => {
+            text += &format!("Files: {file_count}\n");
+            text += &format!("Modified: {}\n", format_timestamp(*modified));
+            text += &format!("Newest file: {}\n", format_timestamp(*newest_file));
+            if !largest.is_empty() {
+                text += "\nLargest subdirectories:\n";
+                for (name, size) in largest.iter().take(DETAIL_SUBDIRS) {
+                    text += &format!("  {:>10}  {name}\n", format_size(*size, unit));
+                }
+            }
+        }
+        Some(Some(Err(e))) => text += &format!("Could not read the directory: {e}\n"),
+        Some(None) => text += "Computing...\n",
+        None if module.is_removed() => {}
+        None => text += "Waiting...\n",
+    }
+    text
+}
+
+/// Rows of the drill-down list: each subdirectory of module `index` with its size and share.
+fn drill_down_items(app: &App, index: usize, unit: SizeUnit) -> Vec<ListItem<'static>> {
+    match app.details.get(&index) {
+        Some(Some(Ok(breakdown))) if breakdown.largest.is_empty() => {
+            vec![ListItem::new("No subdirectories")]
+        }
+        Some(Some(Ok(breakdown))) => {
+            let total: u64 = breakdown.largest.iter().map(|(_, size)| size).sum();
+            breakdown
+                .largest
+                .iter()
+                .map(|(name, size)| {
+                    let share = *size as f64 * 100.0 / total.max(1) as f64;
+                    ListItem::new(format!(
+                        "{:>10} {share:>5.1}%  {name}",
+                        format_size(*size, unit)
+                    ))
+                })
+                .collect()
+        }
+        Some(Some(Err(e))) => vec![ListItem::new(format!("Could not read the directory: {e}"))],
+        _ => vec![ListItem::new("Computing...")],
+    }
+}
+
+/// Text of the help overlay: the keymap, the options in effect and the current view.
+fn help_text(app: &App) -> String {
+    let mut text = String::from("Keys\n");
+    for (key, description) in KEYBINDINGS {
+        text += &format!("  {key:<14} {description}\n");
+    }
+
+    text += "\nOptions\n";
+    for flag in &app.flags {
+        text += &format!("  {flag}\n");
+    }
+
+    text += "\nView\n";
+    text += &format!(
+        "  Sort: {}\n",
+        app.sort
+            .as_ref()
+            .map_or("found", |sort| sort.label(app.sort_reversed))
+    );
+    text += &format!(
+        "  Filter: {}\n",
+        if app.filter.is_empty() {
+            "none"
+        } else {
+            &app.filter
+        }
+    );
+    text += &format!(
+        "  Deletion: {}\n",
+        if app.deleter.use_trash {
+            "trash"
+        } else {
+            "permanent"
+        }
+    );
+    text
+}
+
+/// What a list row shows of a module that can change during a session, to tell when its
+/// [cached](RowCache) text is stale.
+#[derive(PartialEq)]
+struct RowKey {
+    marked: bool,
+    tag: Option<Tag>,
+    state: DeleteState,
+    delete_time: Option<Duration>,
+    size: u64,
+    partial: bool,
+    sizing: bool,
+    modified: i64,
+    unreadable: bool,
+    protection: Option<ProtectLevel>,
+    is_active: bool,
+}
+
+impl RowKey {
+    fn of(module: &NodeModule) -> Self {
+        Self {
+            marked: module.marked,
+            tag: module.tag,
+            state: module.state.clone(),
+            delete_time: module.delete_time,
+            size: module.size,
+            partial: module.partial,
+            sizing: module.sizing,
+            modified: module.modified,
+            unreadable: module.error.is_some(),
+            protection: module.protection,
+            is_active: module.is_active,
+        }
+    }
+}
+
+/// The formatted rows of the list by index into `modules`, kept across frames so that only
+/// modules that changed are formatted again.
+#[derive(Default)]
+struct RowCache {
+    rows: HashMap<usize, (RowKey, ListItem<'static>)>,
+    /// The width the rows were formatted for, and whether with full paths and dates.
+    layout: (usize, bool, bool),
+}
+
+impl RowCache {
+    fn item(
+        &mut self,
+        app: &App,
+        index: usize,
+        show_target_kind: bool,
+        unit: SizeUnit,
+        frame: usize,
+        width: usize,
+    ) -> ListItem<'static> {
+        // Paths are shortened to the width, which changes when the terminal is resized.
+        let layout = (width, app.full_paths, app.absolute_dates);
+        if self.layout != layout {
+            self.rows.clear();
+            self.layout = layout;
+        }
+        // Its size and state add up those of its directories.
+        if let Some((root, members)) = app.workspace_members(index) {
+            return workspace_item(app, root, &members, unit, width);
+        }
+        let module = &app.modules[index];
+        // The spinner turns every frame.
+        if module.state == DeleteState::Deleting {
+            return module_item(app, module, show_target_kind, unit, frame, width);
+        }
+        let key = RowKey::of(module);
+        match self.rows.get(&index) {
+            Some((cached, item)) if *cached == key => item.clone(),
+            _ => {
+                let item = module_item(app, module, show_target_kind, unit, frame, width);
+                self.rows.insert(index, (key, item.clone()));
+                item
+            }
+        }
+    }
+}
+
+/// The row of the list showing `m`, with its path shortened to fit in `width` columns and
+/// indented by how deep it is nested in other targets.
+fn module_item(
+    app: &App,
+    m: &NodeModule,
+    show_target_kind: bool,
+    unit: SizeUnit,
+    frame: usize,
+    width: usize,
+) -> ListItem<'static> {
+    let indent = "  ".repeat(nesting_depth(&m.path, &app.targets));
+    if m.sizing {
+        let kind = if show_target_kind {
+            format!("[{}] ", m.target_kind)
+        } else {
+            String::new()
+        };
+        let path = app.display_path(
+            &m.path,
+            width.saturating_sub(indent.len() + kind.chars().count() + 16),
+        );
+        return ListItem::new(format!("{indent}{kind}{path} | calculating…"))
+            .style(Style::default().fg(app.theme.muted));
+    }
+    if let Some(error) = &m.error {
+        let path = app.display_path(
+            &m.path,
+            width.saturating_sub(indent.len() + error.chars().count() + 19),
+        );
+        return ListItem::new(format!("{indent}{path} | (size unknown: {error})")).style(
+            Style::default()
+                .fg(app.theme.muted)
+                .add_modifier(Modifier::DIM),
+        );
+    }
+    let style = if matches!(m.state, DeleteState::Failed(_)) {
+        Style::default()
+            .fg(app.theme.failed)
+            .add_modifier(Modifier::BOLD)
+    } else if m.is_removed() {
+        Style::default().fg(app.theme.deleted)
+    } else if m.is_system && m.protection.is_some() {
+        Style::default().fg(app.theme.protected_global)
+    } else if m.is_system {
+        Style::default().fg(app.theme.global)
+    } else if m.is_active {
+        Style::default().fg(app.theme.active)
+    } else if m.is_dangerous {
+        Style::default().fg(app.theme.dangerous)
+    } else {
+        Style::default()
+    };
+    let before = format!(
+        "{indent}{}{}{}{}{} {}{}",
+        if m.marked { "[x] " } else { "" },
+        m.tag
+            .map(|tag| format!("[{}] ", tag.label()))
+            .unwrap_or_default(),
+        app.root_label(&m.path)
+            .map(|root| format!("[{root}] "))
+            .unwrap_or_default(),
+        if show_target_kind {
+            format!("[{}] ", m.target_kind)
+        } else {
+            String::new()
+        },
+        match &m.state {
+            DeleteState::Present if m.is_system && m.protection.is_some() => {
+                String::from("[global]")
+            }
+            DeleteState::Present if m.protection.is_some() => {
+                String::from("[protected]")
+            }
+            DeleteState::Present if m.is_active => String::from("[active]"),
+            DeleteState::Present => String::new(),
+            DeleteState::Queued => String::from("[queued]"),
+            DeleteState::Deleting => {
+                format!("[deleting {}]", SPINNER[frame % SPINNER.len()])
+            }
+            DeleteState::Deleted => format!(
+                "[{}{}]",
+                if m.to_trash { "trashed" } else { "deleted" },
+                m.delete_time
+                    .map(|elapsed| format!(" in {elapsed:.1?}"))
+                    .unwrap_or_default()
+            ),
+            DeleteState::Failed(e) => format!("[failed: {e}]"),
+        },
+        m.reinstall_cost()
+            .filter(|_| m.state == DeleteState::Present)
+            .map(|cost| format!("[{cost}] "))
+            .unwrap_or_default(),
+        m.project
+            .as_ref()
+            .map(|project| format!("{project} | "))
+            .unwrap_or_default(),
+    );
+    let age = if app.absolute_dates {
+        modified_at(m.modified).format("%Y-%m-%d").to_string()
+    } else {
+        format_duration(m.modified)
+    };
+    let age = format!(" | {age} | ");
+    let size = format!("{} ", size_label(m, unit));
+    let path = app.display_path(
+        &m.path,
+        width.saturating_sub(before.chars().count() + age.chars().count() + size.chars().count()),
+    );
+    // Only the size takes the color of its band, leaving the row's own color to its state.
+    let size_style = if m.state == DeleteState::Present {
+        Style::default().fg(app.size_bands.color(m.size, &app.theme))
+    } else {
+        Style::default()
+    };
+    ListItem::new(Line::from(vec![
+        Span::raw(format!("{before}{path}{age}")),
+        Span::styled(size, size_style),
+    ]))
+    .style(style)
+}
+
+/// The largest directories as horizontal bars, as many as fit in `area`, each in the color of
+/// its size band.
+fn size_chart(app: &App, area: Rect, unit: SizeUnit) -> BarChart<'static> {
+    let largest = app.largest(usize::from(area.height.saturating_sub(2)));
+    let label_width = usize::from(area.width / 3);
+    let bars: Vec<Bar> = largest
+        .iter()
+        .map(|&i| {
+            let m = &app.modules[i];
+            Bar::default()
+                .value(m.size)
+                .label(Line::from(format!(
+                    "{:>9} {}",
+                    format_size(m.size, unit),
+                    app.display_path(&m.path, label_width)
+                )))
+                // The size is in the label, so that it doesn't hide short bars.
+                .text_value(String::new())
+                .style(Style::default().fg(app.size_bands.color(m.size, &app.theme)))
+        })
+        .collect();
+    BarChart::default()
+        .block(
+            Block::default()
+                .title(format!("Largest {} directories", bars.len()))
+                .borders(Borders::ALL),
+        )
+        .direction(Direction::Horizontal)
+        .bar_width(1)
+        .bar_gap(0)
+        .data(BarGroup::default().bars(&bars))
+}
+
+/// The row of the list standing for the collapsed workspace at `root` and its `members`.
+fn workspace_item(
+    app: &App,
+    root: &Path,
+    members: &[usize],
+    unit: SizeUnit,
+    width: usize,
+) -> ListItem<'static> {
+    let members: Vec<&NodeModule> = members.iter().map(|&i| &app.modules[i]).collect();
+    let removed = members.iter().filter(|m| m.is_removed()).count();
+    let size: u64 = members
+        .iter()
+        .filter(|m| !m.sizing && m.error.is_none() && !m.is_removed())
+        .map(|m| m.size)
+        .sum();
+    let before = format!(
+        "{}[workspace] ",
+        if members.iter().all(|m| m.marked) {
+            "[x] "
+        } else {
+            ""
+        }
+    );
+    let after = format!(
+        " | {} directories{} | ",
+        members.len(),
+        match removed {
+            0 => String::new(),
+            removed => format!(", {removed} deleted"),
+        },
+    );
+    let size_style = if removed < members.len() {
+        Style::default().fg(app.size_bands.color(size, &app.theme))
+    } else {
+        Style::default()
+    };
+    let size_span = Span::styled(format!("{} ", format_size(size, unit)), size_style);
+    let path = app.display_path(
+        root,
+        width.saturating_sub(before.chars().count() + after.chars().count() + size_span.width()),
+    );
+    let style = if removed == members.len() {
+        Style::default().fg(app.theme.deleted)
+    } else {
+        Style::default().add_modifier(Modifier::BOLD)
+    };
+    ListItem::new(Line::from(vec![
+        Span::raw(format!("{before}{path}{after}")),
+        size_span,
+    ]))
+    .style(style)
+}
+
+/// Hands the terminal back to the shell, e.g. while another program runs in it.
+fn suspend_terminal() -> std::io::Result<()> {
+    disable_raw_mode()?;
+    std::io::stdout()
+        .execute(DisableMouseCapture)?
+        .execute(LeaveAlternateScreen)?;
+    Ok(())
+}
+
+/// Takes the terminal back after [`suspend_terminal`], redrawing everything on the next frame.
+fn resume_terminal(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+) -> std::io::Result<()> {
+    enable_raw_mode()?;
+    std::io::stdout()
+        .execute(EnterAlternateScreen)?
+        .execute(EnableMouseCapture)?;
+    terminal.clear()
+}
+
+/// Runs the interactive interface, showing results as the scanner sends them over `rx`. Returns
+/// the project printed with `p`, if any, and what the session did.
 pub fn run_tui(
-    modules: Vec<NodeModule>,
+    mut rx: UnboundedReceiver<ScanEvent>,
+    mut dirs_scanned: Arc<AtomicUsize>,
+    cancel: CancelToken,
+    mut plan: Option<ScanPlan>,
+    disk: Option<DiskSpace>,
     args: Args,
     start: std::time::Instant,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(Option<PathBuf>, SessionSummary), Box<dyn std::error::Error>> {
     enable_raw_mode()?;
-    std::io::stdout().execute(EnterAlternateScreen)?;
+    std::io::stdout()
+        .execute(EnterAlternateScreen)?
+        .execute(EnableMouseCapture)?;
     let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
-    let mut app = App::new(modules, start);
-    let total_size_bytes = app.modules.iter().map(|m| m.size).sum();
-    let total_size = from_bytes(total_size_bytes, true);
-    loop {
+    // Kept across frames so clicks can be mapped back to rows.
+    let mut list_rect = Rect::default();
+    let mut delete_button: Option<Rect> = None;
+    let mut row_cache = RowCache::default();
+    let mut show_target_kind = args.targets().len() > 1 || args.caches;
+    let mut app = App::new(&args, disk, start);
+    app.cancel = cancel;
+    if let Some(path) = cache::tags_path() {
+        app.load_notes(path);
+    }
+    let mut frame = 0;
+    let mut watcher: Option<TargetWatcher> = None;
+    let mut watch_pending = args.watch;
+    let chosen = loop {
+        drain_scan_events(&mut app, &mut rx);
+        // Started once the scan is done, so that nothing it finds is reported twice.
+        if let Some(plan) = plan.as_ref().filter(|_| watch_pending && !app.scanning) {
+            watch_pending = false;
+            match plan.watch() {
+                Ok(started) => watcher = Some(started),
+                Err(e) => app.set_status(format!("Could not watch for new directories: {e}")),
+            }
+        }
+        if let Some(targets) = app.retarget.take() {
+            match plan.as_mut() {
+                Some(plan) => {
+                    let scanner = plan.scanner.retargeted(targets.clone());
+                    let started = tokio::runtime::Handle::current()
+                        .block_on(scanner.spawn_all(&plan.start_dirs));
+                    match started {
+                        Ok((new_rx, counter)) => {
+                            (rx, dirs_scanned) = (new_rx, counter);
+                            show_target_kind = targets.len() > 1 || args.caches;
+                            app.retarget_scan(targets, scanner.cancel_token());
+                            plan.scanner = scanner;
+                            // The watcher looks for the old names.
+                            watcher = None;
+                            watch_pending = args.watch;
+                        }
+                        Err(e) => app.set_status(format!("Could not scan again: {e}")),
+                    }
+                }
+                None => app.set_status(String::from("This list can't be scanned again")),
+            }
+        }
+        if let Some(watcher) = &mut watcher {
+            while let Ok(event) = watcher.rx.try_recv() {
+                apply_scan_event(&mut app, event);
+            }
+        }
+        app.drain_delete_events();
+        app.drain_details();
+        app.drain_recounts();
+        app.request_details();
+        app.dirs_scanned = dirs_scanned.load(Ordering::Relaxed);
+        frame += 1;
+
+        if app
+            .status
+            .as_ref()
+            .is_some_and(|(_, shown_at)| shown_at.elapsed() > STATUS_TIMEOUT)
+        {
+            app.status = None;
+        }
+
         terminal.draw(|f| {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Length(3), Constraint::Min(0)])
+                .constraints([
+                    Constraint::Length(3),
+                    Constraint::Length(if app.batch.is_some() { 3 } else { 0 }),
+                    Constraint::Length(if show_target_kind { 1 } else { 0 }),
+                    Constraint::Min(0),
+                    Constraint::Length(1),
+                ])
                 .split(f.area());
-            let size_metric = if args.in_gb { "GB" } else { "MB" };
-            let header = Table::new(
-                vec![Row::new(vec![
-                    format!("Total Size: {:.2}GB", total_size),
-                    format!("Modules: {}", app.modules.len()),
-                    format!("Scan Time: {:?}", app.scan_time),
+            // Too narrow for both, the list wins until the window grows again.
+            let (list_area, details_area) = if app.show_details && chunks[3].width >= MIN_DETAILS_WIDTH {
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                    .split(chunks[3]);
+                (columns[0], Some(columns[1]))
+            } else {
+                (chunks[3], None)
+            };
+            app.page_size = usize::from(list_area.height.saturating_sub(2)).max(1);
+            delete_button = None;
+            let unit = args.unit();
+            let (marked_count, marked_size) = app.marked_summary();
+            let mut header_cells = vec![
+                format!("Total Size: {}", format_size(app.total_size, unit)),
+                if app.filter.is_empty() {
+                    format!("Modules: {}", app.visible.len())
+                } else {
                     format!(
-                        "Total Deleted: {:.2}GB",
-                        from_bytes(app.total_deleted, true)
-                    ),
-                ])],
-                &[
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                ],
-            )
-            .block(Block::default().borders(Borders::ALL));
+                        "Showing {} of {}",
+                        app.visible.len(),
+                        app.modules.iter().filter(|m| !m.hidden).count()
+                    )
+                },
+                if app.scanning {
+                    format!(
+                        "{} Scanning: {} dirs",
+                        SPINNER[frame % SPINNER.len()],
+                        app.dirs_scanned
+                    )
+                } else if watcher.is_some() {
+                    format!("Watching (scan: {:?})", app.scan_time)
+                } else if app.cancel.is_cancelled() {
+                    format!("Cancelled after: {:?}", app.scan_time)
+                } else {
+                    format!("Scan Time: {:?}", app.scan_time)
+                },
+                format!("Total Deleted: {}", format_size(app.total_deleted, unit)),
+                format!("Skipped: {} (E)", app.skipped.len()),
+                String::from(if app.deleter.use_trash { "Mode: trash (T)" } else { "Mode: delete (T)" }),
+                format!("Marked: {} ({})", marked_count, format_size(marked_size, unit)),
+                match &app.sort {
+                    Some(sort) => format!("Sort: {} (s)", sort.label(app.sort_reversed)),
+                    None => String::from("Sort: found (s)"),
+                },
+            ];
+            let in_flight = app.deletions_in_flight();
+            if in_flight > 0 {
+                header_cells.push(format!(
+                    "Deleting: {in_flight} {}",
+                    SPINNER[frame % SPINNER.len()]
+                ));
+            }
+            if let Some(disk) = app.disk {
+                header_cells.push(format_disk_usage(disk, app.total_deleted));
+                header_cells.push(format_free_space(disk, app.total_deleted, marked_size));
+            }
+            let widths = vec![Constraint::Ratio(1, header_cells.len() as u32); header_cells.len()];
+            let header = Table::new(vec![Row::new(header_cells)], widths)
+                .block(Block::default().borders(Borders::ALL));
             f.render_widget(header, chunks[0]);
 
+            if let Some(batch) = &app.batch {
+                f.render_widget(
+                    Gauge::default()
+                        .block(Block::default().title("Deleting").borders(Borders::ALL))
+                        .gauge_style(Style::default().fg(app.theme.gauge))
+                        .ratio(batch.ratio())
+                        .label(batch_label(batch)),
+                    chunks[1],
+                );
+            }
+
+            if show_target_kind {
+                let tabs = app.tabs();
+                let titles = std::iter::once(format!("All {}", format_size(app.total_size, unit)))
+                    .chain(tabs.iter().map(|(name, size)| format!("{name} {}", format_size(*size, unit))));
+                let selected = app
+                    .tab
+                    .as_ref()
+                    .and_then(|tab| tabs.iter().position(|(name, _)| name == tab))
+                    .map_or(0, |i| i + 1);
+                f.render_widget(
+                    Tabs::new(titles)
+                        .select(selected)
+                        .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+                    chunks[2],
+                );
+            }
+
             if app.modules.is_empty() {
-                let message = Paragraph::new("No directories found")
+                let message = Paragraph::new(if app.scanning {
+                    "Scanning directories..."
+                } else {
+                    "No directories found"
+                })
+                    .block(Block::default().title("Directories").borders(Borders::ALL))
+                    .alignment(Alignment::Center);
+                f.render_widget(message, list_area);
+            } else if app.visible.is_empty() {
+                let message = Paragraph::new("No directories match the filter")
                     .block(Block::default().title("Directories").borders(Borders::ALL))
                     .alignment(Alignment::Center);
-                f.render_widget(message, chunks[1]);
+                f.render_widget(message, list_area);
+            } else if app.show_chart {
+                // Rows can't be clicked while the chart hides them.
+                list_rect = Rect::default();
+                f.render_widget(size_chart(&app, list_area, unit), list_area);
             } else {
-                let items: Vec<ListItem> = app
-                    .modules
-                    .iter_mut()
-                    .map(|m| {
-                        if args.delete_all && !m.deleted {
-                            m.delete();
-                            app.total_deleted += m.size;
-                        }
-                        let style = if m.deleted {
-                            Style::default().fg(Color::Red)
-                        } else if m.is_dangerous {
-                            Style::default().fg(Color::Yellow)
-                        } else {
-                            Style::default()
-                        };
-                        ListItem::new(format!(
-                            "{} {} | {} | {:.2}{size_metric} ",
-                            if m.deleted { "[deleted]" } else { "" },
-                            m.path.display(),
-                            format_duration(m.modified),
-                            from_bytes(m.size, args.in_gb),
+                let rows = app.rows();
+                let window = app.list_window(&rows, usize::from(list_area.height.saturating_sub(2)));
+                // Less the borders, the highlight symbol and the delete button.
+                let width =
+                    usize::from(list_area.width.saturating_sub(4 + DELETE_BUTTON.len() as u16));
+                let items: Vec<ListItem> = rows[window.clone()]
+                    .iter()
+                    .map(|row| match row {
+                        ListRow::Module(position) => row_cache.item(
+                            &app,
+                            app.visible[*position],
+                            show_target_kind,
+                            unit,
+                            frame,
+                            width,
+                        ),
+                        ListRow::Project { path, size, count } => ListItem::new(format!(
+                            "{} | {count} targets | {}",
+                            app.display_path(path, width.saturating_sub(24)),
+                            format_size(*size, unit),
                         ))
-                        .style(style)
+                        .style(
+                            Style::default()
+                                .fg(app.theme.muted)
+                                .add_modifier(Modifier::BOLD),
+                        ),
                     })
                     .collect();
+                list_rect = list_area;
+                // Only the window is drawn, so the selection is relative to its start.
+                let selected = rows[window]
+                    .iter()
+                    .position(|row| *row == ListRow::Module(app.scroll));
+                let mut list_state = ListState::default().with_selected(selected);
                 let modules_list = List::new(items)
                     .block(Block::default().title("Node Modules").borders(Borders::ALL))
                     .highlight_symbol("> ");
-                f.render_stateful_widget(
-                    modules_list,
-                    chunks[1],
-                    &mut ListState::default().with_selected(Some(app.scroll)),
+                f.render_stateful_widget(modules_list, list_area, &mut list_state);
+
+                let inner = list_area.inner(Margin::new(1, 1));
+                delete_button = selected
+                    .filter(|_| app.selected().is_some_and(|i| !app.modules[i].is_removed()))
+                    .map(|row| {
+                        let width = (DELETE_BUTTON.len() as u16).min(inner.width);
+                        Rect::new(inner.right() - width, inner.y + row as u16, width, 1)
+                    });
+                if let Some(area) = delete_button {
+                    f.render_widget(
+                        Paragraph::new(DELETE_BUTTON).style(Style::default().fg(app.theme.failed)),
+                        area,
+                    );
+                }
+            }
+
+            if let Some(area) = details_area {
+                let details = Paragraph::new(details_text(&app, unit))
+                    .wrap(Wrap { trim: false })
+                    .block(Block::default().title("Details (Tab)").borders(Borders::ALL));
+                f.render_widget(details, area);
+            }
+
+            let summary = app.state_summary();
+            let status_bar = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Min(0),
+                    Constraint::Length(summary.chars().count() as u16),
+                ])
+                .split(chunks[4]);
+            let hints = Span::styled(app.key_hints(), Style::default().fg(app.theme.muted));
+            let message = if app.filtering {
+                Paragraph::new(Line::from(vec![Span::raw(format!("/{}  ", app.filter)), hints]))
+            } else if let Some(input) = &app.target_input {
+                Paragraph::new(Line::from(vec![Span::raw(format!("Search for: {input}  ")), hints]))
+            } else if let Some(input) = &app.note_input {
+                Paragraph::new(Line::from(vec![Span::raw(format!("Note: {input}  ")), hints]))
+            } else if let Some((message, _)) = &app.status {
+                Paragraph::new(message.as_str()).style(Style::default().fg(app.theme.status))
+            } else {
+                Paragraph::new(Line::from(hints))
+            };
+            f.render_widget(message, status_bar[0]);
+            f.render_widget(Paragraph::new(summary), status_bar[1]);
+
+            if app.confirm_delete_marked {
+                let area = centered_rect(50, 20, f.area());
+                let prompt = Paragraph::new(format!(
+                    "Delete {marked_count} marked directories ({})?\n\nPress 'y' to confirm or any other key to cancel",
+                    format_size(marked_size, unit)
+                ))
+                .style(Style::default().fg(app.theme.warning))
+                .alignment(Alignment::Center)
+                .block(Block::default().title("Confirm").borders(Borders::ALL));
+                f.render_widget(Clear, area);
+                f.render_widget(prompt, area);
+            }
+
+            if let Some(members) = &app.pending_workspace {
+                let size: u64 = members.iter().map(|&i| app.modules[i].size).sum();
+                let root = app.modules[members[0]]
+                    .workspace
+                    .as_deref()
+                    .map(|root| root.display().to_string())
+                    .unwrap_or_default();
+                let area = centered_rect(50, 20, f.area());
+                let prompt = Paragraph::new(format!(
+                    "Delete the {} directories of the workspace {root} ({})?\n\nPress 'y' to confirm or any other key to cancel",
+                    members.len(),
+                    format_size(size, unit)
+                ))
+                .style(Style::default().fg(app.theme.warning))
+                .alignment(Alignment::Center)
+                .block(Block::default().title("Confirm").borders(Borders::ALL));
+                f.render_widget(Clear, area);
+                f.render_widget(prompt, area);
+            }
+
+            if let Some(module) = app.pending_delete.map(|i| &app.modules[i]) {
+                let area = centered_rect(60, 30, f.area());
+                let prompt = Paragraph::new(format!(
+                    "{}{}\n\nSize: {}\nLast modified: {} ago\n{}\nPress 'y' to delete or any other key to cancel",
+                    match (module.protection, app.confirmations_left) {
+                        (Some(_), 2) => "This directory is protected, confirm twice to delete it\n\n",
+                        (Some(_), _) => "Are you sure? This directory is protected\n\n",
+                        (None, _) => "",
+                    },
+                    module.path.display(),
+                    size_label(module, unit),
+                    format_duration(module.modified),
+                    module
+                        .warning
+                        .map(|warning| format!("\nWarning: {warning}\n"))
+                        .unwrap_or_default(),
+                ))
+                .style(Style::default().fg(app.theme.warning))
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: false })
+                .block(Block::default().title("Delete?").borders(Borders::ALL));
+                f.render_widget(Clear, area);
+                f.render_widget(prompt, area);
+            }
+
+            if let Some(module) = app.pending_exclude.map(|i| &app.modules[i]) {
+                let area = centered_rect(60, 25, f.area());
+                let prompt = Paragraph::new(format!(
+                    "{}\n\nHidden from the list. Press 'y' to also exclude it from future scans, or any other key to only hide it now",
+                    module.path.display(),
+                ))
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: false })
+                .block(Block::default().title("Exclude?").borders(Borders::ALL));
+                f.render_widget(Clear, area);
+                f.render_widget(prompt, area);
+            }
+
+            if let Some(module) = app.pending_elevation.map(|i| &app.modules[i]) {
+                let area = centered_rect(60, 25, f.area());
+                let prompt = Paragraph::new(format!(
+                    "{}\n\nCould not be deleted for lack of permission. Press 'y' to delete it as administrator, which may ask for your password, or any other key to cancel",
+                    module.path.display(),
+                ))
+                .style(Style::default().fg(app.theme.warning))
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: false })
+                .block(Block::default().title("Delete as administrator?").borders(Borders::ALL));
+                f.render_widget(Clear, area);
+                f.render_widget(prompt, area);
+            }
+
+            if let Some(index) = app.drill_down {
+                let area = centered_rect(70, 80, f.area());
+                let mut state = ListState::default().with_selected(Some(app.drill_scroll));
+                let list = List::new(drill_down_items(&app, index, unit))
+                    .block(
+                        Block::default()
+                            .title(format!(
+                                "{} (Enter/Esc to close)",
+                                app.modules[index].path.display()
+                            ))
+                            .borders(Borders::ALL),
+                    )
+                    .highlight_symbol("> ");
+                f.render_widget(Clear, area);
+                f.render_stateful_widget(list, area, &mut state);
+            }
+
+            if app.show_help {
+                let area = centered_rect(60, 80, f.area());
+                let help = Paragraph::new(help_text(&app))
+                    .wrap(Wrap { trim: false })
+                    .block(
+                        Block::default()
+                            .title("Help (any key to close)")
+                            .borders(Borders::ALL),
+                    );
+                f.render_widget(Clear, area);
+                f.render_widget(help, area);
+            }
+
+            if app.show_skipped {
+                let area = centered_rect(80, 60, f.area());
+                let items: Vec<ListItem> = if app.skipped.is_empty() {
+                    vec![ListItem::new("No directories were skipped")]
+                } else {
+                    app.skipped
+                        .iter()
+                        .map(|s| ListItem::new(format!("{} | {}", s.path.display(), s.reason)))
+                        .collect()
+                };
+                let skipped_list = List::new(items).block(
+                    Block::default()
+                        .title("Skipped Directories (E/Esc to close)")
+                        .borders(Borders::ALL),
                 );
+                f.render_widget(Clear, area);
+                f.render_widget(skipped_list, area);
             }
         })?;
 
-        if let Event::Key(key) = event::read()? {
-            if key.code == KeyCode::Char('q') {
-                break;
+        if !event::poll(Duration::from_millis(80))? {
+            continue;
+        }
+
+        match event::read()? {
+            // Raw mode swallows SIGINT, so Ctrl+C arrives as a key press.
+            Event::Key(key)
+                if key.code == KeyCode::Char('c')
+                    && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                if !app.scanning {
+                    break None;
+                }
+                app.cancel_scan();
+            }
+            Event::Key(key) => match key.code {
+                KeyCode::Char('y') if app.pending_elevation.is_some() => {
+                    if let Some((index, path)) = app.confirm_elevation() {
+                        // sudo asks for the password on the terminal.
+                        suspend_terminal()?;
+                        println!("Deleting {} as administrator", path.display());
+                        let result = deleter::remove_elevated(&path);
+                        resume_terminal(&mut terminal)?;
+                        app.finish_elevation(index, result);
+                    }
+                }
+                code if app.captures_input() => app.on_key(code),
+                code if app.show_chart && App::is_row_key(code) => app.on_key(code),
+                KeyCode::Char('q') => break None,
+                KeyCode::Char('p') => break app.selected_project(),
+                KeyCode::Char('R') if app.scanning => {
+                    app.set_status(String::from("Wait for the scan to finish to scan again"))
+                }
+                KeyCode::Char('R') => match &plan {
+                    Some(plan) => {
+                        let scanner = plan.scanner.restarted();
+                        let started = tokio::runtime::Handle::current()
+                            .block_on(scanner.spawn_all(&plan.start_dirs));
+                        match started {
+                            Ok((new_rx, counter)) => {
+                                (rx, dirs_scanned) = (new_rx, counter);
+                                app.restart_scan(scanner.cancel_token());
+                            }
+                            Err(e) => app.set_status(format!("Could not scan again: {e}")),
+                        }
+                    }
+                    None => app.set_status(String::from("This list can't be scanned again")),
+                },
+                KeyCode::Char('e') => {
+                    let project = app
+                        .selected_project()
+                        .filter(|_| app.is_local("Opening the project"));
+                    if let Some(project) = project {
+                        // Terminal editors need the screen back until they exit.
+                        suspend_terminal()?;
+                        let opened = open_in_editor(&project);
+                        resume_terminal(&mut terminal)?;
+                        app.set_status(match opened {
+                            Ok(()) => format!("Opened {} in the editor", project.display()),
+                            Err(e) => format!("Could not open {}: {e}", project.display()),
+                        });
+                    }
+                }
+                code => app.on_key(code),
+            },
+            // Repaint everything at the new size. The next frame lays the screen out again and
+            // scrolls the list to keep the selection in view.
+            Event::Resize(..) => {
+                terminal.autoresize()?;
+                terminal.clear()?;
             }
-            app.on_key(key.code);
+            Event::Mouse(mouse) if !app.captures_input() && !app.show_chart => match mouse.kind {
+                MouseEventKind::ScrollUp => app.move_selection(-MOUSE_SCROLL_LINES),
+                MouseEventKind::ScrollDown => app.move_selection(MOUSE_SCROLL_LINES),
+                MouseEventKind::Down(MouseButton::Left) => {
+                    let inner = list_rect.inner(Margin::new(1, 1));
+                    let position = Position::new(mouse.column, mouse.row);
+                    if delete_button.is_some_and(|button| button.contains(position)) {
+                        app.delete_module();
+                    } else if inner.contains(position) {
+                        let row = app.list_offset + usize::from(mouse.row - inner.y);
+                        app.click_row(row, std::time::Instant::now());
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
         }
+    };
+    suspend_terminal()?;
+    Ok((chosen, app.summary()))
+}
+
+/// Raw mode and the alternate screen of a prompt shown before the scan, given back to the shell
+/// when dropped, so also when the prompt fails half way.
+struct PromptScreen;
+
+impl PromptScreen {
+    fn enter() -> std::io::Result<Self> {
+        enable_raw_mode()?;
+        let screen = PromptScreen;
+        std::io::stdout().execute(EnterAlternateScreen)?;
+        Ok(screen)
     }
-    disable_raw_mode()?;
-    std::io::stdout().execute(LeaveAlternateScreen)?;
-    Ok(())
 }
 
-pub async fn display_spinner(scanning: Arc<AtomicBool>) -> std::io::Result<()> {
-    let spinner = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-    let mut i = 0;
+impl Drop for PromptScreen {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = std::io::stdout().execute(LeaveAlternateScreen);
+    }
+}
 
-    enable_raw_mode()?;
-    std::io::stdout().execute(EnterAlternateScreen)?;
+/// Lets the user browse from `start` to the directory to scan. `None` if they gave up.
+pub fn pick_root(
+    start: PathBuf,
+    theme: &Theme,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let _screen = PromptScreen::enter()?;
     let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    let mut picker = RootPicker::new(start);
+    let mut list_state = ListState::default();
 
-    while scanning.load(Ordering::Relaxed) {
+    let chosen = loop {
         terminal.draw(|f| {
-            let text = format!("{} Scanning directories...", spinner[i]);
-            let paragraph = Paragraph::new(text).alignment(Alignment::Center);
-            f.render_widget(paragraph, f.area());
-        })?;
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(1)])
+                .split(f.area());
 
-        i = (i + 1) % spinner.len();
-        tokio::time::sleep(Duration::from_millis(80)).await;
-    }
+            let items: Vec<ListItem> = match &picker.error {
+                Some(e) => vec![ListItem::new(format!("Could not read the directory: {e}"))],
+                None if picker.entries.is_empty() => vec![ListItem::new("No subdirectories")],
+                None => picker
+                    .entries
+                    .iter()
+                    .map(|name| ListItem::new(format!("{name}/")))
+                    .collect(),
+            };
+            list_state.select(Some(picker.selected));
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .title(format!("Scan from {}", picker.dir.display()))
+                        .borders(Borders::ALL),
+                )
+                .highlight_symbol("> ");
+            f.render_stateful_widget(list, chunks[0], &mut list_state);
 
-    disable_raw_mode()?;
-    std::io::stdout().execute(LeaveAlternateScreen)?;
-    Ok(())
+            let hint = Paragraph::new(
+                "Enter/→ open | ←/Backspace parent | s scan this directory | Esc cancel",
+            )
+            .style(Style::default().fg(theme.muted));
+            f.render_widget(hint, chunks[1]);
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            match picker.on_key(key.code) {
+                PickerAction::Browse => {}
+                PickerAction::Scan(dir) => break Some(dir),
+                PickerAction::Cancel => break None,
+            }
+        }
+    };
+    Ok(chosen)
 }
 
-pub fn confirm_delete_all(target: &str) -> Result<bool, Box<dyn std::error::Error>> {
-    enable_raw_mode()?;
-    std::io::stdout().execute(EnterAlternateScreen)?;
+pub fn confirm_delete_all(target: &str, theme: &Theme) -> Result<bool, Box<dyn std::error::Error>> {
+    let _screen = PromptScreen::enter()?;
     let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
 
     let confirmed = loop {
@@ -181,7 +1292,7 @@ pub fn confirm_delete_all(target: &str) -> Result<bool, Box<dyn std::error::Erro
             let warning = Paragraph::new(format!(
                 "⚠️  WARNING: You are about to delete ALL {target} directories!"
             ))
-            .style(Style::default().fg(Color::Yellow))
+            .style(Style::default().fg(theme.warning))
             .alignment(Alignment::Center)
             .block(Block::default().borders(Borders::ALL));
 
@@ -199,8 +1310,5 @@ pub fn confirm_delete_all(target: &str) -> Result<bool, Box<dyn std::error::Erro
             }
         }
     };
-
-    disable_raw_mode()?;
-    std::io::stdout().execute(LeaveAlternateScreen)?;
     Ok(confirmed)
 }
@@ -0,0 +1,125 @@
+//! Finding target directories created after the initial scan, for `--watch`.
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::{
+    fs::{is_listable_target, measure},
+    scanner::{ScanEvent, ScanPlan},
+};
+
+/// How long a new directory must go without changes before it is measured, so that e.g. a
+/// running `npm install` isn't measured half way.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+/// How often directories waiting to settle are checked.
+const TICK: Duration = Duration::from_millis(250);
+
+/// A running watch. Dropping it stops watching.
+pub struct TargetWatcher {
+    _watcher: RecommendedWatcher,
+    /// New target directories, as a `Matched` event followed by `Found` or `Dropped` once
+    /// their size is known.
+    pub rx: UnboundedReceiver<ScanEvent>,
+}
+
+impl ScanPlan {
+    /// Watches the starting directories for target directories created from now on.
+    pub fn watch(&self) -> notify::Result<TargetWatcher> {
+        let (raw_tx, raw_rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(raw_tx)?;
+        for dir in &self.start_dirs {
+            watcher.watch(dir, RecursiveMode::Recursive)?;
+        }
+
+        let (tx, rx) = unbounded_channel();
+        let options = self.scanner.options().clone();
+        std::thread::spawn(move || {
+            // New target directories and when something last changed inside them.
+            let mut settling: HashMap<PathBuf, Instant> = HashMap::new();
+            loop {
+                match raw_rx.recv_timeout(TICK) {
+                    Ok(Ok(event)) => {
+                        for path in event.paths {
+                            if let Some(changed) = settling
+                                .iter_mut()
+                                .find_map(|(dir, changed)| path.starts_with(dir).then_some(changed))
+                            {
+                                *changed = Instant::now();
+                            } else if matches!(event.kind, EventKind::Create(_))
+                                && is_listable_target(&path, &options)
+                            {
+                                if tx.send(ScanEvent::Matched(path.clone())).is_err() {
+                                    return;
+                                }
+                                settling.insert(path, Instant::now());
+                            }
+                        }
+                    }
+                    Ok(Err(_)) | Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+
+                let settled: Vec<PathBuf> = settling
+                    .iter()
+                    .filter(|(_, changed)| changed.elapsed() >= SETTLE_TIME)
+                    .map(|(path, _)| path.clone())
+                    .collect();
+                for path in settled {
+                    settling.remove(&path);
+                    let dir = measure(path, &options);
+                    let event = if dir.matches(&options) {
+                        ScanEvent::Found(dir)
+                    } else {
+                        ScanEvent::Dropped(dir.path)
+                    };
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(TargetWatcher {
+            _watcher: watcher,
+            rx,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{ScanOptions, Scanner};
+    use crate::testing::TempDir;
+
+    #[tokio::test]
+    async fn test_new_targets_are_reported_once_settled() {
+        let dir = TempDir::new("watch");
+        std::fs::create_dir_all(dir.join("app")).unwrap();
+        let root = std::fs::canonicalize(&dir).unwrap();
+
+        let mut watcher = ScanPlan {
+            scanner: Scanner::new(ScanOptions::default()),
+            start_dirs: vec![root.clone()],
+        }
+        .watch()
+        .unwrap();
+        std::fs::create_dir_all(root.join("app/node_modules/pkg")).unwrap();
+        std::fs::create_dir_all(root.join("app/src")).unwrap();
+
+        let target = root.join("app/node_modules");
+        assert!(
+            matches!(watcher.rx.recv().await, Some(ScanEvent::Matched(path)) if path == target)
+        );
+        assert!(
+            matches!(watcher.rx.recv().await, Some(ScanEvent::Found(dir)) if dir.path == target)
+        );
+    }
+}